@@ -0,0 +1,42 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use chipper::{Chip8, Opcode, RomBuilder};
+
+const CYCLES: u32 = 10_000;
+
+/// Run MAZE, which spends most of its time in `RAND`, `SKIP`, `DRAW` and `JUMP`
+fn workload(c: &mut Criterion) {
+    let rom = include_bytes!("../roms/MAZE");
+
+    let mut group = c.benchmark_group("workload");
+    group.throughput(Throughput::Elements(CYCLES as u64));
+    group.bench_function("maze", |b| b.iter_batched(
+        || Chip8::new_with_rom_slice(rom).with_seed(0xC8),
+        |mut chip8| chip8.run_cycles(CYCLES).unwrap(),
+        BatchSize::SmallInput,
+    ));
+    group.finish();
+}
+
+/// Draw a font sprite in a tight loop, moving it one pixel to the right each time
+fn draw(c: &mut Criterion) {
+    let mut rom = RomBuilder::new();
+    rom.push(Opcode::IndexFont { x: 0x2 });
+    rom.label("loop");
+    rom.push(Opcode::Draw { x: 0x0, y: 0x1, n: 0x5 });
+    rom.push(Opcode::AddConstant { x: 0x0, value: 0x1 });
+    rom.push(Opcode::Jump(rom.resolve("loop")));
+    let rom = rom.build().unwrap();
+
+    let mut group = c.benchmark_group("opcode");
+    group.throughput(Throughput::Elements(CYCLES as u64));
+    group.bench_function("draw", |b| b.iter_batched(
+        || Chip8::new_with_rom(rom.clone()),
+        |mut chip8| chip8.run_cycles(CYCLES).unwrap(),
+        BatchSize::SmallInput,
+    ));
+    group.finish();
+}
+
+criterion_group!(benches, workload, draw);
+criterion_main!(benches);
@@ -0,0 +1,178 @@
+use anyhow::{self, Context};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chipper::{Chip8, ChipperUI, QuirkProfile};
+
+const HELP: &str = "\
+Run a Chip-8 ROM
+
+USAGE:
+  chipper-cli [OPTIONS] <ROM>
+
+OPTIONS:
+  --hz <HZ>            Clock speed in cycles per second [default: 500]
+  --quirks <PROFILE>   Quirk profile: modern, chip8 or schip [default: from the ROM's
+                       extension, .ch8 is chip8 and .sc8 is schip, otherwise modern]
+  --strict             Reject opcodes the quirk profile's interpreter doesn't support
+  --seed <SEED>        Seed for the random number generator
+  --headless           Run without a window
+  --cycles <N>         Number of cycles to run in headless mode [default: 1000]
+  --dump-screen        Print the screen after running in headless mode
+  -h, --help           Print this message
+";
+
+struct Args {
+    rom: PathBuf,
+    hz: Option<f64>,
+    quirks: Option<QuirkProfile>,
+    strict: bool,
+    seed: Option<u64>,
+    headless: bool,
+    cycles: u32,
+    dump_screen: bool,
+}
+
+impl Args {
+    fn parse(mut args: pico_args::Arguments) -> anyhow::Result<Args> {
+        let parsed = Args {
+            hz: args.opt_value_from_str("--hz")?,
+            quirks: args.opt_value_from_str("--quirks")?,
+            strict: args.contains("--strict"),
+            seed: args.opt_value_from_str("--seed")?,
+            headless: args.contains("--headless"),
+            cycles: args.opt_value_from_str("--cycles")?.unwrap_or(1000),
+            dump_screen: args.contains("--dump-screen"),
+            rom: args.free_from_str()?,
+        };
+
+        let remaining = args.finish();
+        if !remaining.is_empty() {
+            anyhow::bail!("unexpected arguments: {:?}", remaining);
+        }
+        if let Some(hz) = parsed.hz {
+            if !hz.is_finite() || hz <= 0.0 {
+                anyhow::bail!("--hz must be a positive number, not {}", hz);
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn load_chip8(&self) -> anyhow::Result<Chip8> {
+        self.load_chip8_from(&self.rom)
+    }
+
+    /// Load the ROM at `rom` with the settings given on the command line
+    fn load_chip8_from(&self, rom: &Path) -> anyhow::Result<Chip8> {
+        let mut chip8 = Chip8::from_file(rom, self.quirks, None)
+            .with_context(|| format!("Failed to load ROM from path: {}", rom.display()))?;
+
+        if self.strict {
+            let profile = chip8.quirk_profile();
+            chip8 = chip8.with_strict_profile(profile);
+        }
+        if let Some(hz) = self.hz {
+            chip8.clock_speed = Duration::from_secs_f64(1.0 / hz);
+        }
+        if let Some(seed) = self.seed {
+            chip8 = chip8.with_seed(seed);
+        }
+
+        Ok(chip8)
+    }
+}
+
+/// Run `chip8` for up to `cycles` cycles, stopping early if the program halts. Each cycle also
+/// advances the timers by the time it would have taken.
+fn run_headless(chip8: &mut Chip8, cycles: u32) -> anyhow::Result<()> {
+    for _ in 0..cycles {
+        if chip8.is_halted() {
+            break;
+        }
+
+        chip8.tick(chip8.clock_speed)?;
+    }
+
+    Ok(())
+}
+
+fn run(args: Args) -> anyhow::Result<()> {
+    let mut chip8 = args.load_chip8()?;
+
+    if !args.headless {
+        let rom_loader = move |rom: &Path| {
+            Ok(args.load_chip8_from(rom)?.with_input_buffering(true))
+        };
+        return ChipperUI::run_with_rom_loader(chip8.with_input_buffering(true), Box::new(rom_loader));
+    }
+
+    run_headless(&mut chip8, args.cycles)?;
+    if args.dump_screen {
+        print!("{}", chip8.gpu.to_ascii());
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let mut args = pico_args::Arguments::from_env();
+    if args.contains(["-h", "--help"]) {
+        print!("{}", HELP);
+        return;
+    }
+
+    let result = Args::parse(args).and_then(run);
+    if let Err(e) = result {
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn parse(args: &[&str]) -> anyhow::Result<Args> {
+        Args::parse(pico_args::Arguments::from_vec(args.iter().map(OsString::from).collect()))
+    }
+
+    #[test]
+    pub fn parse_args() {
+        let args = parse(&["--hz", "1000", "--quirks", "schip", "--strict", "--seed", "42", "--headless", "--cycles", "10", "--dump-screen", "PONG"]).unwrap();
+
+        assert_eq!(args.rom, PathBuf::from("PONG"));
+        assert_eq!(args.hz, Some(1000.0));
+        assert_eq!(args.quirks, Some(QuirkProfile::SuperChip));
+        assert!(args.strict);
+        assert_eq!(args.seed, Some(42));
+        assert!(args.headless);
+        assert_eq!(args.cycles, 10);
+        assert!(args.dump_screen);
+    }
+
+    #[test]
+    pub fn parse_args_rejects_invalid_hz() {
+        for hz in &["0", "-500", "NaN", "inf"] {
+            let error = parse(&["--hz", hz, "PONG"]).err().unwrap();
+            assert!(error.to_string().starts_with("--hz must be a positive number"), "{}", error);
+        }
+    }
+
+    #[test]
+    pub fn parse_args_requires_rom() {
+        assert!(parse(&["--headless"]).is_err());
+    }
+
+    #[test]
+    pub fn headless_runs_demo_rom() {
+        let rom = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("roms").join("MAZE");
+        let args = parse(&["--headless", "--cycles", "2000", "--seed", "1", rom.to_str().unwrap()]).unwrap();
+
+        let mut chip8 = args.load_chip8().unwrap();
+        run_headless(&mut chip8, args.cycles).unwrap();
+
+        assert!(chip8.gpu.to_ascii().contains('#'));
+    }
+}
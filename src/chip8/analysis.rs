@@ -0,0 +1,411 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::ops::Range;
+
+use crate::chip8::{Opcode, Address, Register, MemorySize};
+
+/// Where execution can continue after the last opcode of a `BasicBlock`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Edge {
+    Address(Address),
+
+    /// The destination depends on the state of the machine, e.g. `JUMP addr, V0`
+    Unknown,
+}
+
+/// A sequence of opcodes that always execute in order. Only the first opcode can be jumped to
+/// and only the last opcode can transfer control elsewhere.
+#[derive(PartialEq, Debug, Clone)]
+pub struct BasicBlock {
+    pub start: Address,
+
+    pub opcodes: Vec<(Address, Opcode)>,
+
+    /// Everywhere execution can continue after this block. Calls include both the subroutine
+    /// and the return address.
+    pub successors: Vec<Edge>,
+}
+
+/// The result of `analyze`
+#[derive(PartialEq, Debug, Clone)]
+pub struct ControlFlowGraph {
+    /// Every block reachable from the entry point, ordered by address
+    pub blocks: Vec<BasicBlock>,
+
+    /// Ranges of the ROM that are never executed, which are usually sprites or other data
+    pub data: Vec<Range<Address>>,
+}
+
+/// A likely bug in a ROM found by `Chip8::validate_rom`
+#[derive(PartialEq, Debug, Clone)]
+pub enum RomWarning {
+    /// The ROM has an odd number of bytes, so its last byte can't be a complete opcode
+    OddLength { length: usize },
+
+    /// Execution can reach `address` but the word there isn't an opcode
+    UnsupportedOpcode { address: Address, word: u16 },
+
+    /// The `JUMP` or `CALL` at `address` targets an odd address, so every following opcode is
+    /// read misaligned
+    MisalignedTarget { address: Address, target: Address },
+
+    /// The `JUMP` or `CALL` at `address` targets an address outside of the ROM
+    TargetOutsideRom { address: Address, target: Address },
+
+    /// Execution continues past the end of the ROM after the opcode at `address`
+    RunsPastEnd { address: Address },
+
+    /// The `READ` or `WRITE` at `address` accesses memory past the end of the standard 4KB, given
+    /// the `IDX index` earlier in the same block
+    MemoryOverflow { address: Address, index: Address, x: Register },
+}
+
+impl ControlFlowGraph {
+    /// Returns the block starting at `address`, if any
+    pub fn block_at(&self, address: Address) -> Option<&BasicBlock> {
+        self.blocks.iter().find(|block| block.start == address)
+    }
+}
+
+/// Split `rom`, loaded and starting execution at `entry`, into basic blocks by following every
+/// path through the program.
+///
+/// Paths stop at a `RET`, at an `Edge::Unknown`, when they leave the ROM or when they reach bytes
+/// that aren't a valid opcode. Anything that isn't reached is reported as data.
+pub fn analyze(rom: &[u8], entry: Address) -> ControlFlowGraph {
+    let decode = |address: Address| -> Option<Opcode> {
+        let offset = address.checked_sub(entry)? as usize;
+        let bytes = rom.get(offset..offset + 2)?;
+        Opcode::from_bytes(&[bytes[0], bytes[1]]).ok()
+    };
+
+    // Find every reachable opcode and every address that must start a block
+    let mut opcodes = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    leaders.insert(entry);
+
+    let mut pending = vec![entry];
+    while let Some(address) = pending.pop() {
+        if opcodes.contains_key(&address) {
+            continue;
+        }
+
+        let opcode = match decode(address) {
+            Some(opcode) => opcode,
+            None => continue,
+        };
+
+        for edge in successors(address, &opcode) {
+            if let Edge::Address(next) = edge {
+                if opcode.is_control_flow() {
+                    leaders.insert(next);
+                }
+                pending.push(next);
+            }
+        }
+
+        opcodes.insert(address, opcode);
+    }
+
+    // Group consecutive opcodes into blocks
+    let mut blocks: Vec<BasicBlock> = Vec::new();
+    for (address, opcode) in opcodes.iter() {
+        let continues_block = blocks.last()
+            .and_then(|block| block.opcodes.last())
+            .map(|(last_address, last_opcode)| {
+                last_address.checked_add(2) == Some(*address) && !last_opcode.is_control_flow() && !leaders.contains(address)
+            })
+            .unwrap_or(false);
+
+        if !continues_block {
+            blocks.push(BasicBlock { start: *address, opcodes: Vec::new(), successors: Vec::new() });
+        }
+
+        let block = blocks.last_mut().unwrap();
+        block.opcodes.push((*address, opcode.clone()));
+        block.successors = successors(*address, opcode);
+    }
+
+    // Anything not covered by an opcode is data. A `Range<Address>` can't include the last
+    // address, so data stops just short of the end of the address space.
+    let rom_end = (entry as usize + rom.len()).min(Address::MAX as usize);
+    let mut data: Vec<Range<Address>> = Vec::new();
+    for address in (entry as usize)..rom_end {
+        let address = address as Address;
+        let is_code = opcodes.contains_key(&address) || (address > entry && opcodes.contains_key(&(address - 1)));
+        if is_code {
+            continue;
+        }
+
+        match data.last_mut() {
+            Some(range) if range.end == address => range.end += 1,
+            _ => data.push(address..address + 1),
+        }
+    }
+
+    ControlFlowGraph { blocks, data }
+}
+
+/// Check `rom`, loaded and starting execution at `entry`, for likely bugs. See `Chip8::validate_rom`.
+pub(crate) fn validate(rom: &[u8], entry: Address) -> Vec<RomWarning> {
+    let graph = analyze(rom, entry);
+    let rom_range = (entry as usize)..(entry as usize + rom.len());
+    let in_rom = |address: Address| rom_range.contains(&(address as usize));
+    let decoded: BTreeSet<Address> = graph.blocks.iter()
+        .flat_map(|block| block.opcodes.iter().map(|(address, _)| *address))
+        .collect();
+    let mut warnings = Vec::new();
+
+    for block in &graph.blocks {
+        // The value of `I` if this block set it
+        let mut index: Option<Address> = None;
+
+        for (address, opcode) in &block.opcodes {
+            match opcode {
+                Opcode::Jump(target) | Opcode::CallSubroutine(target) => {
+                    if target % 2 != 0 {
+                        warnings.push(RomWarning::MisalignedTarget { address: *address, target: *target });
+                    }
+                    if !in_rom(*target) {
+                        warnings.push(RomWarning::TargetOutsideRom { address: *address, target: *target });
+                    }
+                },
+                Opcode::IndexAddress(value) => index = Some(*value),
+                Opcode::WriteMemory { x } | Opcode::ReadMemory { x } => {
+                    if let Some(i) = index {
+                        if i as usize + *x as usize >= MemorySize::Standard.bytes() {
+                            warnings.push(RomWarning::MemoryOverflow { address: *address, index: i, x: *x });
+                        }
+                    }
+
+                    // `I` may have been incremented, see `ReadWriteIncrementQuirk`
+                    index = None;
+                },
+                Opcode::AddAddress { .. } | Opcode::IndexFont { .. } => index = None,
+                _ => {},
+            }
+        }
+
+        // Jumps and calls were reported above, anything else leaving the ROM falls off its end
+        let (last_address, last_opcode) = block.opcodes.last().unwrap();
+        if runs_past_address_space(*last_address, last_opcode) {
+            warnings.push(RomWarning::RunsPastEnd { address: *last_address });
+        }
+
+        for edge in &block.successors {
+            let target = match edge {
+                Edge::Address(target) => *target,
+                Edge::Unknown => continue,
+            };
+
+            if !in_rom(target) {
+                if last_opcode.target_address() != Some(target) {
+                    warnings.push(RomWarning::RunsPastEnd { address: *last_address });
+                }
+            } else if !decoded.contains(&target) {
+                let offset = (target - entry) as usize;
+                if let Some(bytes) = rom.get(offset..offset + 2) {
+                    let word = u16::from_be_bytes([bytes[0], bytes[1]]);
+                    warnings.push(RomWarning::UnsupportedOpcode { address: target, word });
+                }
+            }
+        }
+    }
+
+    warnings.sort_by_key(|warning| match warning {
+        RomWarning::UnsupportedOpcode { address, .. }
+        | RomWarning::MisalignedTarget { address, .. }
+        | RomWarning::TargetOutsideRom { address, .. }
+        | RomWarning::RunsPastEnd { address }
+        | RomWarning::MemoryOverflow { address, .. } => *address,
+        RomWarning::OddLength { .. } => Address::MAX,
+    });
+    warnings.dedup();
+
+    if rom.len() % 2 == 1 {
+        warnings.push(RomWarning::OddLength { length: rom.len() });
+    }
+
+    warnings
+}
+
+// Everywhere execution can continue after executing `opcode` at `address`
+//
+// Reachable code ends at the end of the address space, so addresses past `Address::MAX` are left
+// out. `runs_past_address_space` reports when that happens.
+fn successors(address: Address, opcode: &Opcode) -> Vec<Edge> {
+    unbounded_successors(address, opcode)
+        .into_iter()
+        .filter_map(|edge| match edge {
+            Some(target) => Address::try_from(target).ok().map(Edge::Address),
+            None => Some(Edge::Unknown),
+        })
+        .collect()
+}
+
+// True if execution can continue past `Address::MAX` after executing `opcode` at `address`
+fn runs_past_address_space(address: Address, opcode: &Opcode) -> bool {
+    unbounded_successors(address, opcode)
+        .into_iter()
+        .any(|edge| matches!(edge, Some(target) if target > Address::MAX as u32))
+}
+
+// Like `successors` but with addresses that can go past `Address::MAX`. `None` is `Edge::Unknown`.
+fn unbounded_successors(address: Address, opcode: &Opcode) -> Vec<Option<u32>> {
+    let next = address as u32 + 2;
+    let skipped = address as u32 + 4;
+
+    match opcode {
+        Opcode::Return | Opcode::Exit => vec![],
+        Opcode::Jump(target) => vec![Some(*target as u32)],
+        Opcode::JumpWithOffset(_) => vec![None],
+        Opcode::CallSubroutine(target) => vec![Some(*target as u32), Some(next)],
+        _ if opcode.is_control_flow() => vec![Some(next), Some(skipped)],
+        _ => vec![Some(next)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::{Chip8, RomBuilder};
+
+    #[test]
+    pub fn analyze_splits_at_jumps_and_finds_data() {
+        let mut rom = Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x1 },
+            Opcode::Jump(0x206),
+        ]);
+        rom.extend(vec![0xF0, 0x90]);
+        rom.extend(Opcode::to_rom(vec![
+            Opcode::AddConstant { x: 0x0, value: 0x1 },
+            Opcode::Return,
+        ]));
+
+        let graph = analyze(&rom, Chip8::PROGRAM_START);
+
+        assert_eq!(graph.blocks, vec![
+            BasicBlock {
+                start: 0x200,
+                opcodes: vec![(0x200, Opcode::LoadConstant { x: 0x0, value: 0x1 }), (0x202, Opcode::Jump(0x206))],
+                successors: vec![Edge::Address(0x206)],
+            },
+            BasicBlock {
+                start: 0x206,
+                opcodes: vec![(0x206, Opcode::AddConstant { x: 0x0, value: 0x1 }), (0x208, Opcode::Return)],
+                successors: vec![],
+            },
+        ]);
+        assert_eq!(graph.data, vec![0x204..0x206]);
+    }
+
+    #[test]
+    pub fn analyze_follows_both_sides_of_a_skip() {
+        let mut builder = RomBuilder::new();
+        builder.push(Opcode::SkipNextIfEqual { x: 0x0, value: 0x0 });
+        builder.push(Opcode::LoadConstant { x: 0x1, value: 0x1 });
+        builder.label("end");
+        builder.push(Opcode::Jump(builder.resolve("end")));
+        let rom = builder.build().unwrap();
+
+        let graph = analyze(&rom, Chip8::PROGRAM_START);
+
+        assert_eq!(graph.blocks.len(), 3);
+        assert_eq!(graph.block_at(0x200).unwrap().successors, vec![Edge::Address(0x202), Edge::Address(0x204)]);
+        assert_eq!(graph.block_at(0x202).unwrap().successors, vec![Edge::Address(0x204)]);
+        assert_eq!(graph.block_at(0x204).unwrap().successors, vec![Edge::Address(0x204)]);
+        assert!(graph.data.is_empty());
+    }
+
+    #[test]
+    pub fn analyze_treats_jump_with_offset_as_unknown() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::JumpWithOffset(0x300),
+            Opcode::ClearScreen,
+        ]);
+
+        let graph = analyze(&rom, Chip8::PROGRAM_START);
+
+        assert_eq!(graph.blocks.len(), 1);
+        assert_eq!(graph.blocks[0].successors, vec![Edge::Unknown]);
+        assert_eq!(graph.data, vec![0x202..0x204]);
+    }
+
+    #[test]
+    pub fn analyze_stops_at_end_of_address_space() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::SkipNextIfEqual { x: 0x0, value: 0x0 },
+            Opcode::SkipNextIfNotEqual { x: 0x0, value: 0x0 },
+        ]);
+
+        let graph = analyze(&rom, 0xFFFC);
+
+        assert_eq!(graph.block_at(0xFFFC).unwrap().successors, vec![Edge::Address(0xFFFE)]);
+        assert_eq!(graph.block_at(0xFFFE).unwrap().successors, vec![]);
+        assert_eq!(validate(&rom, 0xFFFC), vec![
+            RomWarning::RunsPastEnd { address: 0xFFFC },
+            RomWarning::RunsPastEnd { address: 0xFFFE },
+        ]);
+
+        let graph = analyze(&[0x00, 0xE0, 0xFF, 0xFF], 0xFFFC);
+        assert_eq!(graph.block_at(0xFFFC).unwrap().successors, vec![Edge::Address(0xFFFE)]);
+        assert_eq!(graph.data, vec![0xFFFE..0xFFFF]);
+    }
+
+    #[test]
+    pub fn validate_reports_likely_bugs() {
+        let mut rom = Opcode::to_rom(vec![
+            Opcode::IndexAddress(0xFFE),
+            Opcode::WriteMemory { x: 0x2 },
+            Opcode::SkipNextIfEqual { x: 0x0, value: 0x0 },
+            Opcode::Jump(0x209),
+            Opcode::CallSubroutine(0x20C),
+        ]);
+        rom.extend(vec![0xFF, 0xFF]);
+        rom.extend(Opcode::to_rom(vec![Opcode::ClearScreen]));
+
+        assert_eq!(validate(&rom, Chip8::PROGRAM_START), vec![
+            RomWarning::MemoryOverflow { address: 0x202, index: 0xFFE, x: 0x2 },
+            RomWarning::MisalignedTarget { address: 0x206, target: 0x209 },
+            RomWarning::UnsupportedOpcode { address: 0x209, word: 0x0CFF },
+            RomWarning::UnsupportedOpcode { address: 0x20A, word: 0xFFFF },
+            RomWarning::RunsPastEnd { address: 0x20C },
+        ]);
+    }
+
+    #[test]
+    pub fn validate_reports_odd_length() {
+        let mut rom = Opcode::to_rom(vec![Opcode::Jump(0x200)]);
+        rom.push(0x00);
+
+        assert_eq!(validate(&rom, Chip8::PROGRAM_START), vec![RomWarning::OddLength { length: 3 }]);
+    }
+
+    #[test]
+    pub fn validate_accepts_well_formed_rom() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x300),
+            Opcode::WriteMemory { x: 0xF },
+            Opcode::Jump(0x204),
+        ]);
+
+        assert!(validate(&rom, Chip8::PROGRAM_START).is_empty());
+    }
+
+    #[test]
+    pub fn analyze_includes_subroutines_and_return_address() {
+        let mut builder = RomBuilder::new();
+        builder.push_ref("subroutine", Opcode::CallSubroutine);
+        builder.push(Opcode::Return);
+        builder.label("subroutine");
+        builder.push(Opcode::ClearScreen);
+        builder.push(Opcode::Return);
+        let rom = builder.build().unwrap();
+
+        let graph = analyze(&rom, Chip8::PROGRAM_START);
+
+        assert_eq!(graph.block_at(0x200).unwrap().successors, vec![Edge::Address(0x204), Edge::Address(0x202)]);
+        assert!(graph.block_at(0x202).is_some());
+        assert!(graph.block_at(0x204).is_some());
+    }
+}
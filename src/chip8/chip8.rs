@@ -1,10 +1,20 @@
-use std::time::Duration;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
 
-use crate::chip8::{Opcode, Register, Address, Chip8Result, Chip8Error};
-use crate::chip8::quirks::{ReadWriteIncrementQuirk, BitShiftQuirk};
-use crate::chip8::gpu::{self, Gpu};
+use crate::chip8::{Opcode, Register, Address, Chip8Result, Chip8Error, MemoryBus, RomWarning};
+use crate::chip8::analysis;
+use crate::chip8::quirks::{Quirks, ReadWriteIncrementQuirk, BitShiftQuirk, SpriteWrapQuirk, LogicQuirk, MemoryBoundsQuirk, ResolutionSwitchQuirk, DrawModeQuirk, CollisionCountQuirk, Quirk, QuirkProfile};
+use crate::chip8::gpu::{Gpu, Resolution};
+use crate::chip8::input_script::InputScript;
+use crate::chip8::save_state::{StateReader, StateWriter};
+use crate::chip8::trace::TraceWriter;
+#[cfg(feature = "gif-recording")]
+use crate::chip8::gif_recorder::GifRecorder;
 
 /// `Chip8` is the core emulation structure of this project. It implements the memory and opcodes
 /// of the Chip-8 architecture.
@@ -15,7 +25,9 @@ pub struct Chip8 {
     /// - `0x200-0xFFF`: Program ROM and RAM
     ///
     /// We only use `0x050-0x0A0` in the reserved memory for the built in 4x5 pixel font set with digits (0-9) and letters (A-F)
-    pub memory: [u8; 4096],
+    ///
+    /// The size of memory is determined by `MemorySize`. Addresses wrap around the end of memory.
+    pub memory: Vec<u8>,
 
     /// Stack holds the addresses to return to when the current subroutine finishes.
     pub stack: Vec<u16>,
@@ -42,9 +54,14 @@ pub struct Chip8 {
     pub v: [u8; 16],
 
     /// Index Register: Generally used to store memory addresses which means only the lowest (rightmost) 12 bits are usually used
+    ///
+    /// Prefer `set_index`, which keeps `I` within memory.
     pub i: u16,
 
     /// Program Counter. Points to the currently executing address in `memory`
+    ///
+    /// Opcodes are normally 2-byte aligned but nothing enforces it: a jump to an odd address
+    /// fetches the word starting at that byte and carries on from there, as on real hardware.
     pub pc: u16,
 
     /// Delay Timer Register. When non-zero it decrements by 1 at the rate of 60hz.
@@ -62,12 +79,48 @@ pub struct Chip8 {
     /// `timer_speed` defines how often we decrement `delay_timer` and `sound_timer`
     pub timer_speed: Duration,
 
+    /// The pitch of the buzzer, reported by `beep`
+    beep_frequency_hz: f32,
+
+    /// The most time a single `tick` will run the CPU for, so a huge `delta` (e.g. after the
+    /// window was minimized) can't freeze the caller. Being time rather than a number of cycles,
+    /// it never limits how fast a high `clock_speed` runs during normal frames.
+    ///
+    /// The CPU skips any time past the limit but the timers still advance by it.
+    pub max_tick_duration: Duration,
+
     /// When `debug_mode` is true `tick` should do nothing. `step` needs to be used to advance the program.
     pub debug_mode: bool,
 
-    read_write_increment_quirk: ReadWriteIncrementQuirk,
+    quirks: Quirks,
+
+    /// Every quirk that affected an executed opcode, when tracking with `with_quirk_tracking`
+    quirks_exercised: Option<HashSet<Quirk>>,
+
+    /// When set, opcodes the profile's interpreter doesn't support fail with
+    /// `Chip8Error::UnsupportedOpcode` instead of being executed
+    strict_profile: Option<QuirkProfile>,
 
-    bit_shift_quirk: BitShiftQuirk,
+    /// The address ROMs are loaded at and execution begins from. Defaults to `PROGRAM_START`
+    start_address: Address,
+
+    /// The number of bytes of the most recently loaded ROM, starting from `start_address`
+    rom_size: usize,
+
+    /// Set when the program writes into the ROM region, cleared by `code_was_modified`
+    code_modified: bool,
+
+    /// When true key presses are latched in `buffered_keys` until the end of the next `tick`
+    input_buffering: bool,
+
+    /// Keys that have been pressed since the last `tick`, even if they have since been released
+    buffered_keys: [bool; 16],
+
+    /// The `cycle_count` at which each key was last pressed, see `key_pressed_at`
+    key_pressed_at: [Option<u64>; 16],
+
+    /// Key changes waiting for the end of the current frame, see `queue_key`
+    queued_keys: Vec<(u8, bool)>,
 
     /// Execution state, used to wait for keypresses
     state: Chip8State,
@@ -75,46 +128,184 @@ pub struct Chip8 {
     /// Random Number Generator used for `Opcode::Random`
     rng: ChaCha8Rng,
 
+    /// The seed `rng` was created from. `ChaCha8Rng` can't report its seed so we keep it for save states
+    rng_seed: RngSeed,
+
     /// Stores how much time has elapsed since our last `cycle()`
     clock_tick_accumulator: Duration,
 
     /// Stores how much time has elapsed since we last decreased `delay_timer` and `sound_timer`
     timer_tick_accumulator: Duration,
+
+    /// When `poll_tick` was last called
+    last_poll: Option<Instant>,
+
+    /// The number of times the timers have ticked, i.e. 60hz frames since the machine started
+    frame_count: u64,
+
+    /// The number of instructions executed since the machine started
+    cycle_count: u64,
+
+    /// Records every executed instruction, see `start_binary_trace`
+    binary_trace: Option<TraceWriter>,
+
+    /// Set when the display changes, cleared by `clear_redraw_flag`
+    needs_redraw: bool,
+
+    /// Called at the end of every frame, see `set_vblank_hook`
+    vblank_hook: Option<Box<dyn FnMut()>>,
+
+    /// Polled at the start of every `cycle` to refresh `keys`, see `set_input_source`
+    input_source: Option<Box<dyn FnMut() -> [bool; 16]>>,
+
+    /// The keys `input_source` returned when it was last polled, so only its changes are applied
+    input_source_keys: [bool; 16],
+
+    /// Handles the program's memory accesses when set, see `set_memory_bus`
+    memory_bus: Option<Box<dyn MemoryBus>>,
+
+    /// Runs opcodes that can't be decoded, see `set_custom_opcode_handler`
+    custom_opcode_handler: Option<Box<CustomOpcodeHandler>>,
+
+    /// Captures the display at the end of every frame, see `start_recording_gif`
+    #[cfg(feature = "gif-recording")]
+    gif_recorder: Option<GifRecorder>,
 }
 
 
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Debug)]
 enum Chip8State {
     Running,
-    WaitingForKey { target_register: Register }
+    WaitingForKey { target_register: Register },
+
+    /// The program executed `EXIT`, nothing runs from now on
+    Exited,
+}
+
+#[derive(Clone, Copy)]
+enum RngSeed {
+    U64(u64),
+    Bytes([u8; 32]),
+}
+
+impl RngSeed {
+    fn rng(&self) -> ChaCha8Rng {
+        let mut rng = match self {
+            RngSeed::U64(seed) => ChaCha8Rng::seed_from_u64(*seed),
+            RngSeed::Bytes(seed) => ChaCha8Rng::from_seed(*seed),
+        };
+
+        // `get_word_pos` underflows until the first block is generated, this generates it
+        // without moving the stream
+        rng.set_word_pos(0);
+        rng
+    }
+}
+
+/// What the buzzer should currently sound like, see `Chip8::beep`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Beep {
+    /// True while `sound_timer` is non-zero
+    pub playing: bool,
+
+    /// The pitch of the tone in hertz
+    pub frequency_hz: f32,
+}
+
+/// Runs an opcode `Chip8` doesn't know, see `Chip8::set_custom_opcode_handler`
+pub type CustomOpcodeHandler = dyn FnMut(&mut Chip8, u16) -> Chip8Result<Chip8Output>;
+
+/// The amount of RAM available to the interpreter.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MemorySize {
+    /// 4KB as used by the original Chip-8 and SUPER-CHIP
+    Standard,
+
+    /// 64KB as used by XO-CHIP
+    Extended,
+}
+
+impl MemorySize {
+    pub fn bytes(&self) -> usize {
+        match self {
+            MemorySize::Standard => 4096,
+            MemorySize::Extended => 65536,
+        }
+    }
+}
+
+impl Default for MemorySize {
+    fn default() -> MemorySize {
+        MemorySize::Standard
+    }
 }
 
-#[derive(PartialEq)]
+/// A summary of what happened during a `tick`.
+///
+/// Variants are ordered by precedence: when several things happen during a single `tick` the
+/// greatest one is reported, see `Chip8Output::combine`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 pub enum Chip8Output {
     None,
     Tick,
-    Redraw
+
+    /// The program has stopped making progress (e.g. it is stuck on a `JUMP` to itself).
+    Halted,
+
+    Redraw,
+}
+
+/// A single thing that happened while ticking the interpreter. Unlike `Chip8Output` a sequence
+/// of events preserves everything that happened during a `tick`, in order.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Chip8Event {
+    /// A CPU cycle was executed
+    Tick,
+
+    /// The display changed and should be redrawn
+    Redraw,
+
+    /// The sound timer was set, starting the buzzer
+    Sound,
+
+    /// The program started waiting for a key to be released
+    WaitingForKey,
+
+    /// The program has stopped making progress
+    Halted,
+}
+
+impl From<Chip8Event> for Chip8Output {
+    fn from(event: Chip8Event) -> Chip8Output {
+        match event {
+            Chip8Event::Tick => Chip8Output::Tick,
+            Chip8Event::Redraw => Chip8Output::Redraw,
+            Chip8Event::Halted => Chip8Output::Halted,
+            Chip8Event::Sound | Chip8Event::WaitingForKey => Chip8Output::None,
+        }
+    }
 }
 
 impl Chip8Output {
+    /// Returns whichever output has the higher precedence: `Redraw > Halted > Tick > None`.
+    ///
+    /// Since this is `max` the order outputs are combined in never matters.
     fn combine(x: Chip8Output, y: Chip8Output) -> Chip8Output {
-        match (x, y) {
-            (Chip8Output::Redraw, _) => Chip8Output::Redraw,
-            (_, Chip8Output::Redraw) => Chip8Output::Redraw,
-            (Chip8Output::Tick, _) => Chip8Output::Tick,
-            (_, Chip8Output::Tick) => Chip8Output::Tick,
-            _ => Chip8Output::None,
-        }
+        x.max(y)
     }
 }
 
 impl Chip8 {
     pub const PROGRAM_START: u16 = 0x200;
+
+    /// The size of standard memory. Memory can now be resized, see `memory_size`
+    #[deprecated(note = "use `MemorySize::Standard.bytes()` or `Chip8::memory_size` instead")]
     pub const MEMORY: u16 = 4096;
 
     const FONT_START: u16 = 0x50;
     const FONT_END: u16 = 0xA0;
+
     const FONTSET: [u8; 80] = [
         0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
         0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -134,6 +325,13 @@ impl Chip8 {
         0xF0, 0x80, 0xF0, 0x80, 0x80  // F
     ];
 
+    // Upper bound on the number of cycles `step_over`/`step_out` will run before giving up
+    const STEP_MAX_CYCLES: u32 = 100_000;
+
+    const DEFAULT_MAX_TICK_DURATION: Duration = Duration::from_millis(250);
+
+    const DEFAULT_BEEP_FREQUENCY_HZ: f32 = 440.0;
+
     pub fn new() -> Chip8 {
         let mut chip8 = Chip8::empty();
         chip8.pc = Chip8::PROGRAM_START;
@@ -146,10 +344,16 @@ impl Chip8 {
     }
 
     pub fn new_with_rom(rom_bytes: Vec<u8>) -> Chip8 {
+        Chip8::new_with_rom_slice(&rom_bytes)
+    }
+
+    /// Like `new_with_rom` but loads the ROM from a slice, which avoids an allocation
+    /// for ROMs embedded with `include_bytes!`.
+    ///
+    /// Panics if the ROM does not fit in memory. Use `load_rom` to handle this case.
+    pub fn new_with_rom_slice(rom_bytes: &[u8]) -> Chip8 {
         let mut chip8 = Chip8::new();
-        let rom_start = Chip8::PROGRAM_START as usize;
-        let rom_end = rom_start + rom_bytes.len();
-        chip8.memory[rom_start..rom_end].copy_from_slice(&rom_bytes[..]);
+        chip8.load_rom(rom_bytes).expect("Failed to load ROM");
         chip8
     }
 
@@ -161,10 +365,52 @@ impl Chip8 {
 
         Chip8::new_with_rom(default_rom)
     }
+
+    /// Load the ROM at `path`, choosing the quirks and memory size from its extension, see
+    /// `detect_profile`. `profile` and `memory_size` override what was detected.
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        profile: Option<QuirkProfile>,
+        memory_size: Option<MemorySize>,
+    ) -> Chip8Result<Chip8> {
+        let path = path.as_ref();
+        let (detected_profile, detected_memory_size) = Chip8::detect_profile(path);
+        let rom = fs::read(path)?;
+
+        let mut chip8 = Chip8::new()
+            .with_memory_size(memory_size.unwrap_or(detected_memory_size))
+            .with_quirks(profile.unwrap_or(detected_profile).quirks());
+        chip8.load_rom(&rom)?;
+
+        Ok(chip8)
+    }
+
+    /// The quirk profile and memory size a ROM expects, going by the extension of its `path`:
+    ///
+    /// - `.ch8`: `QuirkProfile::Chip8`
+    /// - `.sc8`: `QuirkProfile::SuperChip`
+    /// - `.xo8`: `QuirkProfile::Modern` with `MemorySize::Extended`
+    ///
+    /// Any other extension gets `QuirkProfile::Modern` with `MemorySize::Standard`.
+    pub fn detect_profile<P: AsRef<Path>>(path: P) -> (QuirkProfile, MemorySize) {
+        let extension = path.as_ref().extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("ch8") => (QuirkProfile::Chip8, MemorySize::Standard),
+            Some("sc8") => (QuirkProfile::SuperChip, MemorySize::Standard),
+            Some("xo8") => (QuirkProfile::Modern, MemorySize::Extended),
+            _ => (QuirkProfile::Modern, MemorySize::Standard),
+        }
+    }
+
     /// Returns a Chip8 with _no initialized memory_
     pub fn empty() -> Chip8 {
+        let rng_seed = RngSeed::Bytes(rand::random());
+
         Chip8 {
-            memory: [0; Chip8::MEMORY as usize],
+            memory: vec![0; MemorySize::default().bytes()],
             stack: Vec::new(),
             gpu: Gpu::new(),
             keys: [false; 16],
@@ -178,390 +424,3707 @@ impl Chip8 {
 
             clock_speed: Duration::from_secs_f64(1.0 / 500.0),
             timer_speed: Duration::from_secs_f64(1.0 / 60.0),
+            beep_frequency_hz: Chip8::DEFAULT_BEEP_FREQUENCY_HZ,
+            max_tick_duration: Chip8::DEFAULT_MAX_TICK_DURATION,
 
             debug_mode: false,
-            read_write_increment_quirk: ReadWriteIncrementQuirk::default(),
-            bit_shift_quirk: BitShiftQuirk::default(),
+            quirks: Quirks::default(),
+            strict_profile: None,
+            quirks_exercised: None,
+            start_address: Chip8::PROGRAM_START,
+            rom_size: 0,
+            code_modified: false,
+            input_buffering: false,
+            buffered_keys: [false; 16],
+            queued_keys: Vec::new(),
+            key_pressed_at: [None; 16],
 
             state: Chip8State::Running,
-            rng: ChaCha8Rng::from_entropy(),
+            rng: rng_seed.rng(),
+            rng_seed,
             clock_tick_accumulator: Duration::new(0, 0),
             timer_tick_accumulator: Duration::new(0, 0),
+            last_poll: None,
+            frame_count: 0,
+            cycle_count: 0,
+            binary_trace: None,
+            needs_redraw: false,
+            vblank_hook: None,
+            input_source: None,
+            input_source_keys: [false; 16],
+            memory_bus: None,
+            custom_opcode_handler: None,
+            #[cfg(feature = "gif-recording")]
+            gif_recorder: None,
         }
     }
 
-    pub fn with_seed(mut self, seed: u64) -> Self {
-        self.rng = ChaCha8Rng::seed_from_u64(seed);
-        self
+    /// Statically check `rom` for likely bugs before running it, e.g. jumps outside of the ROM
+    /// or opcodes that can't be decoded. Warnings are ordered by address, with
+    /// `RomWarning::OddLength` last.
+    ///
+    /// Only code reachable from `PROGRAM_START` is checked, see `analyze`, so sprites and other
+    /// data never cause warnings.
+    pub fn validate_rom(rom: &[u8]) -> Vec<RomWarning> {
+        analysis::validate(rom, Chip8::PROGRAM_START)
     }
 
-    pub fn with_read_write_increment_quirk(mut self, quirk: ReadWriteIncrementQuirk) -> Self {
-        self.read_write_increment_quirk = quirk;
+    /// Copy `rom_bytes` into memory starting at `start_address`.
+    pub fn load_rom(&mut self, rom_bytes: &[u8]) -> Chip8Result<()> {
+        let rom_start = self.start_address as usize;
+        let rom_end = rom_start + rom_bytes.len();
+        if rom_end > self.memory.len() {
+            return Err(Chip8Error::RomTooLarge(rom_bytes.len()));
+        }
+
+        self.memory[rom_start..rom_end].copy_from_slice(rom_bytes);
+        self.rom_size = rom_bytes.len();
+        self.code_modified = false;
+
+        Ok(())
+    }
+
+    /// Copy `bytes` into memory starting at `address`, e.g. to preload data tables after
+    /// `load_rom`. Unlike writes made by the program this doesn't mark the ROM as modified.
+    ///
+    /// Fails with `Chip8Error::MemoryOutOfBounds` without writing anything if `bytes` doesn't
+    /// fit between `address` and the end of memory.
+    pub fn write_memory_region(&mut self, address: Address, bytes: &[u8]) -> Chip8Result<()> {
+        let start = address as usize;
+        let end = start + bytes.len();
+        if end > self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds(start.max(self.memory.len())));
+        }
+
+        self.memory[start..end].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// A copy of all of memory, e.g. for diffing memory between runs. Restore it with `memory_load`.
+    pub fn memory_dump(&self) -> Vec<u8> {
+        self.memory.clone()
+    }
+
+    /// Replace all of memory with `memory`, which must be exactly `memory_size` bytes as
+    /// returned by `memory_dump`. Only memory is restored, registers and the display are left alone.
+    pub fn memory_load(&mut self, memory: &[u8]) -> Chip8Result<()> {
+        if memory.len() != self.memory.len() {
+            return Err(Chip8Error::MemorySizeMismatch { expected: self.memory.len(), actual: memory.len() });
+        }
+
+        self.memory.copy_from_slice(memory);
+        Ok(())
+    }
+
+    /// Route the program's memory accesses through `bus`, see `MemoryBus`.
+    pub fn set_memory_bus(&mut self, bus: Box<dyn MemoryBus>) {
+        self.memory_bus = Some(bus);
+    }
+
+    /// Remove the bus set by `set_memory_bus`, going back to accessing memory directly
+    pub fn clear_memory_bus(&mut self) {
+        self.memory_bus = None;
+    }
+
+    /// Run `handler` whenever `cycle` fetches a word that isn't a known opcode, instead of failing
+    /// with `Chip8Error::UnsupportedOpcode`. This allows experimenting with new opcodes, e.g. a
+    /// breakpoint or a print syscall, without changing `Opcode`.
+    ///
+    /// The handler is given the machine and the raw word. `pc` has already moved past the word,
+    /// so the handler only needs to change it to jump or skip. A handler that changes the display
+    /// should return `Chip8Output::Redraw`, which also sets `needs_redraw`.
+    pub fn set_custom_opcode_handler(&mut self, handler: Box<CustomOpcodeHandler>) {
+        self.custom_opcode_handler = Some(handler);
+    }
+
+    /// Remove the handler set by `set_custom_opcode_handler`
+    pub fn clear_custom_opcode_handler(&mut self) {
+        self.custom_opcode_handler = None;
+    }
+
+    /// Load ROMs at `address` and begin execution from there, e.g. `0x600` for ETI-660 ROMs.
+    ///
+    /// This should be called before loading a ROM, typically followed by `with_rom`.
+    pub fn with_start_address(mut self, address: Address) -> Self {
+        self.start_address = address;
+        self.pc = address;
         self
     }
 
-    pub fn with_bit_shift_quirk(mut self, quirk: BitShiftQuirk) -> Self {
-        self.bit_shift_quirk = quirk;
+    /// Load `rom_bytes` at `start_address`.
+    ///
+    /// Panics if the ROM does not fit in memory. Use `load_rom` to handle this case.
+    pub fn with_rom(mut self, rom_bytes: &[u8]) -> Self {
+        self.load_rom(rom_bytes).expect("Failed to load ROM");
         self
     }
 
-    pub fn key(&mut self, key: u8, pressed: bool) {
-        // Transition out of `WaitingForKey` when the correct key is released.
-        if let Chip8State::WaitingForKey { target_register } = self.state {
-            if pressed == false && self.keys[key as usize] == true {
-                self.v[target_register as usize] = key;
-                self.state = Chip8State::Running;
-            }
-        }
+    pub fn start_address(&self) -> Address {
+        self.start_address
+    }
 
-        self.keys[key as usize] = pressed;
+    /// The region of memory the ROM was loaded into. This includes any changes made by
+    /// self-modifying code.
+    pub fn rom(&self) -> &[u8] {
+        let start = self.start_address as usize;
+        &self.memory[start..start + self.rom_size]
     }
 
-    pub fn press_key(&mut self, key: u8) {
-        self.key(key, true);
+    /// Returns true if the program has written into the region of memory the ROM was loaded
+    /// into since the last call. Debuggers can use this to re-disassemble the program.
+    pub fn code_was_modified(&mut self) -> bool {
+        let modified = self.code_modified;
+        self.code_modified = false;
+        modified
     }
 
-    pub fn release_key(&mut self, key: u8) {
-        self.key(key, false);
+    /// When enabled a key that is pressed and released between two `tick`s is still seen
+    /// as pressed by the program during the next `tick`. Without buffering such a short tap
+    /// can be missed entirely because `tick` runs many cycles at once.
+    pub fn with_input_buffering(mut self, input_buffering: bool) -> Self {
+        self.input_buffering = input_buffering;
+        self
     }
 
-    /// Return (Address, Opcode) from the chip8 memory for all opcodes that fall
-    /// within `start_addr..end_addr`
-    pub fn opcodes(&self, start_addr: Address, end_addr: Address) -> Vec<(Address, Opcode)> {
-        let start_addr = start_addr as usize;
-        let end_addr = end_addr as usize;
+    /// Set the pitch hosts should play the buzzer at. Defaults to 440hz.
+    pub fn with_beep_frequency_hz(mut self, frequency_hz: f32) -> Self {
+        self.beep_frequency_hz = frequency_hz;
+        self
+    }
 
-        let mut result = Vec::new();
-        for opcode_addr in (start_addr..end_addr).step_by(2) {
-            let bytes = [self.memory[opcode_addr], self.memory[opcode_addr + 1]];
+    pub fn beep_frequency_hz(&self) -> f32 {
+        self.beep_frequency_hz
+    }
 
-            if let Ok(opcode) = Opcode::from_bytes(&bytes) {
-                result.push((opcode_addr as u16, opcode));
-            }
-        }
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng_seed = RngSeed::U64(seed);
+        self.rng = self.rng_seed.rng();
+        self
+    }
 
-        result
+    /// Resize memory to `size`, keeping anything already loaded (e.g. the font and ROM).
+    ///
+    /// Panics if the loaded ROM would no longer fit. Use `set_memory_size` to handle this case.
+    pub fn with_memory_size(mut self, size: MemorySize) -> Self {
+        self.set_memory_size(size).expect("Failed to resize memory");
+        self
     }
 
-    /// Tick the CPU forward by `delta` time. Depending on how much time
-    /// has elapsed this may:
+    /// Resize memory to `size`, keeping anything already loaded (e.g. the font and ROM).
     ///
-    /// - `cycle` some number of times based on the clock speed
-    /// - decrement `sound_timer`
-    /// - decrement `delay_timer`
-    pub fn tick(&mut self, delta: Duration) -> Chip8Result<Chip8Output> {
-        if self.debug_mode {
-            return Ok(Chip8Output::None)
+    /// Fails with `Chip8Error::RomTooLarge` without resizing if the loaded ROM doesn't fit in `size`.
+    pub fn set_memory_size(&mut self, size: MemorySize) -> Chip8Result<()> {
+        if self.start_address as usize + self.rom_size > size.bytes() {
+            return Err(Chip8Error::RomTooLarge(self.rom_size));
         }
 
-        self.tick_internal(delta)
+        self.memory.resize(size.bytes(), 0);
+        Ok(())
     }
 
-    /// Step the CPU forward by a fixed amount of time.
-    pub fn step(&mut self) -> Chip8Result<Chip8Output> {
-        self.tick_internal(self.clock_speed)
+    /// The number of bytes of addressable memory
+    pub fn memory_size(&self) -> usize {
+        self.memory.len()
     }
 
-    // Internal implementation of `tick` that ignores `debug_mode`
-    fn tick_internal(&mut self, delta: Duration) -> Chip8Result<Chip8Output> {
-        self.clock_tick_accumulator += delta;
+    /// Set `I` to `address`, dropping any bits past the end of memory. For example `0x1ABC`
+    /// becomes `0xABC` with 4KB of memory.
+    pub fn set_index(&mut self, address: u16) {
+        self.i = self.mask_address(address as usize) as u16;
+    }
 
-        let mut output = Chip8Output::None;
-        while self.clock_tick_accumulator >= self.clock_speed {
-            self.clock_tick_accumulator -= self.clock_speed;
-            self.timer_tick_accumulator += self.clock_speed;
-            if self.timer_tick_accumulator > self.timer_speed {
-                self.delay_timer = self.delay_timer.saturating_sub(1);
-                self.sound_timer = self.sound_timer.saturating_sub(1);
+    /// The value of `I`
+    pub fn index(&self) -> u16 {
+        self.i
+    }
 
-                self.timer_tick_accumulator -= self.timer_speed;
-            }
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.set_quirks(quirks);
+        self
+    }
 
-            let cycle_output = self.cycle()?;
-            output = Chip8Output::combine(output, Chip8Output::Tick);
-            output = Chip8Output::combine(output, cycle_output);
-        }
+    /// The profile whose quirks exactly match the current quirks, if any
+    pub fn quirk_profile(&self) -> Option<QuirkProfile> {
+        [QuirkProfile::Modern, QuirkProfile::Chip8, QuirkProfile::SuperChip].iter()
+            .copied()
+            .find(|profile| profile.quirks() == self.quirks)
+    }
 
-        Ok(output)
+    pub fn with_read_write_increment_quirk(mut self, quirk: ReadWriteIncrementQuirk) -> Self {
+        self.quirks.read_write_increment = quirk;
+        self
     }
 
+    pub fn with_bit_shift_quirk(mut self, quirk: BitShiftQuirk) -> Self {
+        self.quirks.bit_shift = quirk;
+        self
+    }
 
-    /// Execute one cycle of the chip8 interpreter.
-    pub fn cycle(&mut self) -> Chip8Result<Chip8Output> {
-        if self.state != Chip8State::Running {
-            return Ok(Chip8Output::None);
-        }
+    pub fn with_sprite_wrap_quirk(mut self, quirk: SpriteWrapQuirk) -> Self {
+        self.quirks.sprite_wrap = quirk;
+        self
+    }
 
-        let opcode = self.read_opcode()?;
-        self.pc += 2;
+    pub fn with_logic_quirk(mut self, quirk: LogicQuirk) -> Self {
+        self.quirks.logic = quirk;
+        self
+    }
 
-        self.execute_opcode(opcode.clone())?;
+    pub fn with_memory_bounds_quirk(mut self, quirk: MemoryBoundsQuirk) -> Self {
+        self.quirks.memory_bounds = quirk;
+        self
+    }
 
-        match opcode {
-            Opcode::Draw { x: _, y: _, n: _ } => Ok(Chip8Output::Redraw),
-            _ => Ok(Chip8Output::None),
-        }
+    pub fn with_resolution_switch_quirk(mut self, quirk: ResolutionSwitchQuirk) -> Self {
+        self.quirks.resolution_switch = quirk;
+        self.apply_resolution_switch_quirk();
+        self
     }
 
-    pub fn cycle_n(&mut self, times: u32) -> Chip8Result<()> {
-        for _ in 0..times {
-            self.cycle()?;
-        }
+    pub fn with_draw_mode_quirk(mut self, quirk: DrawModeQuirk) -> Self {
+        self.quirks.draw_mode = quirk;
+        self
+    }
 
-        Ok(())
+    pub fn with_collision_count_quirk(mut self, quirk: CollisionCountQuirk) -> Self {
+        self.quirks.collision_count = quirk;
+        self
     }
 
-    fn read_opcode(&self) -> Chip8Result<Opcode> {
-        let pc = self.pc as usize;
-        let opcode_bytes = [self.memory[pc], self.memory[pc+1]];
-        Opcode::from_bytes(&opcode_bytes)
+    /// Only allow the opcodes supported by `profile`, see `Opcode::is_supported_in`. This
+    /// doesn't change the quirks, use `with_quirks(profile.quirks())` for that.
+    pub fn with_strict_profile(mut self, profile: Option<QuirkProfile>) -> Self {
+        self.strict_profile = profile;
+        self
     }
 
-    fn execute_opcode(&mut self, opcode: Opcode) -> Chip8Result<()> {
-        match opcode {
-            // Flow Control
-            Opcode::CallSubroutine(address) => self.op_call_subroutine(address),
-            Opcode::Return => self.op_return()?,
-            Opcode::Jump(address) => self.pc = address,
-            Opcode::JumpWithOffset(address) => self.pc = address + (self.v[0] as u16),
+    pub fn strict_profile(&self) -> Option<QuirkProfile> {
+        self.strict_profile
+    }
 
-            // Conditional Execution
-            Opcode::SkipNextIfEqual { x, value } => self.op_skip_next_if(self.v[x as usize] == value),
-            Opcode::SkipNextIfNotEqual { x, value } => self.op_skip_next_if(self.v[x as usize] != value),
-            Opcode::SkipNextIfRegisterEqual { x, y } => self.op_skip_next_if(self.v[x as usize] == self.v[y as usize]),
-            Opcode::SkipNextIfRegisterNotEqual { x, y } => self.op_skip_next_if(self.v[x as usize] != self.v[y as usize]),
+    /// Record which quirks the opcodes executed from now on depend on, see `quirks_exercised`.
+    /// Turning tracking off forgets everything recorded so far.
+    pub fn with_quirk_tracking(mut self, track_quirks: bool) -> Self {
+        self.quirks_exercised = if track_quirks { Some(HashSet::new()) } else { None };
+        self
+    }
 
-            // Manipulate `Vx`
-            Opcode::LoadConstant { x, value } => self.v[x as usize] = value,
-            Opcode::Load { x, y } => self.v[x as usize] = self.v[y as usize],
-            Opcode::Or { x, y } => self.v[x as usize] = self.v[x as usize] | self.v[y as usize],
-            Opcode::And { x, y } => self.v[x as usize] = self.v[x as usize] & self.v[y as usize],
-            Opcode::Xor { x, y } => self.v[x as usize] = self.v[x as usize] ^ self.v[y as usize],
-            Opcode::Add { x, y } => self.op_add(x, y),
-            Opcode::AddConstant { x, value } => self.v[x as usize] = self.v[x as usize].wrapping_add(value),
-            Opcode::SubtractXY { x, y } => self.op_subtract(x, x, y),
-            Opcode::SubtractYX { x, y } => self.op_subtract(x, y, x),
-            Opcode::ShiftRight { x, y } => self.op_shift_right(x, y),
-            Opcode::ShiftLeft { x, y } => self.op_shift_left(x, y),
+    /// The quirks that could have changed the behavior of an opcode executed since tracking
+    /// was enabled with `with_quirk_tracking`, i.e. the quirk settings that matter for the
+    /// running ROM. Empty when not tracking.
+    pub fn quirks_exercised(&self) -> HashSet<Quirk> {
+        self.quirks_exercised.clone().unwrap_or_default()
+    }
 
-            // Manipulate `I`
-            Opcode::IndexAddress(address) => self.i = address,
-            Opcode::AddAddress { x } => self.i += self.v[x as usize] as u16,
-            Opcode::IndexFont { x } => self.i = Chip8::FONT_START + (self.v[x as usize] as u16 * 5),
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
 
-            // Manipulate Memory
-            Opcode::WriteMemory { x } => self.op_write_memory(x),
-            Opcode::ReadMemory { x } => self.op_read_memory(x),
-            Opcode::WriteBCD { x } => self.op_store_bcd(x),
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        self.apply_resolution_switch_quirk();
+    }
 
-            // IO Opcodes
-            Opcode::SkipIfKeyPressed { x } => self.op_skip_if_key_pressed(x),
-            Opcode::SkipIfKeyNotPressed { x } => self.op_skip_if_key_not_pressed(x),
-            Opcode::WaitForKeyRelease { x } => self.state = Chip8State::WaitingForKey { target_register: x },
-            Opcode::LoadDelayIntoRegister { x } => self.v[x as usize] = self.delay_timer,
-            Opcode::LoadRegisterIntoDelay { x } => self.delay_timer = self.v[x as usize],
-            Opcode::LoadRegisterIntoSound { x } => self.sound_timer = self.v[x as usize],
-            Opcode::Random { x, mask } => self.op_rand(x, mask),
-            Opcode::ClearScreen => self.gpu.clear(),
-            Opcode::Draw { x, y, n } => self.op_draw(x, y, n),
-        }
+    // `HIGH` leaves scaling up to the `Gpu`, so this quirk is the only place it's decided
+    fn apply_resolution_switch_quirk(&mut self) {
+        self.gpu.set_scale_on_switch(self.quirks.resolution_switch == ResolutionSwitchQuirk::Scale);
+    }
 
-        Ok(())
+    /// Returns the value of register `Vx`, or an error if `x` is not in `0x0..=0xF`.
+    pub fn register(&self, x: Register) -> Chip8Result<u8> {
+        self.v.get(x as usize).copied().ok_or(Chip8Error::InvalidRegister(x))
     }
 
-    fn op_call_subroutine(&mut self, address: Address) {
-        self.stack.push(self.pc);
-        self.pc = address;
+    /// Sets register `Vx` to `value`, or returns an error if `x` is not in `0x0..=0xF`.
+    pub fn set_register(&mut self, x: Register, value: u8) -> Chip8Result<()> {
+        let register = self.v.get_mut(x as usize).ok_or(Chip8Error::InvalidRegister(x))?;
+        *register = value;
+        Ok(())
     }
 
-    fn op_return(&mut self) -> Chip8Result<()> {
-        self.pc = self.stack.pop().ok_or(Chip8Error::StackUnderflow)?;
+    /// Press or release `key`. Only the low nibble of `key` is used, so `0x1A` is key `A`.
+    pub fn key(&mut self, key: u8, pressed: bool) {
+        let key = key & 0xF;
+
+        // Transition out of `WaitingForKey` when the correct key is released.
+        if let Chip8State::WaitingForKey { target_register } = self.state {
+            if !pressed && self.keys[key as usize] {
+                self.v[target_register as usize] = key;
+                self.state = Chip8State::Running;
+                // This release has been used up, so a following `WAIT.KEY` waits for a new one
+                self.buffered_keys[key as usize] = false;
+            }
+        }
+
+        // Only buffer fresh presses. Pressing a held key again (e.g. keyboard auto-repeat) after
+        // the buffer was cleared would otherwise outlive the key being released.
+        let was_pressed = self.keys[key as usize];
+        self.keys[key as usize] = pressed;
+        if pressed && !was_pressed && self.input_buffering {
+            self.buffered_keys[key as usize] = true;
+        }
+        if pressed && !was_pressed {
+            self.key_pressed_at[key as usize] = Some(self.cycle_count);
+        }
+    }
+
+    /// The `cycle_count` when `key` was most recently pressed, or `None` if it's never been
+    /// pressed. Comparing this with the cycle a ROM reacts at measures its input latency.
+    ///
+    /// Pressing a key that is already held doesn't count as a new press.
+    pub fn key_pressed_at(&self, key: u8) -> Option<u64> {
+        self.key_pressed_at[(key & 0xF) as usize]
+    }
+
+    // A key is considered pressed if it's held or, with input buffering, was tapped since the last tick.
+    //
+    // `SKIP.KEQ`/`SKIP.KNE` can ask about any value of `Vx`. Like most interpreters only the low
+    // nibble picks the key, so `0x20` checks key `0`.
+    fn is_key_pressed(&self, key: u8) -> bool {
+        let key = (key & 0xF) as usize;
+        self.keys[key] || self.buffered_keys[key]
+    }
+
+    pub fn press_key(&mut self, key: u8) {
+        self.key(key, true);
+    }
+
+    pub fn release_key(&mut self, key: u8) {
+        self.key(key, false);
+    }
+
+    /// Press or release `key` at the end of the current 60hz frame rather than immediately, the
+    /// way hardware that scans the keypad once per frame would see it. Changes are applied in
+    /// the order they were queued, after the timers tick.
+    ///
+    /// `press_key` and `release_key` still apply immediately, even in the middle of a `tick`.
+    pub fn queue_key(&mut self, key: u8, pressed: bool) {
+        self.queued_keys.push((key, pressed));
+    }
+
+    /// Pull the state of every key from `source` at the start of each `cycle`, e.g. so a script
+    /// can drive input by cycle number.
+    ///
+    /// Only keys the source changes since its previous poll are passed on to `key`, so this can
+    /// be mixed with `press_key`/`release_key` and `queue_key`: a key pressed by hand stays
+    /// pressed until either it's released or the source changes it. On the first poll every
+    /// key that differs from the current keypad counts as a change.
+    pub fn set_input_source(&mut self, source: Box<dyn FnMut() -> [bool; 16]>) {
+        self.input_source = Some(source);
+        self.input_source_keys = self.keys;
+    }
+
+    /// Remove the source set by `set_input_source`
+    pub fn clear_input_source(&mut self) {
+        self.input_source = None;
+    }
+
+    /// Drive the keys from `script`, starting with the next `cycle`. This is an input source, so
+    /// it replaces any source set by `set_input_source` and works with `cycle_n`, `run_cycles`
+    /// and `tick` alike. Once the script ends its final key states are held.
+    pub fn set_input_script(&mut self, script: InputScript) {
+        let mut cycle_offset = 0;
+        self.set_input_source(Box::new(move || {
+            let keys = script.keypad_at(cycle_offset);
+            cycle_offset += 1;
+            keys
+        }));
+    }
+
+    /// The keypad state with every key in `keys` pressed, e.g. `"5A"` presses keys `5` and `A`.
+    /// Handy for building input sources in tests.
+    pub fn keypad_from_hex_string(keys: &str) -> Chip8Result<[bool; 16]> {
+        let mut keypad = [false; 16];
+        for c in keys.chars() {
+            let key = c.to_digit(16)
+                .ok_or_else(|| Chip8Error::ParseError(format!("invalid key: {}", c)))?;
+            keypad[key as usize] = true;
+        }
+
+        Ok(keypad)
+    }
+
+    fn poll_input_source(&mut self) {
+        let keys = match self.input_source.as_mut() {
+            Some(source) => source(),
+            None => return,
+        };
+
+        for (key, pressed) in keys.iter().enumerate() {
+            if self.input_source_keys[key] != *pressed {
+                self.key(key as u8, *pressed);
+            }
+        }
+        self.input_source_keys = keys;
+    }
+
+    /// How long until `delay_timer` reaches zero at the current `timer_speed`
+    pub fn delay_remaining(&self) -> Duration {
+        self.timer_speed * self.delay_timer as u32
+    }
+
+    /// How long until `sound_timer` reaches zero, i.e. how much longer the buzzer will sound
+    pub fn sound_remaining(&self) -> Duration {
+        self.timer_speed * self.sound_timer as u32
+    }
+
+    /// Set `delay_timer` to the count closest to `remaining` at the current `timer_speed`,
+    /// up to the maximum of 255.
+    pub fn set_delay_remaining(&mut self, remaining: Duration) {
+        self.delay_timer = self.timer_count(remaining);
+    }
+
+    /// Set `sound_timer` to the count closest to `remaining` at the current `timer_speed`,
+    /// up to the maximum of 255.
+    pub fn set_sound_remaining(&mut self, remaining: Duration) {
+        self.sound_timer = self.timer_count(remaining);
+    }
+
+    fn timer_count(&self, remaining: Duration) -> u8 {
+        let count = remaining.as_secs_f64() / self.timer_speed.as_secs_f64();
+        count.round().min(u8::MAX as f64) as u8
+    }
+
+    /// Returns the 4x5 glyph `IndexFont` would point `I` at for the hex digit `digit & 0xF`.
+    ///
+    /// The glyph is read from memory, one row per entry with `1` for filled pixels and `0` for
+    /// empty ones.
+    pub fn font_glyph(&self, digit: u8) -> Vec<Vec<u8>> {
+        let glyph_start = Chip8::FONT_START as usize + (digit & 0xF) as usize * 5;
+
+        self.memory[glyph_start..glyph_start + 5]
+            .iter()
+            .map(|row| (0..4).map(|x| (row >> (7 - x)) & 0x1).collect())
+            .collect()
+    }
+
+    /// Returns the index of every key that is currently held, in ascending order.
+    pub fn keys_pressed(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..16).filter(move |key| self.keys[*key as usize])
+    }
+
+    /// Returns the `(width, height)` of the active display resolution in pixels.
+    pub fn screen_size(&self) -> (usize, usize) {
+        (self.gpu.width(), self.gpu.height())
+    }
+
+    /// Returns a human-readable report of the entire machine state, suitable for pasting into
+    /// bug reports.
+    pub fn dump_state(&self) -> String {
+        let mut dump = String::new();
+
+        let instruction = match self.peek_next_opcode() {
+            Ok(opcode) => opcode.to_assembly(),
+            Err(e) => e.to_string(),
+        };
+
+        // Writing to a `String` never fails so we can ignore the results
+        let _ = writeln!(dump, "PC = {:03X}  IX = {:03X}  ({})", self.pc, self.i, instruction);
+        let _ = writeln!(dump, "DT = {:02X}  ST = {:02X}", self.delay_timer, self.sound_timer);
+        for (row_index, registers) in self.v.chunks(8).enumerate() {
+            let line: Vec<String> = registers.iter().enumerate()
+                .map(|(i, value)| format!("V{:X} = {:02X}", row_index * 8 + i, value))
+                .collect();
+            let _ = writeln!(dump, "{}", line.join("  "));
+        }
+        let stack: Vec<String> = self.stack.iter().map(|address| format!("{:03X}", address)).collect();
+        let _ = writeln!(dump, "Stack = [{}]", stack.join(", "));
+        let _ = writeln!(dump, "State = {:?}", self.state);
+        let _ = writeln!(dump, "Quirks = {:?}", self.quirks);
+        let _ = writeln!(dump, "Display =");
+        let _ = write!(dump, "{:?}", self.gpu);
+
+        dump
+    }
+
+    /// Describe every difference between the state of `self` and `other`, one line per
+    /// difference using the names from `dump_state`, e.g. `V3 = 00 != AB`. Returns an empty list
+    /// if the machines are in the same state.
+    ///
+    /// Differing memory is reported as contiguous ranges, e.g. `Memory 300..302`, and the display
+    /// as the number of differing pixels.
+    pub fn diff(&self, other: &Chip8) -> Vec<String> {
+        let mut diff = Vec::new();
+
+        if self.pc != other.pc {
+            diff.push(format!("PC = {:03X} != {:03X}", self.pc, other.pc));
+        }
+        if self.i != other.i {
+            diff.push(format!("IX = {:03X} != {:03X}", self.i, other.i));
+        }
+        if self.delay_timer != other.delay_timer {
+            diff.push(format!("DT = {:02X} != {:02X}", self.delay_timer, other.delay_timer));
+        }
+        if self.sound_timer != other.sound_timer {
+            diff.push(format!("ST = {:02X} != {:02X}", self.sound_timer, other.sound_timer));
+        }
+        for (register, (a, b)) in self.v.iter().zip(other.v.iter()).enumerate() {
+            if a != b {
+                diff.push(format!("V{:X} = {:02X} != {:02X}", register, a, b));
+            }
+        }
+        if self.stack != other.stack {
+            diff.push(format!("Stack = {:03X?} != {:03X?}", self.stack, other.stack));
+        }
+        if self.state != other.state {
+            diff.push(format!("State = {:?} != {:?}", self.state, other.state));
+        }
+
+        if self.memory.len() != other.memory.len() {
+            diff.push(format!("Memory size = {} != {}", self.memory.len(), other.memory.len()));
+        }
+        let mut range_start = None;
+        let differs: Vec<bool> = self.memory.iter().zip(other.memory.iter()).map(|(a, b)| a != b).collect();
+        for (address, differs) in differs.iter().chain(std::iter::once(&false)).enumerate() {
+            match (range_start, differs) {
+                (None, true) => range_start = Some(address),
+                (Some(start), false) => {
+                    diff.push(format!("Memory {:03X}..{:03X}", start, address));
+                    range_start = None;
+                },
+                _ => {},
+            }
+        }
+
+        if self.gpu.resolution() != other.gpu.resolution() {
+            diff.push(format!("Display resolution = {:?} != {:?}", self.gpu.resolution(), other.gpu.resolution()));
+        } else {
+            let pixels = self.gpu.diff(&other.gpu).len();
+            if pixels > 0 {
+                diff.push(format!("Display = {} pixels differ", pixels));
+            }
+        }
+
+        diff
+    }
+
+    /// Serialize the entire machine state into a compact binary format that can be restored
+    /// with `load_state`.
+    ///
+    /// Only the machine itself is saved, not what the host attached to it: the vblank hook,
+    /// input source, memory bus, custom opcode handler, keys waiting in `queue_key`, binary
+    /// trace and GIF recording all need to be set up again after loading.
+    pub fn save_state(&self) -> Vec<u8> {
+        self.write_state(self.rng.get_word_pos())
+    }
+
+    // `save_state` with the random number generator at `rng_position`, so tests can write
+    // positions the generator can't reach itself
+    fn write_state(&self, rng_position: u128) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+
+        writer.bytes(&self.memory);
+        writer.u16(self.stack.len() as u16);
+        for address in &self.stack {
+            writer.u16(*address);
+        }
+
+        writer.bool(self.gpu.collision_tracking());
+        writer.bool(self.gpu.present_on_vblank());
+        writer.u8(match self.gpu.resolution() {
+            Resolution::LowRes => 0,
+            Resolution::HighRes => 1,
+        });
+        writer.fixed_bytes(self.gpu.pixels());
+        writer.u8(self.gpu.selected_planes());
+
+        for key in &self.keys {
+            writer.bool(*key);
+        }
+        writer.fixed_bytes(&self.v);
+        writer.u16(self.i);
+        writer.u16(self.pc);
+        writer.u8(self.delay_timer);
+        writer.u8(self.sound_timer);
+        writer.duration(self.clock_speed);
+        writer.duration(self.timer_speed);
+        writer.u32(self.beep_frequency_hz.to_bits());
+        writer.duration(self.max_tick_duration);
+        writer.bool(self.debug_mode);
+
+        writer.u8(match self.quirks.read_write_increment {
+            ReadWriteIncrementQuirk::InvariantIndex => 0,
+            ReadWriteIncrementQuirk::IncrementIndex => 1,
+        });
+        writer.u8(match self.quirks.bit_shift {
+            BitShiftQuirk::ShiftX => 0,
+            BitShiftQuirk::ShiftYIntoX => 1,
+        });
+        writer.bool(self.quirks.sprite_wrap.horizontal_wrap);
+        writer.bool(self.quirks.sprite_wrap.vertical_wrap);
+        writer.u8(match self.quirks.logic {
+            LogicQuirk::KeepVf => 0,
+            LogicQuirk::ResetVf => 1,
+        });
+        writer.u8(match self.quirks.memory_bounds {
+            MemoryBoundsQuirk::Wrap => 0,
+            MemoryBoundsQuirk::Error => 1,
+        });
+        writer.u8(match self.quirks.resolution_switch {
+            ResolutionSwitchQuirk::Clear => 0,
+            ResolutionSwitchQuirk::Scale => 1,
+        });
+        writer.u8(match self.quirks.draw_mode {
+            DrawModeQuirk::Xor => 0,
+            DrawModeQuirk::Or => 1,
+        });
+        writer.u8(match self.quirks.collision_count {
+            CollisionCountQuirk::Flag => 0,
+            CollisionCountQuirk::CountRows => 1,
+        });
+        writer.u8(match self.strict_profile {
+            None => 0,
+            Some(QuirkProfile::Modern) => 1,
+            Some(QuirkProfile::Chip8) => 2,
+            Some(QuirkProfile::SuperChip) => 3,
+        });
+
+        writer.u16(self.start_address);
+        writer.u32(self.rom_size as u32);
+        writer.bool(self.code_modified);
+        writer.bool(self.input_buffering);
+        for key in &self.buffered_keys {
+            writer.bool(*key);
+        }
+        for pressed_at in &self.key_pressed_at {
+            writer.bool(pressed_at.is_some());
+            writer.u64(pressed_at.unwrap_or(0));
+        }
+
+        match self.state {
+            Chip8State::Running => writer.u8(0),
+            Chip8State::WaitingForKey { target_register } => {
+                writer.u8(1);
+                writer.u8(target_register);
+            }
+            Chip8State::Exited => writer.u8(2),
+        }
+
+        match self.rng_seed {
+            RngSeed::U64(seed) => {
+                writer.u8(0);
+                writer.u64(seed);
+            },
+            RngSeed::Bytes(seed) => {
+                writer.u8(1);
+                writer.fixed_bytes(&seed);
+            }
+        }
+        writer.u128(rng_position);
+
+        writer.duration(self.clock_tick_accumulator);
+        writer.duration(self.timer_tick_accumulator);
+        writer.u64(self.frame_count);
+        writer.u64(self.cycle_count);
+
+        writer.into_bytes()
+    }
+
+    /// Restore a machine from the output of `save_state`.
+    ///
+    /// Returns `Chip8Error::InvalidSaveState` if `bytes` is not a save state, was written by an
+    /// incompatible version of chipper or decodes to a machine that can't run, e.g. a zero clock
+    /// speed or a ROM outside of memory.
+    pub fn load_state(bytes: &[u8]) -> Chip8Result<Chip8> {
+        let invalid = |message: &str| Chip8Error::InvalidSaveState(message.to_string());
+        let mut reader = StateReader::new(bytes)?;
+        let mut chip8 = Chip8::empty();
+
+        chip8.memory = reader.bytes()?.to_vec();
+        let memory_size = chip8.memory.len();
+        if memory_size != MemorySize::Standard.bytes() && memory_size != MemorySize::Extended.bytes() {
+            return Err(invalid("memory must be 4KB or 64KB"));
+        }
+
+        let stack_len = reader.u16()?;
+        for _ in 0..stack_len {
+            chip8.stack.push(reader.u16()?);
+        }
+
+        chip8.gpu = Gpu::new()
+            .with_collision_tracking(reader.bool()?)
+            .with_present_on_vblank(reader.bool()?);
+        chip8.gpu.set_resolution(match reader.u8()? {
+            0 => Resolution::LowRes,
+            1 => Resolution::HighRes,
+            _ => return Err(invalid("unknown resolution")),
+        });
+        let (width, height) = chip8.screen_size();
+        let pixels = reader.fixed_bytes(width * height)?;
+        for (index, pixel) in pixels.iter().enumerate() {
+            *chip8.gpu.pixel(index % width, index / width) = *pixel;
+        }
+        chip8.gpu.present();
+        chip8.gpu.select_planes(reader.u8()?);
+
+        for key in chip8.keys.iter_mut() {
+            *key = reader.bool()?;
+        }
+        chip8.v.copy_from_slice(reader.fixed_bytes(16)?);
+        chip8.i = reader.u16()?;
+        chip8.pc = reader.u16()?;
+        chip8.delay_timer = reader.u8()?;
+        chip8.sound_timer = reader.u8()?;
+        chip8.clock_speed = reader.duration()?;
+        chip8.timer_speed = reader.duration()?;
+        if chip8.clock_speed == Duration::from_secs(0) || chip8.timer_speed == Duration::from_secs(0) {
+            return Err(invalid("clock and timer speeds must be non-zero"));
+        }
+        chip8.beep_frequency_hz = f32::from_bits(reader.u32()?);
+        chip8.max_tick_duration = reader.duration()?;
+        chip8.debug_mode = reader.bool()?;
+
+        chip8.quirks.read_write_increment = match reader.u8()? {
+            0 => ReadWriteIncrementQuirk::InvariantIndex,
+            1 => ReadWriteIncrementQuirk::IncrementIndex,
+            _ => return Err(invalid("unknown read/write increment quirk")),
+        };
+        chip8.quirks.bit_shift = match reader.u8()? {
+            0 => BitShiftQuirk::ShiftX,
+            1 => BitShiftQuirk::ShiftYIntoX,
+            _ => return Err(invalid("unknown bit shift quirk")),
+        };
+        chip8.quirks.sprite_wrap.horizontal_wrap = reader.bool()?;
+        chip8.quirks.sprite_wrap.vertical_wrap = reader.bool()?;
+        chip8.quirks.logic = match reader.u8()? {
+            0 => LogicQuirk::KeepVf,
+            1 => LogicQuirk::ResetVf,
+            _ => return Err(invalid("unknown logic quirk")),
+        };
+        chip8.quirks.memory_bounds = match reader.u8()? {
+            0 => MemoryBoundsQuirk::Wrap,
+            1 => MemoryBoundsQuirk::Error,
+            _ => return Err(invalid("unknown memory bounds quirk")),
+        };
+        chip8.quirks.resolution_switch = match reader.u8()? {
+            0 => ResolutionSwitchQuirk::Clear,
+            1 => ResolutionSwitchQuirk::Scale,
+            _ => return Err(invalid("unknown resolution switch quirk")),
+        };
+        chip8.apply_resolution_switch_quirk();
+        chip8.quirks.draw_mode = match reader.u8()? {
+            0 => DrawModeQuirk::Xor,
+            1 => DrawModeQuirk::Or,
+            _ => return Err(invalid("unknown draw mode quirk")),
+        };
+        chip8.quirks.collision_count = match reader.u8()? {
+            0 => CollisionCountQuirk::Flag,
+            1 => CollisionCountQuirk::CountRows,
+            _ => return Err(invalid("unknown collision count quirk")),
+        };
+        chip8.strict_profile = match reader.u8()? {
+            0 => None,
+            1 => Some(QuirkProfile::Modern),
+            2 => Some(QuirkProfile::Chip8),
+            3 => Some(QuirkProfile::SuperChip),
+            _ => return Err(invalid("unknown strict profile")),
+        };
+
+        chip8.start_address = reader.u16()?;
+        chip8.rom_size = reader.u32()? as usize;
+        if chip8.start_address as usize + chip8.rom_size > memory_size {
+            return Err(invalid("rom must fit in memory"));
+        }
+        chip8.code_modified = reader.bool()?;
+        chip8.input_buffering = reader.bool()?;
+        for key in chip8.buffered_keys.iter_mut() {
+            *key = reader.bool()?;
+        }
+        for pressed_at in chip8.key_pressed_at.iter_mut() {
+            let was_pressed = reader.bool()?;
+            let cycle = reader.u64()?;
+            *pressed_at = if was_pressed { Some(cycle) } else { None };
+        }
+
+        chip8.state = match reader.u8()? {
+            0 => Chip8State::Running,
+            1 => Chip8State::WaitingForKey { target_register: reader.u8()? },
+            2 => Chip8State::Exited,
+            _ => return Err(invalid("unknown execution state")),
+        };
+        if let Chip8State::WaitingForKey { target_register } = chip8.state {
+            if target_register > 0xF {
+                return Err(invalid("key must be waited for into V0 through VF"));
+            }
+        }
+
+        chip8.rng_seed = match reader.u8()? {
+            0 => RngSeed::U64(reader.u64()?),
+            1 => {
+                let mut seed = [0; 32];
+                seed.copy_from_slice(reader.fixed_bytes(32)?);
+                RngSeed::Bytes(seed)
+            },
+            _ => return Err(invalid("unknown rng seed")),
+        };
+        chip8.rng = chip8.rng_seed.rng();
+        let rng_position = reader.u128()?;
+        if rng_position > u64::MAX as u128 {
+            return Err(invalid("random number generator position out of range"));
+        }
+        chip8.rng.set_word_pos(rng_position);
+
+        chip8.clock_tick_accumulator = reader.duration()?;
+        chip8.timer_tick_accumulator = reader.duration()?;
+        chip8.frame_count = reader.u64()?;
+        chip8.cycle_count = reader.u64()?;
+        // The host hasn't seen the restored display yet
+        chip8.needs_redraw = true;
+
+        Ok(chip8)
+    }
+
+    /// Write `save_state` to the file at `path`
+    pub fn save_state_to_file<P: AsRef<Path>>(&self, path: P) -> Chip8Result<()> {
+        fs::write(path, self.save_state())?;
+        Ok(())
+    }
+
+    /// Restore a machine from a file written by `save_state_to_file`
+    pub fn load_state_from_file<P: AsRef<Path>>(path: P) -> Chip8Result<Chip8> {
+        let bytes = fs::read(path)?;
+        Chip8::load_state(&bytes)
+    }
+
+    /// Return (Address, Opcode) from the chip8 memory for all opcodes that fall
+    /// within `start_addr..end_addr`
+    pub fn opcodes(&self, start_addr: Address, end_addr: Address) -> Vec<(Address, Opcode)> {
+        let start_addr = start_addr as usize;
+        let end_addr = end_addr as usize;
+
+        let mut result = Vec::new();
+        for opcode_addr in (start_addr..end_addr).step_by(2) {
+            let bytes = [self.memory[self.mask_address(opcode_addr)], self.memory[self.mask_address(opcode_addr + 1)]];
+
+            if let Ok(opcode) = Opcode::from_bytes(&bytes) {
+                result.push((opcode_addr as u16, opcode));
+            }
+        }
+
+        result
+    }
+
+    /// Decode the opcode at `pc` without executing it or advancing `pc`
+    ///
+    /// The two bytes are read from `pc` and `pc + 1` even when `pc` is odd, wrapping around to
+    /// the start of memory past the end.
+    pub fn peek_next_opcode(&self) -> Chip8Result<Opcode> {
+        let pc = self.pc as usize;
+        let opcode_bytes = [self.memory[self.mask_address(pc)], self.memory[self.mask_address(pc + 1)]];
+        Opcode::from_bytes(&opcode_bytes)
+    }
+
+    /// Like `peek_next_opcode` but also returns the address the opcode was read from, i.e. `pc`
+    pub fn current_instruction(&self) -> Chip8Result<(Address, Opcode)> {
+        Ok((self.pc, self.peek_next_opcode()?))
+    }
+
+    /// The sprite data the next opcode will draw if it's a `DRAW`, i.e. the `n` rows for each
+    /// selected plane starting at `I`. In high resolution `n == 0` is a SUPER-CHIP 16x16 sprite,
+    /// which is 32 bytes for each selected plane. Returns `None` for any other opcode.
+    ///
+    /// This reads memory directly, bypassing any `MemoryBus`.
+    pub fn current_sprite(&self) -> Option<Vec<u8>> {
+        let len = match self.peek_next_opcode() {
+            Ok(Opcode::Draw { n, .. }) => self.sprite_len(n),
+            _ => return None,
+        };
+
+        Some(
+            (0..len)
+                .map(|offset| self.memory[self.mask_address(self.i as usize + offset)])
+                .collect()
+        )
+    }
+
+    /// Return the addresses targeted by a `JUMP` or `CALL` within `start_addr..end_addr`.
+    ///
+    /// Debuggers can use this to mark likely subroutine entry points in a disassembly.
+    pub fn jump_targets(&self, start_addr: Address, end_addr: Address) -> HashSet<Address> {
+        self.opcodes(start_addr, end_addr)
+            .iter()
+            .filter_map(|(_, opcode)| opcode.target_address())
+            .collect()
+    }
+
+    /// Returns true if the program can no longer make progress.
+    ///
+    /// Many Chip-8 programs finish by executing a `JUMP` to the address of the jump itself,
+    /// which loops forever without changing any state. SUPER-CHIP programs can also stop
+    /// with `EXIT`.
+    pub fn is_halted(&self) -> bool {
+        if self.state == Chip8State::Exited {
+            return true;
+        }
+
+        match self.peek_next_opcode() {
+            Ok(Opcode::Jump(address)) => address == self.pc,
+            _ => false,
+        }
+    }
+
+    /// Returns the address `pc` will hold after the next `cycle`, without executing anything.
+    ///
+    /// Skips, jumps, calls and returns are resolved using the current registers, keys and
+    /// stack. While waiting for a key `pc` doesn't move. Fails if the next opcode can't be
+    /// decoded or would `RET` with an empty stack.
+    pub fn next_pc(&self) -> Chip8Result<u16> {
+        if self.state != Chip8State::Running {
+            return Ok(self.pc);
+        }
+
+        let next = self.pc.wrapping_add(2);
+        let skip_if = |condition: bool| if condition { next.wrapping_add(2) } else { next };
+        let v = |x: Register| self.v[x as usize];
+
+        let pc = match self.peek_next_opcode()? {
+            Opcode::CallSubroutine(address) | Opcode::Jump(address) => address,
+            Opcode::JumpWithOffset(address) => self.jump_with_offset_target(address)?,
+            Opcode::Return => *self.stack.last().ok_or(Chip8Error::StackUnderflow)?,
+            Opcode::SkipNextIfEqual { x, value } => skip_if(v(x) == value),
+            Opcode::SkipNextIfNotEqual { x, value } => skip_if(v(x) != value),
+            Opcode::SkipNextIfRegisterEqual { x, y } => skip_if(v(x) == v(y)),
+            Opcode::SkipNextIfRegisterNotEqual { x, y } => skip_if(v(x) != v(y)),
+            Opcode::SkipIfKeyPressed { x } => skip_if(self.is_key_pressed(v(x))),
+            Opcode::SkipIfKeyNotPressed { x } => skip_if(!self.is_key_pressed(v(x))),
+            _ => next,
+        };
+
+        Ok(pc)
+    }
+
+    /// Returns true if the display has changed since the last `clear_redraw_flag`, e.g. because
+    /// of a `DRAW` or `CLEAR`, or the end of a frame when presenting on vblank.
+    ///
+    /// This is an alternative to checking every `Chip8Output` for `Chip8Output::Redraw`. Any
+    /// number of changes between two checks only need a single redraw.
+    pub fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    /// Acknowledge that the host has drawn the current display
+    pub fn clear_redraw_flag(&mut self) {
+        self.needs_redraw = false;
+    }
+
+    /// Returns true while the buzzer should sound, i.e. `sound_timer` is non-zero
+    pub fn is_sound_playing(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Describes the tone a host should currently be playing
+    pub fn beep(&self) -> Beep {
+        Beep {
+            playing: self.is_sound_playing(),
+            frequency_hz: self.beep_frequency_hz,
+        }
+    }
+
+    /// Pause or resume `tick`, the same as setting `debug_mode`.
+    ///
+    /// Resuming discards any partial cycle or timer period left over from before the pause, so
+    /// the first `tick` afterwards starts from a clean slate instead of catching up.
+    pub fn set_paused(&mut self, paused: bool) {
+        if self.debug_mode && !paused {
+            self.clock_tick_accumulator = Duration::new(0, 0);
+            self.timer_tick_accumulator = Duration::new(0, 0);
+        }
+
+        self.debug_mode = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.debug_mode
+    }
+
+    /// Tick the CPU forward by `delta` time. Depending on how much time
+    /// has elapsed this may:
+    ///
+    /// - `cycle` some number of times based on the clock speed
+    /// - decrement `sound_timer`
+    /// - decrement `delay_timer`
+    pub fn tick(&mut self, delta: Duration) -> Chip8Result<Chip8Output> {
+        if self.debug_mode {
+            return Ok(Chip8Output::None)
+        }
+
+        self.tick_internal(delta)
+    }
+
+    /// Like `tick` but works out `delta` itself from the time of the previous `poll_tick`, so an
+    /// event loop or async task can call it whenever it wakes up without tracking time itself.
+    ///
+    /// The first call only records `now`. Time that passes while paused is skipped rather than
+    /// caught up on, the same as with `tick`.
+    pub fn poll_tick(&mut self, now: Instant) -> Chip8Result<Chip8Output> {
+        let delta = match self.last_poll.replace(now) {
+            Some(last_poll) => now.saturating_duration_since(last_poll),
+            None => return Ok(Chip8Output::None),
+        };
+
+        self.tick(delta)
+    }
+
+    /// Like `tick` but returns every event that happened during the elapsed time
+    /// rather than collapsing them into a single `Chip8Output`.
+    pub fn tick_events(&mut self, delta: Duration) -> Chip8Result<Vec<Chip8Event>> {
+        if self.debug_mode {
+            return Ok(Vec::new())
+        }
+
+        self.tick_internal_events(delta)
+    }
+
+    /// Run exactly `cycles` cycles and then decrement `sound_timer` and `delay_timer`
+    /// `timer_ticks` times, ignoring `clock_speed`, `timer_speed` and how much time has passed.
+    ///
+    /// Unlike `tick` the result only depends on the arguments, which makes it suitable for
+    /// deterministic tests or for keeping several machines in lockstep.
+    pub fn tick_cycles(&mut self, cycles: u32, timer_ticks: u32) -> Chip8Result<Chip8Output> {
+        if self.debug_mode {
+            return Ok(Chip8Output::None)
+        }
+
+        let mut output = Chip8Output::None;
+        for _ in 0..cycles {
+            let cycle_output = Chip8Output::combine(Chip8Output::Tick, self.cycle()?);
+            output = Chip8Output::combine(output, cycle_output);
+        }
+
+        for _ in 0..timer_ticks {
+            self.end_frame();
+        }
+
+        if timer_ticks > 0 && self.gpu.present_on_vblank() {
+            output = Chip8Output::combine(output, Chip8Output::Redraw);
+        }
+
+        if cycles > 0 {
+            self.buffered_keys = [false; 16];
+        }
+
+        Ok(output)
+    }
+
+    /// Run `cycles` cycles as fast as possible, ticking the timers as often as `clock_speed`
+    /// and `timer_speed` say they would have ticked while those cycles ran.
+    ///
+    /// Like `tick_cycles` this never sleeps or reads the clock, so the same ROM and seed always
+    /// give the same result. Time towards the next timer tick carries into the next call, and
+    /// into `tick`, so running a few cycles at a time still ticks the timers at `timer_speed`.
+    pub fn run_cycles(&mut self, cycles: u32) -> Chip8Result<Chip8Output> {
+        if self.debug_mode {
+            return Ok(Chip8Output::None)
+        }
+
+        let mut output = Chip8Output::None;
+        for _ in 0..cycles {
+            // Each cycle takes `clock_speed`, and the timers tick before a cycle that starts after them
+            if self.advance_timers(self.clock_speed) > 0 && self.gpu.present_on_vblank() {
+                output = Chip8Output::combine(output, Chip8Output::Redraw);
+            }
+
+            let cycle_output = Chip8Output::combine(Chip8Output::Tick, self.cycle()?);
+            output = Chip8Output::combine(output, cycle_output);
+        }
+
+        if cycles > 0 {
+            self.buffered_keys = [false; 16];
+        }
+
+        Ok(output)
+    }
+
+    /// Step the CPU forward by a fixed amount of time.
+    pub fn step(&mut self) -> Chip8Result<Chip8Output> {
+        self.tick_internal(self.clock_speed)
+    }
+
+    /// Call `step` `n` times, returning the combined output. Like `step` this runs even in
+    /// `debug_mode`.
+    pub fn step_n(&mut self, n: u32) -> Chip8Result<Chip8Output> {
+        let mut output = Chip8Output::None;
+        for _ in 0..n {
+            output = Chip8Output::combine(output, self.step()?);
+        }
+
+        Ok(output)
+    }
+
+    /// Step the CPU forward, treating a `CallSubroutine` as a single step by running
+    /// until the stack returns to its current depth.
+    pub fn step_over(&mut self) -> Chip8Result<Chip8Output> {
+        let depth = self.stack.len();
+        self.step_while(|chip8| chip8.stack.len() > depth)
+    }
+
+    /// Step the CPU forward until the current subroutine returns. Behaves like `step`
+    /// when not inside a subroutine.
+    pub fn step_out(&mut self) -> Chip8Result<Chip8Output> {
+        let depth = self.stack.len();
+        self.step_while(|chip8| depth > 0 && chip8.stack.len() >= depth)
+    }
+
+    // Steps at least once and then keeps stepping while `condition` holds, giving up
+    // after `STEP_MAX_CYCLES` so a subroutine that never returns can't hang the caller.
+    fn step_while<F>(&mut self, condition: F) -> Chip8Result<Chip8Output>
+        where F: Fn(&Chip8) -> bool
+    {
+        let mut output = self.step()?;
+        let mut cycles = 1;
+        while condition(self) && cycles < Chip8::STEP_MAX_CYCLES {
+            if self.state != Chip8State::Running || self.is_halted() {
+                break;
+            }
+
+            output = Chip8Output::combine(output, self.step()?);
+            cycles += 1;
+        }
+
+        Ok(output)
+    }
+
+    // Internal implementation of `tick` that ignores `debug_mode`
+    fn tick_internal(&mut self, delta: Duration) -> Chip8Result<Chip8Output> {
+        let output = self.tick_internal_events(delta)?
+            .into_iter()
+            .map(Chip8Output::from)
+            .fold(Chip8Output::None, Chip8Output::combine);
+
+        Ok(output)
+    }
+
+    // Internal implementation of `tick_events` that ignores `debug_mode`
+    //
+    // The clock and the timers accumulate `delta` independently so the timers always run at
+    // `timer_speed` regardless of `clock_speed`. We walk through `delta` one event at a time so
+    // that cycles and timer decrements still happen in the order they would in real time.
+    fn tick_internal_events(&mut self, delta: Duration) -> Chip8Result<Vec<Chip8Event>> {
+        let mut remaining = delta.min(self.max_tick_duration);
+        let dropped = delta - remaining;
+        let mut cycles = 0;
+
+        let mut events = Vec::new();
+        loop {
+            let until_timer = self.timer_speed.checked_sub(self.timer_tick_accumulator).unwrap_or_default();
+            let until_cycle = self.clock_speed.checked_sub(self.clock_tick_accumulator).unwrap_or_default();
+            // A zero `timer_speed` stops the timers, so they never end a step early
+            let step = if self.timer_speed == Duration::new(0, 0) {
+                until_cycle
+            } else {
+                until_timer.min(until_cycle)
+            };
+            if step > remaining {
+                self.clock_tick_accumulator += remaining;
+                self.advance_timers(remaining);
+                break;
+            }
+
+            remaining -= step;
+            self.clock_tick_accumulator += step;
+            // The display only changes at the end of a frame when presenting on vblank
+            if self.advance_timers(step) > 0 && self.gpu.present_on_vblank() {
+                events.push(Chip8Event::Redraw);
+            }
+
+            if self.clock_tick_accumulator < self.clock_speed {
+                continue;
+            }
+            self.clock_tick_accumulator -= self.clock_speed;
+
+            let was_running = self.state == Chip8State::Running;
+            let was_silent = self.sound_timer == 0;
+
+            let cycle_output = self.cycle()?;
+            cycles += 1;
+            events.push(Chip8Event::Tick);
+            match cycle_output {
+                Chip8Output::Redraw => events.push(Chip8Event::Redraw),
+                Chip8Output::Halted => events.push(Chip8Event::Halted),
+                _ => {}
+            }
+
+            if was_silent && self.sound_timer > 0 {
+                events.push(Chip8Event::Sound);
+            }
+
+            let waiting = matches!(self.state, Chip8State::WaitingForKey { .. });
+            if was_running && waiting {
+                events.push(Chip8Event::WaitingForKey);
+            }
+        }
+
+        if dropped > Duration::new(0, 0) {
+            // The CPU doesn't catch up on dropped time, even in the next tick, but the timers
+            // still count down in real time
+            self.clock_tick_accumulator = Duration::new(0, 0);
+            if self.skip_timers(dropped) > 0 && self.gpu.present_on_vblank() {
+                events.push(Chip8Event::Redraw);
+            }
+        }
+
+        // Buffered keys only need to survive until the program has had a chance to run
+        if cycles > 0 {
+            self.buffered_keys = [false; 16];
+        }
+
+        Ok(events)
+    }
+
+    // Add `elapsed` to the time since the timers last ticked and tick them once for every whole
+    // `timer_speed` that has built up, returning how many times they ticked.
+    //
+    // Only whole periods are subtracted so the fraction left over carries into the next call.
+    // This keeps the timers in phase with real time: e.g. at 60hz three calls of 10ms tick once,
+    // on the second call, and leave 13.3ms towards the next tick.
+    //
+    // A zero `timer_speed` stops the timers instead of ticking them forever.
+    fn advance_timers(&mut self, elapsed: Duration) -> u32 {
+        if self.timer_speed == Duration::new(0, 0) {
+            return 0;
+        }
+
+        self.timer_tick_accumulator += elapsed;
+
+        let mut ticks = 0;
+        while self.timer_tick_accumulator >= self.timer_speed {
+            self.timer_tick_accumulator -= self.timer_speed;
+            self.end_frame();
+            ticks += 1;
+        }
+
+        ticks
+    }
+
+    // Like `advance_timers` but ends every whole frame in `elapsed` at once, so skipping hours
+    // only presents, records and calls the vblank hook for the last frame.
+    fn skip_timers(&mut self, elapsed: Duration) -> u64 {
+        if self.timer_speed == Duration::new(0, 0) {
+            return 0;
+        }
+
+        self.timer_tick_accumulator += elapsed;
+
+        let period = self.timer_speed.as_nanos();
+        let frames = (self.timer_tick_accumulator.as_nanos() / period) as u64;
+        if frames > 0 {
+            let leftover = self.timer_tick_accumulator.as_nanos() % period;
+            self.timer_tick_accumulator = Duration::from_nanos(leftover as u64);
+            self.end_frames(frames);
+        }
+
+        frames
+    }
+
+    // Called every time the timers tick, which is once per 60hz frame
+    fn end_frame(&mut self) {
+        self.end_frames(1);
+    }
+
+    // Ends `frames` frames at once: the timers and `frame_count` advance by all of them, but
+    // everything else only sees the last one
+    fn end_frames(&mut self, frames: u64) {
+        let ticks = frames.min(u8::MAX as u64) as u8;
+        self.delay_timer = self.delay_timer.saturating_sub(ticks);
+        self.sound_timer = self.sound_timer.saturating_sub(ticks);
+        self.frame_count += frames;
+        // Taken out while the keys are applied and then handed back, so the buffer is reused
+        let mut queued_keys = std::mem::take(&mut self.queued_keys);
+        for (key, pressed) in queued_keys.drain(..) {
+            self.key(key, pressed);
+        }
+        self.queued_keys = queued_keys;
+
+        self.gpu.present();
+        if self.gpu.present_on_vblank() {
+            self.needs_redraw = true;
+        }
+
+        #[cfg(feature = "gif-recording")]
+        {
+            if let Some(recorder) = self.gif_recorder.as_mut() {
+                recorder.add_frame(&self.gpu);
+            }
+        }
+
+        if let Some(hook) = self.vblank_hook.as_mut() {
+            hook();
+        }
+    }
+
+    /// The number of 60hz frames that have passed, counted each time the timers tick
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Call `hook` at the end of every 60hz frame, after the timers have been decremented.
+    ///
+    /// Frames follow `timer_speed` rather than `clock_speed`, so the hook fires at a steady
+    /// rate however fast the CPU runs.
+    pub fn set_vblank_hook(&mut self, hook: Box<dyn FnMut()>) {
+        self.vblank_hook = Some(hook);
+    }
+
+    /// Remove the hook set by `set_vblank_hook`
+    pub fn clear_vblank_hook(&mut self) {
+        self.vblank_hook = None;
+    }
+
+    /// Record the display to an animated GIF at `path`, adding a frame every 60hz frame until
+    /// `stop_recording_gif` is called. Replaces any recording that is already in progress.
+    #[cfg(feature = "gif-recording")]
+    pub fn start_recording_gif<P: AsRef<Path>>(&mut self, path: P) -> Chip8Result<()> {
+        self.stop_recording_gif()?;
+        self.gif_recorder = Some(GifRecorder::create(path, &self.gpu)?);
+        Ok(())
+    }
+
+    /// Finish the GIF started by `start_recording_gif`. Does nothing if we aren't recording.
+    ///
+    /// Returns the first error that happened while writing frames, if any.
+    #[cfg(feature = "gif-recording")]
+    pub fn stop_recording_gif(&mut self) -> Chip8Result<()> {
+        match self.gif_recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "gif-recording")]
+    pub fn is_recording_gif(&self) -> bool {
+        self.gif_recorder.is_some()
+    }
+
+    /// Execute one cycle of the chip8 interpreter.
+    pub fn cycle(&mut self) -> Chip8Result<Chip8Output> {
+        self.poll_input_source();
+
+        if self.state == Chip8State::Exited {
+            return Ok(Chip8Output::Halted);
+        }
+
+        if self.state != Chip8State::Running {
+            return Ok(Chip8Output::None);
+        }
+
+        if self.is_halted() {
+            return Ok(Chip8Output::Halted);
+        }
+
+        let opcode = match self.peek_next_opcode() {
+            Err(Chip8Error::UnsupportedOpcode(word)) if self.custom_opcode_handler.is_some() => {
+                return self.execute_custom_opcode(word);
+            },
+            result => result?,
+        };
+
+        if let Some(trace) = self.binary_trace.as_mut() {
+            trace.record(self.cycle_count, self.pc, opcode.to_u16())?;
+        }
+
+        self.cycle_count += 1;
+        self.pc = self.pc.wrapping_add(2);
+
+        self.execute(opcode)
+    }
+
+    // Fetch `word` like any other opcode and pass it to `custom_opcode_handler`
+    fn execute_custom_opcode(&mut self, word: u16) -> Chip8Result<Chip8Output> {
+        if let Some(trace) = self.binary_trace.as_mut() {
+            trace.record(self.cycle_count, self.pc, word)?;
+        }
+
+        self.cycle_count += 1;
+        self.pc = self.pc.wrapping_add(2);
+
+        // Taken out while it runs so the handler can borrow the machine
+        let mut handler = match self.custom_opcode_handler.take() {
+            Some(handler) => handler,
+            None => return Err(Chip8Error::UnsupportedOpcode(word)),
+        };
+        let output = handler(self, word);
+
+        if self.custom_opcode_handler.is_none() {
+            self.custom_opcode_handler = Some(handler);
+        }
+
+        self.needs_redraw |= output == Ok(Chip8Output::Redraw);
+        output
+    }
+
+    /// The number of instructions executed by `cycle` since the machine started
+    pub fn cycle_count(&self) -> u64 {
+        self.cycle_count
+    }
+
+    /// Write the cycle number, `pc` and opcode of every instruction executed from now on to
+    /// `writer`, until `stop_binary_trace` is called. Replaces any trace already in progress.
+    ///
+    /// Use `read_binary_trace` to parse the result.
+    pub fn start_binary_trace(&mut self, writer: Box<dyn std::io::Write>) -> Chip8Result<()> {
+        self.stop_binary_trace()?;
+        self.binary_trace = Some(TraceWriter::new(writer)?);
+        Ok(())
+    }
+
+    /// Flush and close the trace started by `start_binary_trace`. Does nothing if we aren't tracing.
+    pub fn stop_binary_trace(&mut self) -> Chip8Result<()> {
+        match self.binary_trace.take() {
+            Some(trace) => trace.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Execute `opcode` directly instead of fetching it from memory.
+    ///
+    /// `pc` is left alone unless the opcode itself changes it, e.g. `Jump` or a skip that
+    /// succeeds. This ignores whether the machine is waiting for a key, which is useful for
+    /// experimenting with opcodes without assembling a ROM.
+    pub fn execute(&mut self, opcode: Opcode) -> Chip8Result<Chip8Output> {
+        let output = match opcode {
+            Opcode::Draw { .. } | Opcode::LowRes | Opcode::HighRes => Chip8Output::Redraw,
+            Opcode::Exit => Chip8Output::Halted,
+            _ => Chip8Output::None,
+        };
+        let changes_display = output == Chip8Output::Redraw || opcode == Opcode::ClearScreen;
+
+        if let Some(quirks) = self.quirks_exercised.as_mut() {
+            quirks.extend(opcode.quirks());
+        }
+
+        self.execute_opcode(opcode)?;
+        self.needs_redraw |= changes_display;
+        Ok(output)
+    }
+
+    pub fn cycle_n(&mut self, times: u32) -> Chip8Result<()> {
+        for _ in 0..times {
+            self.cycle()?;
+        }
+
+        Ok(())
+    }
+
+    // Wrap `address` to the active memory size. Memory sizes are always a power of two.
+    fn mask_address(&self, address: usize) -> usize {
+        address & (self.memory.len() - 1)
+    }
+
+    // Write `value` to `address`, flagging the write if it modifies the loaded ROM.
+    fn write_memory_byte(&mut self, address: usize, value: u8) {
+        let address = self.mask_address(address);
+        let rom_start = self.start_address as usize;
+        if address >= rom_start && address < rom_start + self.rom_size {
+            self.code_modified = true;
+        }
+
+        match self.memory_bus.as_mut() {
+            Some(bus) => bus.write(&mut self.memory, address, value),
+            None => self.memory[address] = value,
+        }
+    }
+
+    // Read the byte at `address` on behalf of the program
+    fn read_memory_byte(&mut self, address: usize) -> u8 {
+        let address = self.mask_address(address);
+        match self.memory_bus.as_mut() {
+            Some(bus) => bus.read(&self.memory, address),
+            None => self.memory[address],
+        }
+    }
+
+    fn execute_opcode(&mut self, opcode: Opcode) -> Chip8Result<()> {
+        if let Some(profile) = self.strict_profile {
+            if !opcode.is_supported_in(profile) {
+                return Err(Chip8Error::UnsupportedOpcode(opcode.to_u16()));
+            }
+        }
+
+        match opcode {
+            // Flow Control
+            Opcode::CallSubroutine(address) => self.op_call_subroutine(address),
+            Opcode::Return => self.op_return()?,
+            Opcode::Jump(address) => self.pc = address,
+            Opcode::JumpWithOffset(address) => self.pc = self.jump_with_offset_target(address)?,
+            Opcode::Exit => self.state = Chip8State::Exited,
+
+            // Conditional Execution
+            Opcode::SkipNextIfEqual { x, value } => self.op_skip_next_if(self.v[x as usize] == value),
+            Opcode::SkipNextIfNotEqual { x, value } => self.op_skip_next_if(self.v[x as usize] != value),
+            Opcode::SkipNextIfRegisterEqual { x, y } => self.op_skip_next_if(self.v[x as usize] == self.v[y as usize]),
+            Opcode::SkipNextIfRegisterNotEqual { x, y } => self.op_skip_next_if(self.v[x as usize] != self.v[y as usize]),
+
+            // Manipulate `Vx`
+            Opcode::LoadConstant { x, value } => self.v[x as usize] = value,
+            Opcode::Load { x, y } => self.v[x as usize] = self.v[y as usize],
+            Opcode::Or { x, y } => self.op_logic(x, y, |vx, vy| vx | vy),
+            Opcode::And { x, y } => self.op_logic(x, y, |vx, vy| vx & vy),
+            Opcode::Xor { x, y } => self.op_logic(x, y, |vx, vy| vx ^ vy),
+            Opcode::Add { x, y } => self.op_add(x, y),
+            Opcode::AddConstant { x, value } => self.v[x as usize] = self.v[x as usize].wrapping_add(value),
+            Opcode::SubtractXY { x, y } => self.op_subtract(x, x, y),
+            Opcode::SubtractYX { x, y } => self.op_subtract(x, y, x),
+            Opcode::ShiftRight { x, y } => self.op_shift_right(x, y),
+            Opcode::ShiftLeft { x, y } => self.op_shift_left(x, y),
+
+            // Manipulate `I`
+            Opcode::IndexAddress(address) => self.set_index(address),
+            Opcode::AddAddress { x } => self.set_index(self.i.wrapping_add(self.v[x as usize] as u16)),
+            Opcode::IndexFont { x } => self.set_index(Chip8::FONT_START + (self.v[x as usize] as u16 * 5)),
+
+            // Manipulate Memory
+            Opcode::WriteMemory { x } => self.op_write_memory(x)?,
+            Opcode::ReadMemory { x } => self.op_read_memory(x)?,
+            Opcode::SaveRange { x, y } => self.op_save_range(x, y)?,
+            Opcode::LoadRange { x, y } => self.op_load_range(x, y)?,
+            Opcode::WriteBCD { x } => self.op_store_bcd(x),
+
+            // IO Opcodes
+            Opcode::SkipIfKeyPressed { x } => self.op_skip_if_key_pressed(x),
+            Opcode::SkipIfKeyNotPressed { x } => self.op_skip_if_key_not_pressed(x),
+            Opcode::WaitForKeyRelease { x } => self.op_wait_for_key_release(x),
+            Opcode::LoadDelayIntoRegister { x } => self.v[x as usize] = self.delay_timer,
+            Opcode::LoadRegisterIntoDelay { x } => self.delay_timer = self.v[x as usize],
+            Opcode::LoadRegisterIntoSound { x } => self.sound_timer = self.v[x as usize],
+            Opcode::Random { x, mask } => self.op_rand(x, mask),
+            Opcode::ClearScreen => self.gpu.clear(),
+            Opcode::Draw { x, y, n } => self.op_draw(x, y, n),
+            Opcode::SelectPlane { planes } => self.gpu.select_planes(planes),
+            Opcode::LowRes => self.gpu.set_resolution(Resolution::LowRes),
+            Opcode::HighRes => self.gpu.set_resolution(Resolution::HighRes),
+        }
+
+        Ok(())
+    }
+
+    fn op_call_subroutine(&mut self, address: Address) {
+        self.stack.push(self.pc);
+        self.pc = address;
+    }
+
+    fn op_return(&mut self) -> Chip8Result<()> {
+        self.pc = self.stack.pop().ok_or(Chip8Error::StackUnderflow)?;
+
+        Ok(())
+    }
+
+    fn op_skip_next_if(&mut self, expression: bool) {
+        if expression {
+            self.pc = self.pc.wrapping_add(2)
+        }
+    }
+
+    fn op_skip_if_key_pressed(&mut self, x: Register) {
+        let key = self.v[x as usize];
+        self.op_skip_next_if(self.is_key_pressed(key))
+    }
+
+    fn op_skip_if_key_not_pressed(&mut self, x: Register) {
+        let key = self.v[x as usize];
+        self.op_skip_next_if(!self.is_key_pressed(key))
+    }
+
+    fn op_wait_for_key_release(&mut self, x: Register) {
+        // A buffered key that is no longer held was pressed and released since the last tick
+        let tapped_key = (0..16u8).find(|key| self.buffered_keys[*key as usize] && !self.keys[*key as usize]);
+
+        match tapped_key {
+            Some(key) => {
+                self.v[x as usize] = key;
+                self.buffered_keys[key as usize] = false;
+            },
+            None => self.state = Chip8State::WaitingForKey { target_register: x },
+        }
+    }
+
+    fn op_store_bcd(&mut self, x: Register) {
+        let x = x as usize;
+        let i = self.i as usize;
+        let digits = [
+            self.v[x] / 100, // Value of the first digit
+            (self.v[x] / 10) % 10, // Value of the second digit
+            self.v[x] % 10, // Value of the third digit
+        ];
+
+        for (offset, digit) in digits.iter().enumerate() {
+            self.write_memory_byte(i + offset, *digit);
+        }
+    }
+
+    fn op_rand(&mut self, x: Register, mask: u8) {
+        let value: u8 = self.rng.gen();
+
+        self.v[x as usize] = value & mask;
+    }
+
+    // For all of the arithmetic ops `VF` is written _after_ `Vx`. When `x == 0xF` the flag
+    // overwrites the result, which matches the behaviour of the original interpreters.
+    fn op_add(&mut self, x: Register, y: Register) {
+        let (result, carry) = self.v[x as usize].overflowing_add(self.v[y as usize]);
+        self.v[x as usize] = result;
+        self.v[0xF] = carry as u8;
+    }
+
+    fn op_subtract(&mut self, target: Register, x: Register, y: Register) {
+        let (result, carry) = self.v[x as usize].overflowing_sub(self.v[y as usize]);
+        self.v[target as usize] = result;
+        self.v[0xF] = !carry as u8;
+    }
+
+    fn op_logic<F>(&mut self, x: Register, y: Register, operation: F)
+        where F: Fn(u8, u8) -> u8
+    {
+        self.v[x as usize] = operation(self.v[x as usize], self.v[y as usize]);
+
+        if self.quirks.logic == LogicQuirk::ResetVf {
+            self.v[0xF] = 0;
+        }
+    }
+
+    fn op_shift_right(&mut self, x: Register, y: Register) {
+        let source: &mut u8 = match self.quirks.bit_shift {
+            BitShiftQuirk::ShiftYIntoX => &mut self.v[y as usize],
+            BitShiftQuirk::ShiftX => &mut self.v[x as usize],
+        };
+
+        let least_significant_bit = *source & 0b00000001;
+        self.v[x as usize] = source.wrapping_shr(1);
+        self.v[0xF] = least_significant_bit;
+    }
+
+    fn op_shift_left(&mut self, x: Register, y: Register) {
+        let source: &mut u8 = match self.quirks.bit_shift {
+            BitShiftQuirk::ShiftYIntoX => &mut self.v[y as usize],
+            BitShiftQuirk::ShiftX => &mut self.v[x as usize],
+        };
+
+        let most_significant_bit = (*source >> 7) & 1;
+        self.v[x as usize] = source.wrapping_shl(1);
+        self.v[0xF] = most_significant_bit;
+    }
+
+    fn op_draw(&mut self, x: Register, y: Register, n: u8) {
+
+        let x = self.v[x as usize] as usize;
+        let y = self.v[y as usize] as usize;
+
+        // At most 32 bytes in each of 2 planes, read onto the stack so drawing doesn't allocate
+        let mut sprite = [0; 64];
+        let sprite = &mut sprite[..self.sprite_len(n)];
+        for (offset, byte) in sprite.iter_mut().enumerate() {
+            *byte = self.read_memory_byte(self.i as usize + offset);
+        }
+
+        let result = if self.is_16x16_sprite(n) {
+            self.gpu.draw_16x16(x, y, sprite, self.quirks.sprite_wrap, self.quirks.draw_mode)
+        } else {
+            self.gpu.draw_with_mode(x, y, sprite, self.quirks.sprite_wrap, self.quirks.draw_mode)
+        };
+        let count_rows = self.quirks.collision_count == CollisionCountQuirk::CountRows
+            && self.gpu.resolution() == Resolution::HighRes;
+
+        self.v[0xF] = if count_rows {
+            let rows = self.gpu.last_draw_rows();
+            (rows.collided + rows.clipped) as u8
+        } else {
+            result.collision as u8
+        };
+    }
+
+    // The number of bytes `DRAW` reads for an `n` row sprite. Each selected plane has its own `n`
+    // rows, or 16 rows of 2 bytes for a 16x16 sprite.
+    fn sprite_len(&self, n: u8) -> usize {
+        let plane_len = if self.is_16x16_sprite(n) { 32 } else { n as usize };
+        plane_len * self.gpu.selected_plane_count()
+    }
+
+    // In high resolution `DRAW` with `n == 0` draws a SUPER-CHIP 16x16 sprite
+    fn is_16x16_sprite(&self, n: u8) -> bool {
+        n == 0 && self.gpu.resolution() == Resolution::HighRes
+    }
+
+    // The address `JUMP addr, V0` continues at, which can be past the end of memory. See `MemoryBoundsQuirk`
+    fn jump_with_offset_target(&self, address: Address) -> Chip8Result<Address> {
+        let target = address as usize + self.v[0] as usize;
+        if self.quirks.memory_bounds == MemoryBoundsQuirk::Error && target >= self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds(target));
+        }
+
+        Ok(self.mask_address(target) as Address)
+    }
+
+    // Check that `READ`/`WRITE` can access the `x + 1` bytes starting at `I`, e.g. for `V0..=Vx`. See `MemoryBoundsQuirk`
+    fn check_memory_range(&self, x: Register) -> Chip8Result<()> {
+        let last_address = self.i as usize + x as usize;
+        if self.quirks.memory_bounds == MemoryBoundsQuirk::Error && last_address >= self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds(self.memory.len()));
+        }
+
+        Ok(())
+    }
+
+    fn increment_index_after_read_write(&mut self, x: Register) {
+        if self.quirks.read_write_increment == ReadWriteIncrementQuirk::IncrementIndex {
+            self.i = self.mask_address(self.i as usize + x as usize + 1) as u16;
+        }
+    }
+
+    fn op_write_memory(&mut self, x: Register) -> Chip8Result<()> {
+        self.check_memory_range(x)?;
+
+        for register in 0..=(x as usize) {
+            self.write_memory_byte(self.i as usize + register, self.v[register]);
+        }
+
+        self.increment_index_after_read_write(x);
+        Ok(())
+    }
+
+    fn op_read_memory(&mut self, x: Register) -> Chip8Result<()> {
+        self.check_memory_range(x)?;
+
+        for register in 0..=(x as usize) {
+            self.v[register] = self.read_memory_byte(self.i as usize + register);
+        }
+
+        self.increment_index_after_read_write(x);
+        Ok(())
+    }
+
+    // The registers from `x` to `y` inclusive, counting down if `x > y`
+    fn register_range(x: Register, y: Register) -> Vec<usize> {
+        if x <= y {
+            (x as usize..=y as usize).collect()
+        } else {
+            (y as usize..=x as usize).rev().collect()
+        }
+    }
+
+    fn op_save_range(&mut self, x: Register, y: Register) -> Chip8Result<()> {
+        let registers = Chip8::register_range(x, y);
+        self.check_memory_range(registers.len() as Register - 1)?;
+
+        for (offset, register) in registers.into_iter().enumerate() {
+            self.write_memory_byte(self.i as usize + offset, self.v[register]);
+        }
+
+        Ok(())
+    }
+
+    fn op_load_range(&mut self, x: Register, y: Register) -> Chip8Result<()> {
+        let registers = Chip8::register_range(x, y);
+        self.check_memory_range(registers.len() as Register - 1)?;
+
+        for (offset, register) in registers.into_iter().enumerate() {
+            self.v[register] = self.read_memory_byte(self.i as usize + offset);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::chip8::{RomBuilder, DrawRows, DrawResult};
+
+    const ALL_OUTPUTS: [Chip8Output; 4] = [Chip8Output::None, Chip8Output::Tick, Chip8Output::Halted, Chip8Output::Redraw];
+
+    #[test]
+    pub fn combine_prefers_higher_precedence() {
+        assert_eq!(Chip8Output::combine(Chip8Output::Tick, Chip8Output::Redraw), Chip8Output::Redraw);
+        assert_eq!(Chip8Output::combine(Chip8Output::Redraw, Chip8Output::None), Chip8Output::Redraw);
+        assert_eq!(Chip8Output::combine(Chip8Output::Halted, Chip8Output::Redraw), Chip8Output::Redraw);
+        assert_eq!(Chip8Output::combine(Chip8Output::Tick, Chip8Output::Halted), Chip8Output::Halted);
+        assert_eq!(Chip8Output::combine(Chip8Output::None, Chip8Output::Tick), Chip8Output::Tick);
+        assert_eq!(Chip8Output::combine(Chip8Output::None, Chip8Output::None), Chip8Output::None);
+    }
+
+    #[test]
+    pub fn combine_is_commutative() {
+        for x in ALL_OUTPUTS.iter() {
+            for y in ALL_OUTPUTS.iter() {
+                assert_eq!(Chip8Output::combine(*x, *y), Chip8Output::combine(*y, *x), "{:?}, {:?}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    pub fn combine_is_associative() {
+        for x in ALL_OUTPUTS.iter() {
+            for y in ALL_OUTPUTS.iter() {
+                for z in ALL_OUTPUTS.iter() {
+                    assert_eq!(
+                        Chip8Output::combine(Chip8Output::combine(*x, *y), *z),
+                        Chip8Output::combine(*x, Chip8Output::combine(*y, *z)),
+                        "{:?}, {:?}, {:?}", x, y, z
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn combine_with_none_is_identity() {
+        for x in ALL_OUTPUTS.iter() {
+            assert_eq!(Chip8Output::combine(*x, Chip8Output::None), *x);
+        }
+    }
+
+    #[test]
+    pub fn execute_runs_opcode_without_fetching() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xFF },
+        ]));
+
+        chip8.execute(Opcode::LoadConstant { x: 0x0, value: 0x3 }).unwrap();
+        chip8.execute(Opcode::LoadConstant { x: 0x1, value: 0x4 }).unwrap();
+        assert_eq!(chip8.execute(Opcode::Add { x: 0x0, y: 0x1 }), Ok(Chip8Output::None));
+
+        assert_eq!(chip8.v[0x0], 0x7);
+        assert_eq!(chip8.v[0xF], 0x0);
+        assert_eq!(chip8.pc, Chip8::PROGRAM_START);
+    }
+
+    #[test]
+    pub fn execute_draw_and_jump() {
+        let mut chip8 = Chip8::new_with_rom(Vec::new());
+
+        assert_eq!(chip8.execute(Opcode::IndexFont { x: 0x0 }), Ok(Chip8Output::None));
+        assert_eq!(chip8.execute(Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 }), Ok(Chip8Output::Redraw));
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 4, 0, 1), [[1, 1, 1, 1]]);
+
+        chip8.execute(Opcode::Jump(0x300)).unwrap();
+        assert_eq!(chip8.pc, 0x300);
+    }
+
+    #[test]
+    pub fn op_draw_reports_pixels_changed() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 },
+            Opcode::IndexFont { x: 0x0 },
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 },
+        ]));
+
+        // `I` starts at an empty part of memory so nothing changes
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.gpu.last_draw_result(), DrawResult { collision: false, pixels_changed: 0 });
+
+        chip8.cycle_n(2).unwrap();
+        assert_eq!(chip8.gpu.last_draw_result(), DrawResult { collision: false, pixels_changed: 14 });
+    }
+
+    #[test]
+    pub fn needs_redraw_until_acknowledged() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x1 },
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 },
+            Opcode::LoadConstant { x: 0x0, value: 0x2 },
+            Opcode::ClearScreen,
+        ]));
+
+        chip8.cycle().unwrap();
+        assert!(!chip8.needs_redraw());
+
+        chip8.cycle().unwrap();
+        assert!(chip8.needs_redraw());
+
+        // Still set after cycles that don't touch the display
+        chip8.cycle().unwrap();
+        assert!(chip8.needs_redraw());
+
+        chip8.clear_redraw_flag();
+        assert!(!chip8.needs_redraw());
+
+        chip8.cycle().unwrap();
+        assert!(chip8.needs_redraw());
+    }
+
+    #[test]
+    pub fn is_sound_playing_while_sound_timer_is_set() {
+        let mut chip8 = Chip8::new();
+        assert!(!chip8.is_sound_playing());
+
+        chip8.sound_timer = 0x2;
+        assert!(chip8.is_sound_playing());
+    }
+
+    fn timer_chip8() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.timer_speed = Duration::from_millis(10);
+        chip8.delay_timer = 0x10;
+        chip8.sound_timer = 0x10;
+        chip8
+    }
+
+    #[test]
+    pub fn advance_timers_less_than_a_period() {
+        let mut chip8 = timer_chip8();
+
+        assert_eq!(chip8.advance_timers(Duration::from_millis(9)), 0);
+        assert_eq!(chip8.delay_timer, 0x10);
+        assert_eq!(chip8.sound_timer, 0x10);
+        assert_eq!(chip8.timer_tick_accumulator, Duration::from_millis(9));
+    }
+
+    #[test]
+    pub fn advance_timers_exactly_one_period() {
+        let mut chip8 = timer_chip8();
+
+        assert_eq!(chip8.advance_timers(Duration::from_millis(10)), 1);
+        assert_eq!(chip8.delay_timer, 0xF);
+        assert_eq!(chip8.sound_timer, 0xF);
+        assert_eq!(chip8.frame_count(), 1);
+        assert_eq!(chip8.timer_tick_accumulator, Duration::new(0, 0));
+    }
+
+    #[test]
+    pub fn advance_timers_several_periods() {
+        let mut chip8 = timer_chip8();
+
+        assert_eq!(chip8.advance_timers(Duration::from_millis(35)), 3);
+        assert_eq!(chip8.delay_timer, 0xD);
+        assert_eq!(chip8.sound_timer, 0xD);
+        assert_eq!(chip8.timer_tick_accumulator, Duration::from_millis(5));
+    }
+
+    #[test]
+    pub fn advance_timers_carries_fraction_across_calls() {
+        let mut chip8 = timer_chip8();
+
+        assert_eq!(chip8.advance_timers(Duration::from_millis(6)), 0);
+        assert_eq!(chip8.advance_timers(Duration::from_millis(6)), 1);
+        assert_eq!(chip8.timer_tick_accumulator, Duration::from_millis(2));
+        assert_eq!(chip8.advance_timers(Duration::from_millis(6)), 0);
+        assert_eq!(chip8.advance_timers(Duration::from_millis(6)), 1);
+
+        assert_eq!(chip8.delay_timer, 0xE);
+        assert_eq!(chip8.timer_tick_accumulator, Duration::from_millis(4));
+    }
+
+    #[test]
+    pub fn beep() {
+        let mut chip8 = Chip8::new();
+        assert_eq!(chip8.beep(), Beep { playing: false, frequency_hz: 440.0 });
+
+        chip8 = chip8.with_beep_frequency_hz(880.0);
+        chip8.sound_timer = 0x2;
+        assert_eq!(chip8.beep_frequency_hz(), 880.0);
+        assert_eq!(chip8.beep(), Beep { playing: true, frequency_hz: 880.0 });
+    }
+
+    #[test]
+    pub fn next_pc_skip_taken() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::SkipNextIfEqual { x: 0x0, value: 0x0 },
+        ]));
+
+        assert_eq!(chip8.next_pc(), Ok(Chip8::PROGRAM_START + 4));
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.pc, Chip8::PROGRAM_START + 4);
+    }
+
+    #[test]
+    pub fn next_pc_skip_not_taken() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::SkipIfKeyPressed { x: 0x0 },
+        ]));
+
+        assert_eq!(chip8.next_pc(), Ok(Chip8::PROGRAM_START + 2));
+        chip8.press_key(0x0);
+        assert_eq!(chip8.next_pc(), Ok(Chip8::PROGRAM_START + 4));
+    }
+
+    #[test]
+    pub fn next_pc_jumps() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::Jump(0x300),
+        ]));
+        assert_eq!(chip8.next_pc(), Ok(0x300));
+
+        chip8.load_rom(&Opcode::to_rom(vec![Opcode::JumpWithOffset(0x300)])).unwrap();
+        chip8.v[0x0] = 0x10;
+        assert_eq!(chip8.next_pc(), Ok(0x310));
+
+        chip8.load_rom(&Opcode::to_rom(vec![Opcode::Return])).unwrap();
+        assert_eq!(chip8.next_pc(), Err(Chip8Error::StackUnderflow));
+        chip8.stack.push(0x246);
+        assert_eq!(chip8.next_pc(), Ok(0x246));
+    }
+
+    #[test]
+    pub fn binary_trace_round_trip() {
+        use crate::chip8::read_binary_trace;
+
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x1 },
+            Opcode::SkipNextIfEqual { x: 0x0, value: 0x1 },
+            Opcode::ClearScreen,
+            Opcode::Jump(0x200),
+        ]));
+        chip8.cycle().unwrap();
+
+        let path = std::env::temp_dir().join(format!("chipper-trace-{}.bin", std::process::id()));
+        chip8.start_binary_trace(Box::new(std::fs::File::create(&path).unwrap())).unwrap();
+        chip8.cycle_n(3).unwrap();
+        chip8.stop_binary_trace().unwrap();
+        chip8.cycle().unwrap();
+
+        let records = read_binary_trace(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(records, vec![
+            (1, 0x202, 0x3001),
+            (2, 0x206, 0x1200),
+            (3, 0x200, 0x6001),
+        ]);
+        assert_eq!(chip8.cycle_count(), 5);
+    }
+
+    #[test]
+    pub fn program_counter_increases_after_cycle() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xF }
+        ]));
+
+        assert_eq!(chip8.pc, 0x200);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.pc, 0x202);
+    }
+
+    #[test]
+    pub fn is_halted_when_jumping_to_self() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xF },
+            Opcode::Jump(0x202)
+        ]));
+
+        assert!(!chip8.is_halted());
+        assert_eq!(chip8.cycle().unwrap(), Chip8Output::None);
+        assert!(chip8.is_halted());
+        assert_eq!(chip8.cycle().unwrap(), Chip8Output::Halted);
+        assert_eq!(chip8.pc, 0x202);
+    }
+
+    #[test]
+    pub fn exit_halts() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xF },
+            Opcode::Exit,
+            Opcode::LoadConstant { x: 0x0, value: 0xA },
+        ]));
+
+        assert_eq!(chip8.cycle().unwrap(), Chip8Output::None);
+        assert!(!chip8.is_halted());
+        assert_eq!(chip8.cycle().unwrap(), Chip8Output::Halted);
+        assert!(chip8.is_halted());
+
+        let pc = chip8.pc;
+        assert_eq!(chip8.cycle().unwrap(), Chip8Output::Halted);
+        assert_eq!(chip8.cycle().unwrap(), Chip8Output::Halted);
+        assert_eq!(chip8.pc, pc);
+        assert_eq!(chip8.v[0x0], 0xF);
+    }
+
+    #[test]
+    pub fn custom_opcode_handler() {
+        let mut chip8 = Chip8::new_with_rom(vec![0x50, 0x0F, 0x50, 0x0F, 0x61, 0x01]);
+        assert_eq!(chip8.cycle(), Err(Chip8Error::UnsupportedOpcode(0x500F)));
+
+        chip8.set_custom_opcode_handler(Box::new(|chip8, word| {
+            chip8.v[0x0] += (word & 0xFF) as u8;
+            Ok(Chip8Output::None)
+        }));
+
+        chip8.cycle_n(3).unwrap();
+        assert_eq!(chip8.v[0x0], 0x1E);
+        assert_eq!(chip8.v[0x1], 0x01);
+        assert_eq!(chip8.cycle_count(), 3);
+
+        chip8.clear_custom_opcode_handler();
+        chip8.pc = Chip8::PROGRAM_START;
+        assert_eq!(chip8.cycle(), Err(Chip8Error::UnsupportedOpcode(0x500F)));
+    }
+
+    #[test]
+    pub fn custom_opcode_handler_redraw_sets_needs_redraw() {
+        let mut chip8 = Chip8::new_with_rom(vec![0x50, 0x0F]);
+        chip8.set_custom_opcode_handler(Box::new(|chip8, _| {
+            *chip8.gpu.pixel(0, 0) = 1;
+            Ok(Chip8Output::Redraw)
+        }));
+
+        assert_eq!(chip8.cycle(), Ok(Chip8Output::Redraw));
+        assert!(chip8.needs_redraw());
+    }
+
+    #[test]
+    pub fn is_halted_on_default_rom() {
+        let chip8 = Chip8::new_with_default_rom();
+
+        assert!(chip8.is_halted());
+    }
+
+    #[test]
+    pub fn new_with_rom_slice() {
+        const ROM: &[u8] = &[0x60, 0x0F, 0x61, 0xAA];
+        let mut chip8 = Chip8::new_with_rom_slice(ROM);
+
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.v[0x0], 0x0F);
+        assert_eq!(chip8.v[0x1], 0xAA);
+    }
+
+    #[test]
+    pub fn set_quirks() {
+        let quirks = Quirks {
+            read_write_increment: ReadWriteIncrementQuirk::IncrementIndex,
+            bit_shift: BitShiftQuirk::ShiftYIntoX,
+            ..Quirks::default()
+        };
+
+        let mut chip8 = Chip8::new();
+        assert_eq!(chip8.quirks(), &Quirks::default());
+
+        chip8.set_quirks(quirks);
+        assert_eq!(chip8.quirks(), &quirks);
+    }
+
+    #[test]
+    pub fn with_quirk_builders_update_quirks() {
+        let chip8 = Chip8::new()
+            .with_read_write_increment_quirk(ReadWriteIncrementQuirk::IncrementIndex)
+            .with_bit_shift_quirk(BitShiftQuirk::ShiftYIntoX)
+            .with_sprite_wrap_quirk(SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: true })
+            .with_logic_quirk(LogicQuirk::ResetVf)
+            .with_memory_bounds_quirk(MemoryBoundsQuirk::Error)
+            .with_resolution_switch_quirk(ResolutionSwitchQuirk::Scale)
+            .with_draw_mode_quirk(DrawModeQuirk::Or)
+            .with_collision_count_quirk(CollisionCountQuirk::CountRows);
+
+        assert_eq!(chip8.quirks(), &Quirks {
+            read_write_increment: ReadWriteIncrementQuirk::IncrementIndex,
+            bit_shift: BitShiftQuirk::ShiftYIntoX,
+            sprite_wrap: SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: true },
+            logic: LogicQuirk::ResetVf,
+            memory_bounds: MemoryBoundsQuirk::Error,
+            resolution_switch: ResolutionSwitchQuirk::Scale,
+            draw_mode: DrawModeQuirk::Or,
+            collision_count: CollisionCountQuirk::CountRows,
+        });
+    }
+
+    #[test]
+    pub fn screen_size_defaults_to_low_resolution() {
+        let chip8 = Chip8::new();
+
+        assert_eq!(chip8.screen_size(), (64, 32));
+    }
+
+    #[test]
+    pub fn validate_rom_reports_jump_past_end() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x1 },
+            Opcode::Jump(0x204),
+        ]);
+
+        assert_eq!(Chip8::validate_rom(&rom), vec![RomWarning::TargetOutsideRom { address: 0x202, target: 0x204 }]);
+    }
+
+    #[test]
+    pub fn load_rom_too_large() {
+        let rom = vec![0; 4096];
+        let mut chip8 = Chip8::new();
+
+        assert_eq!(chip8.load_rom(&rom), Err(Chip8Error::RomTooLarge(4096)));
+    }
+
+    #[test]
+    pub fn write_memory_region_is_readable_by_program() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x400),
+            Opcode::ReadMemory { x: 0x2 },
+        ]));
+
+        chip8.write_memory_region(0x400, &[0x12, 0x34, 0x56]).unwrap();
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.v[0x0..=0x2], [0x12, 0x34, 0x56]);
+        assert!(!chip8.code_was_modified());
+    }
+
+    #[test]
+    pub fn write_memory_region_out_of_bounds() {
+        let mut chip8 = Chip8::new();
+
+        assert_eq!(chip8.write_memory_region(0xFFE, &[0x1, 0x2, 0x3]), Err(Chip8Error::MemoryOutOfBounds(0x1000)));
+        assert_eq!(chip8.write_memory_region(0x1001, &[0x1]), Err(Chip8Error::MemoryOutOfBounds(0x1001)));
+        assert_eq!(chip8.memory[0xFFE..], [0x0, 0x0]);
+
+        assert_eq!(chip8.write_memory_region(0xFFE, &[0x1, 0x2]), Ok(()));
+    }
+
+    #[test]
+    pub fn memory_dump_round_trip() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xAB },
+            Opcode::IndexAddress(0x400),
+            Opcode::WriteMemory { x: 0x0 },
+        ]));
+        chip8.cycle_n(3).unwrap();
+
+        let dump = chip8.memory_dump();
+        assert_eq!(dump.len(), 4096);
+        assert_eq!(dump[0x400], 0xAB);
+
+        for byte in chip8.memory.iter_mut() {
+            *byte = 0;
+        }
+        chip8.memory_load(&dump).unwrap();
+
+        assert_eq!(chip8.memory, dump);
+    }
+
+    #[test]
+    pub fn memory_load_requires_matching_size() {
+        let mut chip8 = Chip8::new();
+
+        assert_eq!(
+            chip8.memory_load(&[0; 16]),
+            Err(Chip8Error::MemorySizeMismatch { expected: 4096, actual: 16 })
+        );
+    }
+
+    // Logs every write to `address`, passing all accesses through to memory
+    struct WriteLogger {
+        address: usize,
+        writes: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl MemoryBus for WriteLogger {
+        fn write(&mut self, memory: &mut [u8], address: usize, value: u8) {
+            if address == self.address {
+                self.writes.borrow_mut().push(value);
+            }
+            memory[address] = value;
+        }
+    }
+
+    #[test]
+    pub fn memory_bus_sees_program_writes() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x12 },
+            Opcode::LoadConstant { x: 0x1, value: 0x34 },
+            Opcode::IndexAddress(0x3FF),
+            Opcode::WriteMemory { x: 0x1 },
+            Opcode::LoadConstant { x: 0x2, value: 123 },
+            Opcode::IndexAddress(0x400),
+            Opcode::WriteBCD { x: 0x2 },
+        ]));
+        let writes = Rc::new(RefCell::new(Vec::new()));
+        chip8.set_memory_bus(Box::new(WriteLogger { address: 0x400, writes: writes.clone() }));
+
+        chip8.cycle_n(7).unwrap();
+
+        assert_eq!(*writes.borrow(), vec![0x34, 1]);
+        assert_eq!(chip8.memory[0x3FF..0x403], [0x12, 1, 2, 3]);
+    }
+
+    #[test]
+    pub fn memory_bus_can_map_reads() {
+        // A peripheral at 0x500 that always reads as 0xAB
+        struct Peripheral;
+        impl MemoryBus for Peripheral {
+            fn read(&mut self, memory: &[u8], address: usize) -> u8 {
+                if address == 0x500 { 0xAB } else { memory[address] }
+            }
+        }
+
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x4FF),
+            Opcode::ReadMemory { x: 0x1 },
+        ]));
+        chip8.memory[0x4FF] = 0x12;
+        chip8.set_memory_bus(Box::new(Peripheral));
+
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.v[0x0..=0x1], [0x12, 0xAB]);
+        assert_eq!(chip8.memory[0x500], 0x0);
+    }
+
+    #[test]
+    pub fn dump_state() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x3, value: 0xAB },
+            Opcode::LoadConstant { x: 0xE, value: 0x12 },
+            Opcode::IndexAddress(0x345),
+            Opcode::ClearScreen,
+        ]));
+
+        chip8.cycle_n(3).unwrap();
+        let dump = chip8.dump_state();
+
+        assert!(dump.contains("PC = 206  IX = 345  (CLEAR)"));
+        assert!(dump.contains("V3 = AB"));
+        assert!(dump.contains("VE = 12"));
+        assert!(dump.contains("State = Running"));
+        assert!(dump.contains(&"0".repeat(64)));
+    }
+
+    #[test]
+    pub fn diff() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x3, value: 0xAB },
+            Opcode::Jump(Chip8::PROGRAM_START + 2),
+        ]);
+        let chip8 = Chip8::new_with_rom(rom.clone());
+        let mut other = Chip8::new_with_rom(rom);
+
+        assert!(chip8.diff(&other).is_empty());
+
+        other.v[0x3] = 0x12;
+        assert_eq!(chip8.diff(&other), vec!["V3 = 00 != 12"]);
+
+        other.memory[0x300] = 0x1;
+        other.memory[0x301] = 0x2;
+        other.memory[0x400] = 0x3;
+        other.gpu.draw(0, 0, &[0b11000000], SpriteWrapQuirk::default());
+        assert_eq!(chip8.diff(&other), vec![
+            "V3 = 00 != 12",
+            "Memory 300..302",
+            "Memory 400..401",
+            "Display = 2 pixels differ",
+        ]);
+    }
+
+    #[test]
+    pub fn set_index_masks_to_memory_size() {
+        let mut chip8 = Chip8::new();
+        chip8.set_index(0x1ABC);
+        assert_eq!(chip8.index(), 0xABC);
+
+        let mut chip8 = Chip8::new().with_memory_size(MemorySize::Extended);
+        chip8.set_index(0x1ABC);
+        assert_eq!(chip8.index(), 0x1ABC);
+    }
+
+    #[test]
+    pub fn op_add_address_masks_to_memory_size() {
+        let mut chip8 = Chip8::new_with_rom(vec![]);
+        chip8.i = 0xFFF;
+        chip8.v[0x0] = 0x2;
+
+        chip8.execute(Opcode::AddAddress { x: 0x0 }).unwrap();
+        assert_eq!(chip8.index(), 0x001);
+    }
+
+    /// End to end regression test: run David Winter's MAZE with a fixed seed until it halts and
+    /// compare the whole display against a known good frame.
+    #[test]
+    pub fn golden_frame_maze() {
+        const MAZE: [u8; 34] = [
+            0xA2, 0x1E, 0xC2, 0x01, 0x32, 0x01, 0xA2, 0x1A, 0xD0, 0x14, 0x70, 0x04, 0x30, 0x40, 0x12, 0x00,
+            0x60, 0x00, 0x71, 0x04, 0x31, 0x20, 0x12, 0x00, 0x12, 0x18, 0x80, 0x40, 0x20, 0x10, 0x20, 0x40,
+            0x80, 0x10,
+        ];
+        let mut chip8 = Chip8::new_with_rom_slice(&MAZE).with_seed(0xC8);
+
+        chip8.tick_cycles(1_000, 0).unwrap();
+
+        assert!(chip8.is_halted());
+        assert_eq!(chip8.cycle_count(), 981);
+        assert_eq!(chip8.gpu.to_ascii(), concat!(
+            "#...#.....#.#.....#...#.#.....#.#...#.....#...#...#.#...#...#...\n",
+            ".#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#..\n",
+            "..#...#.#.....#.#...#.....#.#.....#...#.#...#...#.....#...#...#.\n",
+            "...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#\n",
+            "#.....#...#.#.....#.#...#...#.....#...#...#.#...#.....#...#...#.\n",
+            ".#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#..\n",
+            "..#.#...#.....#.#.....#...#...#.#...#...#.....#...#.#...#...#...\n",
+            "...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#\n",
+            "#...#...#...#...#...#.....#.#...#...#.....#...#...#...#...#...#.\n",
+            ".#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#..\n",
+            "..#...#...#...#...#...#.#.....#...#...#.#...#...#...#...#...#...\n",
+            "...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#\n",
+            "..#...#.#...#.....#.#...#.....#...#...#...#...#...#.#...#.....#.\n",
+            ".#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#..\n",
+            "#...#.....#...#.#.....#...#.#...#...#...#...#...#.....#...#.#...\n",
+            "...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#\n",
+            "#...#.....#.#...#.....#.#...#.....#...#.#...#...#.....#...#...#.\n",
+            ".#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#..\n",
+            "..#...#.#.....#...#.#.....#...#.#...#.....#...#...#.#...#...#...\n",
+            "...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#\n",
+            "..#...#.#.....#...#...#...#.#.....#...#.#.....#.#.....#.#...#...\n",
+            ".#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#..\n",
+            "#...#.....#.#...#...#...#.....#.#...#.....#.#.....#.#.....#...#.\n",
+            "...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#\n",
+            "..#...#...#...#.#...#.....#.#.....#.#...#...#.....#...#.#...#...\n",
+            ".#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#..\n",
+            "#...#...#...#.....#...#.#.....#.#.....#...#...#.#...#.....#...#.\n",
+            "...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#\n",
+            "#.....#.#.....#.#.....#...#.#...#...#.....#...#.#...#.....#...#.\n",
+            ".#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#..\n",
+            "..#.#.....#.#.....#.#...#.....#...#...#.#...#.....#...#.#...#...\n",
+            "...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#...#\n",
+        ));
+    }
+
+    #[test]
+    pub fn detect_profile() {
+        assert_eq!(Chip8::detect_profile("roms/pong.ch8"), (QuirkProfile::Chip8, MemorySize::Standard));
+        assert_eq!(Chip8::detect_profile("roms/ANT.SC8"), (QuirkProfile::SuperChip, MemorySize::Standard));
+        assert_eq!(Chip8::detect_profile("roms/t8nks.xo8"), (QuirkProfile::Modern, MemorySize::Extended));
+        assert_eq!(Chip8::detect_profile("roms/MAZE"), (QuirkProfile::Modern, MemorySize::Standard));
+    }
+
+    #[test]
+    pub fn from_file_selects_profile_from_extension() {
+        let path = std::env::temp_dir().join(format!("chipper-rom-{}.sc8", std::process::id()));
+        std::fs::write(&path, [0x12, 0x00]).unwrap();
+
+        let detected = Chip8::from_file(&path, None, None).unwrap();
+        let overridden = Chip8::from_file(&path, Some(QuirkProfile::Chip8), Some(MemorySize::Extended)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(detected.quirk_profile(), Some(QuirkProfile::SuperChip));
+        assert_eq!(detected.memory_size(), MemorySize::Standard.bytes());
+        assert_eq!(overridden.quirk_profile(), Some(QuirkProfile::Chip8));
+        assert_eq!(overridden.memory_size(), MemorySize::Extended.bytes());
+        assert_eq!(detected.peek_next_opcode(), Ok(Opcode::Jump(0x200)));
+    }
+
+    #[test]
+    pub fn quirk_profile_only_matches_exact_quirks() {
+        let chip8 = Chip8::new().with_quirks(QuirkProfile::Chip8.quirks());
+        assert_eq!(chip8.quirk_profile(), Some(QuirkProfile::Chip8));
+
+        let chip8 = chip8.with_draw_mode_quirk(DrawModeQuirk::Or);
+        assert_eq!(chip8.quirk_profile(), None);
+    }
+
+    #[test]
+    pub fn memory_size_defaults_to_standard() {
+        let chip8 = Chip8::new();
+
+        assert_eq!(chip8.memory_size(), 4096);
+    }
+
+    #[test]
+    pub fn extended_memory_writes_near_end() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xAA },
+            Opcode::LoadConstant { x: 0x1, value: 0xBB },
+            Opcode::WriteMemory { x: 0x1 },
+        ])).with_memory_size(MemorySize::Extended);
+        chip8.i = 0xFFFE;
+
+        chip8.cycle_n(3).unwrap();
+
+        assert_eq!(chip8.memory_size(), 65536);
+        assert_eq!(chip8.memory[0xFFFE], 0xAA);
+        assert_eq!(chip8.memory[0xFFFF], 0xBB);
+    }
+
+    #[test]
+    pub fn extended_memory_keeps_font_and_rom() {
+        let rom = Opcode::to_rom(vec![Opcode::ClearScreen]);
+        let chip8 = Chip8::new_with_rom(rom.clone()).with_memory_size(MemorySize::Extended);
+
+        assert_eq!(chip8.memory[0x200..0x202], rom[..]);
+        assert_eq!(chip8.memory[0x50..0xA0], Chip8::FONTSET[..]);
+    }
+
+    #[test]
+    pub fn set_memory_size_rejects_shrinking_below_rom() {
+        let mut chip8 = Chip8::new().with_memory_size(MemorySize::Extended);
+        chip8.load_rom(&vec![0xAA; 0x1000]).unwrap();
+
+        assert_eq!(chip8.set_memory_size(MemorySize::Standard), Err(Chip8Error::RomTooLarge(0x1000)));
+        assert_eq!(chip8.memory_size(), 65536);
+        assert_eq!(chip8.rom().len(), 0x1000);
+
+        chip8.load_rom(&[0xAA; 0x10]).unwrap();
+        assert_eq!(chip8.set_memory_size(MemorySize::Standard), Ok(()));
+        assert_eq!(chip8.memory_size(), 4096);
+    }
+
+    #[test]
+    pub fn extended_memory_pc_wraps_at_end() {
+        let mut chip8 = Chip8::new().with_memory_size(MemorySize::Extended);
+        chip8.memory[0xFFFE..].copy_from_slice(&Opcode::SkipNextIfEqual { x: 0x0, value: 0x0 }.to_u16().to_be_bytes());
+        chip8.pc = 0xFFFE;
+
+        assert_eq!(chip8.next_pc(), Ok(0x0002));
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.pc, 0x0002);
+
+        chip8.pc = 0xFFFE;
+        chip8.v[0x0] = 0x1;
+        assert_eq!(chip8.next_pc(), Ok(0x0000));
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.pc, 0x0000);
+    }
+
+    #[test]
+    pub fn standard_memory_writes_wrap_around() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xAA },
+            Opcode::LoadConstant { x: 0x1, value: 0xBB },
+            Opcode::WriteMemory { x: 0x1 },
+        ]));
+        chip8.i = 0xFFF;
+
+        chip8.cycle_n(3).unwrap();
+
+        assert_eq!(chip8.memory[0xFFF], 0xAA);
+        assert_eq!(chip8.memory[0x000], 0xBB);
+    }
+
+    #[test]
+    pub fn write_memory_past_end_of_memory_errors_with_memory_bounds_quirk() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xAA },
+            Opcode::WriteMemory { x: 0x5 },
+        ])).with_memory_bounds_quirk(MemoryBoundsQuirk::Error);
+        chip8.i = 4094;
+
+        chip8.cycle().unwrap();
+
+        assert_eq!(chip8.cycle(), Err(Chip8Error::MemoryOutOfBounds(4096)));
+        assert_eq!(chip8.memory[4094], 0x0);
+        assert_eq!(chip8.memory[0x0], 0x0);
+    }
+
+    #[test]
+    pub fn read_memory_past_end_of_memory_errors_with_memory_bounds_quirk() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::ReadMemory { x: 0x5 },
+        ])).with_memory_bounds_quirk(MemoryBoundsQuirk::Error);
+        chip8.i = 4094;
+
+        assert_eq!(chip8.cycle(), Err(Chip8Error::MemoryOutOfBounds(4096)));
+    }
+
+    #[test]
+    pub fn write_memory_up_to_end_of_memory_wraps_incremented_index() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x1, value: 0xBB },
+            Opcode::WriteMemory { x: 0x1 },
+        ]))
+            .with_memory_bounds_quirk(MemoryBoundsQuirk::Error)
+            .with_read_write_increment_quirk(ReadWriteIncrementQuirk::IncrementIndex);
+        chip8.i = 4094;
+
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.memory[4095], 0xBB);
+        assert_eq!(chip8.i, 0x0);
+    }
+
+    #[test]
+    pub fn tick_events_reports_every_event_in_order() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x0 },
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 },
+            Opcode::LoadRegisterIntoSound { x: 0x0 },
+            Opcode::LoadConstant { x: 0x1, value: 0x10 },
+            Opcode::LoadRegisterIntoSound { x: 0x1 },
+            Opcode::WaitForKeyRelease { x: 0x2 },
+        ]));
+
+        let events = chip8.tick_events(chip8.clock_speed * 7).unwrap();
+
+        assert_eq!(events, vec![
+            Chip8Event::Tick,
+            Chip8Event::Tick,
+            Chip8Event::Redraw,
+            Chip8Event::Tick,
+            Chip8Event::Tick,
+            Chip8Event::Tick,
+            Chip8Event::Sound,
+            Chip8Event::Tick,
+            Chip8Event::WaitingForKey,
+            Chip8Event::Tick,
+        ]);
+    }
+
+    #[test]
+    pub fn tick_events_reports_halted() {
+        let mut chip8 = Chip8::new_with_default_rom();
+
+        let events = chip8.tick_events(chip8.clock_speed).unwrap();
+
+        assert_eq!(events, vec![Chip8Event::Tick, Chip8Event::Halted]);
+    }
+
+    #[test]
+    pub fn resuming_does_not_catch_up_on_time_before_pause() {
+        let mut rom = RomBuilder::new();
+        rom.label("loop");
+        rom.push(Opcode::AddConstant { x: 0x0, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.delay_timer = 0xA;
+
+        // Leave most of a cycle and a timer period in the accumulators
+        chip8.tick(chip8.clock_speed * 7 + chip8.clock_speed * 9 / 10).unwrap();
+        let state_before_pause = (chip8.v[0x0], chip8.pc);
+        assert_eq!(chip8.delay_timer, 0xA);
+
+        chip8.set_paused(true);
+        assert!(chip8.is_paused());
+        assert_eq!(chip8.tick(Duration::from_secs(10)).unwrap(), Chip8Output::None);
+
+        chip8.set_paused(false);
+        assert!(!chip8.is_paused());
+        chip8.tick(chip8.clock_speed / 2).unwrap();
+
+        assert_eq!((chip8.v[0x0], chip8.pc), state_before_pause);
+        assert_eq!(chip8.delay_timer, 0xA);
+    }
+
+    #[test]
+    pub fn tick_events_does_nothing_in_debug_mode() {
+        let mut chip8 = Chip8::new_with_default_rom();
+        chip8.debug_mode = true;
+
+        let events = chip8.tick_events(chip8.clock_speed * 10).unwrap();
+
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    pub fn with_start_address_loads_and_executes_from_address() {
+        let mut chip8 = Chip8::new()
+            .with_start_address(0x600)
+            .with_rom(&Opcode::to_rom(vec![
+                Opcode::LoadConstant { x: 0x0, value: 0xAB },
+            ]));
+
+        assert_eq!(chip8.start_address(), 0x600);
+        assert_eq!(chip8.pc, 0x600);
+        assert_eq!(chip8.memory[0x600..0x602], [0x60, 0xAB]);
+        assert_eq!(chip8.memory[0x200..0x202], [0x00, 0x00]);
+
+        chip8.cycle().unwrap();
+
+        assert_eq!(chip8.v[0x0], 0xAB);
+        assert_eq!(chip8.pc, 0x602);
+    }
+
+    #[test]
+    pub fn code_was_modified_when_writing_over_rom() {
+        let mut rom = RomBuilder::new();
+        rom.push_ref("target", Opcode::IndexAddress);
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0x00 });
+        rom.push(Opcode::LoadConstant { x: 0x1, value: 0xE0 });
+        rom.push(Opcode::WriteMemory { x: 0x1 });
+        rom.label("target");
+        rom.push(Opcode::Return);
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        let target = rom.resolve("target");
+
+        chip8.cycle_n(3).unwrap();
+        assert!(!chip8.code_was_modified());
+
+        chip8.cycle().unwrap();
+        assert!(chip8.code_was_modified());
+        assert!(!chip8.code_was_modified());
+        assert_eq!(chip8.opcodes(target, target + 2), vec![(target, Opcode::ClearScreen)]);
+    }
+
+    #[test]
+    pub fn code_was_modified_ignores_writes_outside_rom() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x300),
+            Opcode::LoadConstant { x: 0x0, value: 123 },
+            Opcode::WriteBCD { x: 0x0 },
+            Opcode::WriteMemory { x: 0x0 },
+        ]));
+
+        chip8.cycle_n(4).unwrap();
+
+        assert!(!chip8.code_was_modified());
+    }
+
+    #[test]
+    pub fn input_buffering_sees_key_tapped_between_ticks() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xA },
+            Opcode::SkipIfKeyPressed { x: 0x0 },
+            Opcode::LoadConstant { x: 0x1, value: 0xFF },
+            Opcode::LoadConstant { x: 0x2, value: 0xBB }
+        ]);
+        let mut chip8 = Chip8::new_with_rom(rom).with_input_buffering(true);
+
+        chip8.press_key(0xA);
+        chip8.release_key(0xA);
+        chip8.tick(chip8.clock_speed * 3).unwrap();
+
+        assert_eq!(chip8.v[0x1], 0x0);
+        assert_eq!(chip8.v[0x2], 0xBB);
+    }
+
+    #[test]
+    pub fn input_buffering_is_cleared_after_tick() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0xA });
+        rom.label("loop");
+        rom.push(Opcode::SkipIfKeyNotPressed { x: 0x0 });
+        rom.push(Opcode::AddConstant { x: 0x1, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap()).with_input_buffering(true);
+
+        chip8.press_key(0xA);
+        chip8.release_key(0xA);
+        chip8.tick(chip8.clock_speed * 4).unwrap();
+        assert_eq!(chip8.v[0x1], 0x1);
+
+        chip8.tick(chip8.clock_speed * 3).unwrap();
+        assert_eq!(chip8.v[0x1], 0x1);
+    }
+
+    #[test]
+    pub fn key_pressed_at_records_cycle_count() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::AddConstant { x: 0x0, value: 0x1 },
+            Opcode::Jump(Chip8::PROGRAM_START),
+        ]));
+        assert_eq!(chip8.key_pressed_at(0x5), None);
+
+        chip8.cycle_n(7).unwrap();
+        chip8.press_key(0x5);
+        assert_eq!(chip8.key_pressed_at(0x5), Some(7));
+
+        // Repeated presses while held and releasing keep the original timestamp
+        chip8.cycle_n(3).unwrap();
+        chip8.press_key(0x5);
+        chip8.release_key(0x5);
+        assert_eq!(chip8.key_pressed_at(0x5), Some(7));
+
+        chip8.cycle().unwrap();
+        chip8.press_key(0x5);
+        assert_eq!(chip8.key_pressed_at(0x5), Some(11));
+        assert_eq!(chip8.key_pressed_at(0x6), None);
+    }
+
+    #[test]
+    pub fn input_buffering_ignores_repeated_press_of_held_key() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0xA });
+        rom.label("loop");
+        rom.push(Opcode::SkipIfKeyNotPressed { x: 0x0 });
+        rom.push(Opcode::AddConstant { x: 0x1, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap()).with_input_buffering(true);
+
+        chip8.press_key(0xA);
+        chip8.tick(chip8.clock_speed * 4).unwrap();
+        assert_eq!(chip8.v[0x1], 0x1);
+
+        // An auto-repeated press while held, released before the next tick
+        chip8.press_key(0xA);
+        chip8.release_key(0xA);
+        chip8.tick(chip8.clock_speed * 3).unwrap();
+        assert_eq!(chip8.v[0x1], 0x1);
+    }
+
+    #[test]
+    pub fn input_buffering_completes_wait_for_key_release() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::WaitForKeyRelease { x: 0x0 },
+            Opcode::LoadConstant { x: 0x1, value: 0xFF },
+        ]);
+        let mut chip8 = Chip8::new_with_rom(rom).with_input_buffering(true);
+
+        chip8.press_key(0x7);
+        chip8.release_key(0x7);
+        chip8.tick(chip8.clock_speed * 2).unwrap();
+
+        assert_eq!(chip8.v[0x0], 0x7);
+        assert_eq!(chip8.v[0x1], 0xFF);
+    }
+
+    #[test]
+    pub fn without_input_buffering_tapped_key_is_missed() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xA },
+            Opcode::SkipIfKeyPressed { x: 0x0 },
+            Opcode::LoadConstant { x: 0x1, value: 0xFF },
+        ]);
+        let mut chip8 = Chip8::new_with_rom(rom);
+
+        chip8.press_key(0xA);
+        chip8.release_key(0xA);
+        chip8.tick(chip8.clock_speed * 3).unwrap();
+
+        assert_eq!(chip8.v[0x1], 0xFF);
+    }
+
+    #[test]
+    pub fn save_state_round_trip_through_file() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0x5 });
+        rom.push(Opcode::IndexFont { x: 0x0 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 });
+        rom.push_ref("subroutine", Opcode::CallSubroutine);
+        rom.label("subroutine");
+        rom.push(Opcode::Random { x: 0x1, mask: 0xFF });
+        rom.push(Opcode::Random { x: 0x2, mask: 0xFF });
+        rom.push(Opcode::WaitForKeyRelease { x: 0x3 });
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap())
+            .with_seed(1234)
+            .with_bit_shift_quirk(BitShiftQuirk::ShiftYIntoX)
+            .with_sprite_wrap_quirk(SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: true })
+            .with_logic_quirk(LogicQuirk::ResetVf)
+            .with_memory_bounds_quirk(MemoryBoundsQuirk::Error)
+            .with_resolution_switch_quirk(ResolutionSwitchQuirk::Scale)
+            .with_draw_mode_quirk(DrawModeQuirk::Or)
+            .with_collision_count_quirk(CollisionCountQuirk::CountRows)
+            .with_memory_size(MemorySize::Extended);
+        chip8.gpu.set_resolution(Resolution::HighRes);
+        chip8.cycle_n(5).unwrap();
+        chip8.tick_cycles(0, 3).unwrap();
+
+        let path = std::env::temp_dir().join(format!("chipper-save-state-{}.state", std::process::id()));
+        chip8.save_state_to_file(&path).unwrap();
+        let mut loaded = Chip8::load_state_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.dump_state(), chip8.dump_state());
+        assert_eq!(loaded.quirks(), chip8.quirks());
+        assert_eq!(loaded.gpu.resolution(), Resolution::HighRes);
+        assert_eq!(loaded.memory, chip8.memory);
+        assert_eq!(loaded.gpu.pixels(), chip8.gpu.pixels());
+        assert_eq!(loaded.frame_count(), 3);
+
+        // The random number generator continues from where it was saved
+        chip8.cycle_n(2).unwrap();
+        loaded.cycle_n(2).unwrap();
+        assert_eq!(loaded.v[0x2], chip8.v[0x2]);
+        assert_eq!(loaded.state, Chip8State::WaitingForKey { target_register: 0x3 });
+    }
+
+    #[test]
+    pub fn load_state_rejects_incompatible_data() {
+        let mut bytes = Chip8::new_with_default_rom().save_state();
+        bytes[4] += 1;
+
+        assert!(matches!(Chip8::load_state(&bytes), Err(Chip8Error::InvalidSaveState(_))));
+        assert!(matches!(Chip8::load_state(b"not a save state"), Err(Chip8Error::InvalidSaveState(_))));
+    }
+
+    #[test]
+    pub fn load_state_rejects_memory_size() {
+        let mut chip8 = Chip8::new_with_default_rom();
+        chip8.memory = vec![0; 2];
+
+        assert_eq!(
+            Chip8::load_state(&chip8.save_state()).err(),
+            Some(Chip8Error::InvalidSaveState("memory must be 4KB or 64KB".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn load_state_rejects_rom_outside_memory() {
+        let mut chip8 = Chip8::new_with_default_rom();
+        chip8.rom_size = 0x1000;
+
+        assert_eq!(
+            Chip8::load_state(&chip8.save_state()).err(),
+            Some(Chip8Error::InvalidSaveState("rom must fit in memory".to_string()))
+        );
+
+        let mut chip8 = Chip8::new_with_default_rom();
+        chip8.start_address = 0xFFFF;
+
+        assert_eq!(
+            Chip8::load_state(&chip8.save_state()).err(),
+            Some(Chip8Error::InvalidSaveState("rom must fit in memory".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn load_state_rejects_invalid_wait_register() {
+        let mut chip8 = Chip8::new_with_default_rom();
+        chip8.state = Chip8State::WaitingForKey { target_register: 0x10 };
+
+        assert_eq!(
+            Chip8::load_state(&chip8.save_state()).err(),
+            Some(Chip8Error::InvalidSaveState("key must be waited for into V0 through VF".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn load_state_rejects_zero_speeds() {
+        let mut chip8 = Chip8::new_with_default_rom();
+        chip8.timer_speed = Duration::from_secs(0);
+
+        assert_eq!(
+            Chip8::load_state(&chip8.save_state()).err(),
+            Some(Chip8Error::InvalidSaveState("clock and timer speeds must be non-zero".to_string()))
+        );
+
+        let mut chip8 = Chip8::new_with_default_rom();
+        chip8.clock_speed = Duration::from_secs(0);
+
+        assert_eq!(
+            Chip8::load_state(&chip8.save_state()).err(),
+            Some(Chip8Error::InvalidSaveState("clock and timer speeds must be non-zero".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn load_state_restores_rng_position_without_replaying() {
+        let mut chip8 = Chip8::new_with_default_rom().with_seed(42);
+        for _ in 0..100 {
+            chip8.op_rand(0x0, 0xFF);
+        }
+
+        let mut loaded = Chip8::load_state(&chip8.save_state()).unwrap();
+        chip8.op_rand(0x0, 0xFF);
+        loaded.op_rand(0x0, 0xFF);
+        assert_eq!(loaded.v[0x0], chip8.v[0x0]);
+
+        // A position far into the stream loads as quickly as any other
+        assert!(Chip8::load_state(&chip8.write_state(u64::MAX as u128)).is_ok());
+
+        assert_eq!(
+            Chip8::load_state(&chip8.write_state(u128::MAX)).err(),
+            Some(Chip8Error::InvalidSaveState("random number generator position out of range".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn register_accessors() {
+        let mut chip8 = Chip8::new_with_default_rom();
+
+        assert_eq!(chip8.set_register(0x3, 0x42), Ok(()));
+        assert_eq!(chip8.register(0x3), Ok(0x42));
+        assert_eq!(chip8.v[0x3], 0x42);
+
+        assert_eq!(chip8.set_register(0xF, 0x1), Ok(()));
+        assert_eq!(chip8.register(0xF), Ok(0x1));
+    }
+
+    #[test]
+    pub fn register_accessors_reject_out_of_range_index() {
+        let mut chip8 = Chip8::new_with_default_rom();
+
+        assert_eq!(chip8.register(0x10), Err(Chip8Error::InvalidRegister(0x10)));
+        assert_eq!(chip8.set_register(0x10, 0x1), Err(Chip8Error::InvalidRegister(0x10)));
+    }
+
+    #[test]
+    pub fn jump_targets_include_subroutines() {
+        let mut rom = RomBuilder::new();
+        rom.push_ref("subroutine", Opcode::CallSubroutine);
+        rom.label("end");
+        rom.push(Opcode::Jump(rom.resolve("end")));
+        rom.label("subroutine");
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0x1 });
+        rom.push(Opcode::Return);
+
+        let chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        let targets = chip8.jump_targets(Chip8::PROGRAM_START, Chip8::PROGRAM_START + 8);
+
+        assert!(targets.contains(&(Chip8::PROGRAM_START + 4)));
+        assert!(targets.contains(&(Chip8::PROGRAM_START + 2)));
+        assert!(!targets.contains(&(Chip8::PROGRAM_START + 6)));
+    }
+
+    #[test]
+    pub fn delay_remaining() {
+        let mut chip8 = Chip8::new();
+        chip8.delay_timer = 60;
+
+        // `timer_speed` can't represent 1/60th of a second exactly so allow for rounding
+        let difference = 1.0 - chip8.delay_remaining().as_secs_f64();
+        assert!(difference.abs() < 1e-6);
+    }
+
+    #[test]
+    pub fn set_timers_from_remaining_time() {
+        let mut chip8 = Chip8::new();
+
+        chip8.set_delay_remaining(Duration::from_secs(1));
+        assert_eq!(chip8.delay_timer, 60);
+
+        chip8.set_sound_remaining(Duration::from_millis(130));
+        assert_eq!(chip8.sound_timer, 8);
+
+        chip8.set_sound_remaining(Duration::from_secs(10));
+        assert_eq!(chip8.sound_timer, 255);
+    }
+
+    #[test]
+    pub fn font_glyph() {
+        let chip8 = Chip8::new();
+
+        assert_eq!(chip8.font_glyph(0xA), vec![
+            vec![1, 1, 1, 1],
+            vec![1, 0, 0, 1],
+            vec![1, 1, 1, 1],
+            vec![1, 0, 0, 1],
+            vec![1, 0, 0, 1],
+        ]);
+
+        // Every glyph should match the top nibble of its rows in `FONTSET`
+        for digit in 0..16 {
+            let rows = &Chip8::FONTSET[digit as usize * 5..digit as usize * 5 + 5];
+            for (glyph_row, font_row) in chip8.font_glyph(digit).iter().zip(rows) {
+                let glyph_bits = glyph_row.iter().fold(0, |bits, pixel| (bits << 1) | pixel);
+                assert_eq!(glyph_bits, font_row >> 4);
+            }
+        }
+    }
+
+    #[test]
+    pub fn font_glyph_masks_digit() {
+        let chip8 = Chip8::new();
+
+        assert_eq!(chip8.font_glyph(0x1A), chip8.font_glyph(0xA));
+    }
+
+    #[test]
+    pub fn keys_pressed() {
+        let mut chip8 = Chip8::new();
+        chip8.press_key(0xC);
+        chip8.press_key(0x1);
+        chip8.press_key(0x5);
+        chip8.release_key(0x5);
+
+        assert_eq!(chip8.keys_pressed().collect::<Vec<u8>>(), vec![0x1, 0xC]);
+    }
+
+    #[test]
+    pub fn current_sprite() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x300),
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x3 },
+        ]));
+        chip8.write_memory_region(0x300, &[0xAA, 0x55, 0xFF, 0x11]).unwrap();
+
+        assert_eq!(chip8.current_sprite(), None);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.current_sprite(), Some(vec![0xAA, 0x55, 0xFF]));
+
+        chip8.gpu.select_planes(Gpu::PLANE_1 | Gpu::PLANE_2);
+        assert_eq!(chip8.current_sprite(), Some(vec![0xAA, 0x55, 0xFF, 0x11, 0x00, 0x00]));
+    }
+
+    #[test]
+    pub fn current_sprite_16x16() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x300),
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x0 },
+        ]));
+        let sprite: Vec<u8> = (0..32).collect();
+        chip8.write_memory_region(0x300, &sprite).unwrap();
+        chip8.cycle().unwrap();
+
+        // Low resolution `n == 0` draws nothing
+        assert_eq!(chip8.current_sprite(), Some(vec![]));
+
+        chip8.gpu.set_resolution(Resolution::HighRes);
+        assert_eq!(chip8.current_sprite(), Some(sprite));
+    }
+
+    #[test]
+    pub fn current_instruction() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xF },
+            Opcode::Jump(0x300),
+        ]));
+
+        assert_eq!(chip8.current_instruction(), Ok((0x200, Opcode::LoadConstant { x: 0x0, value: 0xF })));
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.current_instruction(), Ok((0x202, Opcode::Jump(0x300))));
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.current_instruction(), Err(Chip8Error::UnsupportedOpcode(0x0000)));
+    }
+
+    #[test]
+    pub fn peek_next_opcode_does_not_advance() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xF },
+            Opcode::LoadConstant { x: 0x1, value: 0xA },
+        ]));
+
+        assert_eq!(chip8.peek_next_opcode(), Ok(Opcode::LoadConstant { x: 0x0, value: 0xF }));
+        assert_eq!(chip8.pc, Chip8::PROGRAM_START);
+        assert_eq!(chip8.v[0x0], 0x0);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.peek_next_opcode(), Ok(Opcode::LoadConstant { x: 0x1, value: 0xA }));
+    }
+
+    #[test]
+    pub fn tick_cycles_cpu_after_enough_time_has_passed() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xF }
+        ]));
+
+        assert_eq!(chip8.v[0x0], 0x0);
+        chip8.tick(chip8.clock_speed).unwrap();
+        assert_eq!(chip8.v[0x0], 0xF);
+    }
+
+    #[test]
+    pub fn tick_does_not_cycle_if_not_enough_time_has_passed() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0xF }
+        ]));
+
+        chip8.tick(Duration::new(0, 0)).unwrap();
+        assert_eq!(chip8.v[0x0], 0x0);
+    }
+
+    #[test]
+    pub fn tick_cycles_multiple_times_if_a_lot_of_time_has_passed() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x05 },
+            Opcode::LoadConstant { x: 0x1, value: 0xAA },
+            Opcode::LoadConstant { x: 0x2, value: 0xBB },
+        ]));
+
+        chip8.tick(chip8.clock_speed * 3).unwrap();
+        assert_eq!(chip8.v[0x0], 0x05);
+        assert_eq!(chip8.v[0x1], 0xAA);
+        assert_eq!(chip8.v[0x2], 0xBB);
+    }
+
+    #[test]
+    pub fn tick_decreases_sound_timer_if_enough_time_has_passed() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0x8 });
+        rom.push(Opcode::LoadRegisterIntoSound { x: 0x0 });
+
+        // Infinite Loop because decreasing sound takes many cycles
+        rom.push(Opcode::LoadConstant { x: 0x1, value: 0xFA });
+        rom.label("loop");
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+
+        chip8.tick(chip8.clock_speed * 2).unwrap();
+        assert_eq!(chip8.sound_timer, 0x8);
+
+        chip8.tick(chip8.timer_speed).unwrap();
+        assert_eq!(chip8.sound_timer, 0x7);
+    }
+
+    #[test]
+    pub fn tick_decreases_delay_timer_if_enough_time_has_passed() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0x8 });
+        rom.push(Opcode::LoadRegisterIntoDelay { x: 0x0 });
+
+        // Infinite Loop because decreasing sound takes many cycles
+        rom.push(Opcode::LoadConstant { x: 0x1, value: 0xFA });
+        rom.label("loop");
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+
+        chip8.tick(chip8.clock_speed * 2).unwrap();
+        assert_eq!(chip8.delay_timer, 0x8);
+
+        chip8.tick(chip8.timer_speed).unwrap();
+        assert_eq!(chip8.delay_timer, 0x7);
+    }
+
+    #[test]
+    pub fn tick_decreases_timers_multiple_times_if_a_lot_of_time_has_passed() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0xA });
+        rom.push(Opcode::LoadRegisterIntoDelay { x: 0x0 });
+        rom.label("loop");
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.clock_speed = chip8.timer_speed * 3;
+
+        chip8.tick(chip8.clock_speed * 2).unwrap();
+        assert_eq!(chip8.delay_timer, 0xA);
+
+        // A single cycle spans three timer periods, so the timer should be decremented three times
+        chip8.tick(chip8.clock_speed).unwrap();
+        assert_eq!(chip8.delay_timer, 0x7);
+    }
+
+    #[test]
+    pub fn tick_decreases_timers_at_timer_speed_with_a_slow_clock() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x1 },
+        ]));
+        chip8.clock_speed = Duration::from_secs(1);
+        chip8.delay_timer = 0xA;
+
+        chip8.tick(chip8.timer_speed * 3).unwrap();
+        assert_eq!(chip8.delay_timer, 0x7);
+        assert_eq!(chip8.v[0x0], 0x0);
+    }
+
+    #[test]
+    pub fn frame_count_follows_timer_speed() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::Jump(Chip8::PROGRAM_START),
+        ]));
+        chip8.clock_speed = Duration::from_secs_f64(1.0 / 1000.0);
+
+        chip8.tick(Duration::from_secs(1)).unwrap();
+
+        // `timer_speed` can't represent 1/60s exactly so the last frame may land just after one second
+        assert!((59..=60).contains(&chip8.frame_count()), "frame_count was {}", chip8.frame_count());
+    }
+
+    #[test]
+    pub fn input_source_is_polled_every_cycle() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x5 },
+            Opcode::SkipIfKeyPressed { x: 0x0 },
+            Opcode::LoadConstant { x: 0x1, value: 0x1 },
+            Opcode::LoadConstant { x: 0x2, value: 0x1 },
+        ]);
+
+        let mut chip8 = Chip8::new_with_rom(rom.clone());
+        let mut keys = [false; 16];
+        keys[0x5] = true;
+        chip8.set_input_source(Box::new(move || keys));
+        chip8.cycle_n(3).unwrap();
+
+        assert!(chip8.keys[0x5]);
+        assert_eq!(chip8.v[0x1], 0x0);
+        assert_eq!(chip8.v[0x2], 0x1);
 
-        Ok(())
-    }
+        let mut chip8 = Chip8::new_with_rom(rom);
+        chip8.set_input_source(Box::new(|| [false; 16]));
+        chip8.cycle_n(3).unwrap();
 
-    fn op_skip_next_if(&mut self, expression: bool) {
-        if expression {
-            self.pc += 2
-        }
+        assert_eq!(chip8.v[0x1], 0x1);
+        assert_eq!(chip8.v[0x2], 0x0);
     }
 
-    fn op_skip_if_key_pressed(&mut self, x: Register) {
-        let key = self.v[x as usize];
-        self.op_skip_next_if(self.keys[key as usize] == true)
-    }
+    #[test]
+    pub fn input_source_mixes_with_manual_keys() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::AddConstant { x: 0x0, value: 0x1 },
+            Opcode::Jump(Chip8::PROGRAM_START),
+        ]));
 
-    fn op_skip_if_key_not_pressed(&mut self, x: Register) {
-        let key = self.v[x as usize];
-        self.op_skip_next_if(self.keys[key as usize] == false)
-    }
+        // Key 0x7 is pressed by the source from the third poll onwards
+        let mut polls = 0;
+        chip8.set_input_source(Box::new(move || {
+            polls += 1;
+            let mut keys = [false; 16];
+            keys[0x7] = polls >= 3;
+            keys
+        }));
 
-    fn op_store_bcd(&mut self, x: Register) {
-        let x = x as usize;
-        let i = self.i as usize;
+        chip8.press_key(0x3);
+        chip8.queue_key(0x4, true);
+        chip8.tick(chip8.timer_speed).unwrap();
+        assert!(chip8.keys[0x3]);
+        assert!(chip8.keys[0x4]);
+        assert!(chip8.keys[0x7]);
 
-        self.memory[i] = self.v[x] / 100; // Value of the first digit
-        self.memory[i + 1] = (self.v[x] / 10) % 10; // Value of the second digit
-        self.memory[i + 2] = self.v[x] % 10; // Value of the third digit
+        // A key held by the source can still be released by hand, until the source changes it
+        chip8.release_key(0x7);
+        chip8.cycle().unwrap();
+        assert!(!chip8.keys[0x7]);
+        assert!(chip8.keys[0x3]);
     }
 
-    fn op_rand(&mut self, x: Register, mask: u8) {
-        let value: u8 = self.rng.gen();
+    #[test]
+    pub fn input_source_completes_wait_for_key_release() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::WaitForKeyRelease { x: 0x3 },
+            Opcode::Jump(Chip8::PROGRAM_START + 2),
+        ]));
 
-        self.v[x as usize] = value & mask;
-    }
+        // Hold key 0x7 for the first two cycles and release it on the third
+        let mut polls = 0;
+        chip8.set_input_source(Box::new(move || {
+            polls += 1;
+            let mut keys = [false; 16];
+            keys[0x7] = polls <= 2;
+            keys
+        }));
 
-    fn op_add(&mut self, x: Register, y: Register) {
-        let (result, carry) = self.v[x as usize].overflowing_add(self.v[y as usize]);
-        self.v[x as usize] = result;
-        self.v[0xF] = carry as u8;
-    }
+        chip8.cycle_n(2).unwrap();
+        assert_eq!(chip8.v[0x3], 0x0);
 
-    fn op_subtract(&mut self, target: Register, x: Register, y: Register) {
-        let (result, carry) = self.v[x as usize].overflowing_sub(self.v[y as usize]);
-        self.v[target as usize] = result;
-        self.v[0xF] = !carry as u8;
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.v[0x3], 0x7);
     }
 
-    fn op_shift_right(&mut self, x: Register, y: Register) {
-        let source: &mut u8 = match self.bit_shift_quirk {
-            BitShiftQuirk::ShiftYIntoX => &mut self.v[y as usize],
-            BitShiftQuirk::ShiftX => &mut self.v[x as usize],
-        };
+    #[test]
+    pub fn input_script_drives_skip_if_key_pressed() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x5 },
+            Opcode::SkipIfKeyPressed { x: 0x0 },
+            Opcode::Jump(Chip8::PROGRAM_START + 2),
+            Opcode::LoadConstant { x: 0x1, value: 0x1 },
+            Opcode::SkipIfKeyPressed { x: 0x0 },
+            Opcode::LoadConstant { x: 0x2, value: 0x1 },
+        ]);
 
-        let least_significant_bit = *source & 0b00000001;
-        self.v[x as usize] = source.wrapping_shr(1);
-        self.v[0xF] = least_significant_bit;
-    }
+        let mut chip8 = Chip8::new_with_rom(rom);
+        chip8.set_input_script("wait 11; press 5; wait 2; release 5".parse().unwrap());
 
-    fn op_shift_left(&mut self, x: Register, y: Register) {
-        let source: &mut u8 = match self.bit_shift_quirk {
-            BitShiftQuirk::ShiftYIntoX => &mut self.v[y as usize],
-            BitShiftQuirk::ShiftX => &mut self.v[x as usize],
-        };
+        // The skip runs on every odd cycle but isn't taken before cycle 11, so the jump loops back
+        chip8.cycle_n(11).unwrap();
+        assert_eq!(chip8.pc, Chip8::PROGRAM_START + 2);
+        assert_eq!(chip8.v[0x1], 0x0);
 
-        let most_significant_bit = (*source >> 7) & 1;
-        self.v[x as usize] = source.wrapping_shl(1);
-        self.v[0xF] = most_significant_bit;
+        // Key 5 is pressed on cycle 11, skipping the jump out of the loop
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.pc, Chip8::PROGRAM_START + 6);
+
+        // ...and released on cycle 13, so the second skip isn't taken
+        chip8.cycle_n(3).unwrap();
+        assert_eq!(chip8.v[0x1], 0x1);
+        assert_eq!(chip8.v[0x2], 0x1);
+        assert!(!chip8.keys[0x5]);
     }
 
-    fn op_draw(&mut self, x: Register, y: Register, n: u8) {
+    #[test]
+    pub fn keypad_from_hex_string() {
+        let keypad = Chip8::keypad_from_hex_string("5a0").unwrap();
+
+        assert_eq!(keypad.iter().filter(|pressed| **pressed).count(), 3);
+        assert!(keypad[0x0] && keypad[0x5] && keypad[0xA]);
+        assert_eq!(Chip8::keypad_from_hex_string(""), Ok([false; 16]));
+        assert_eq!(
+            Chip8::keypad_from_hex_string("5 A"),
+            Err(Chip8Error::ParseError("invalid key:  ".to_string()))
+        );
+    }
 
-        let x = self.v[x as usize] as usize;
-        let y = self.v[y as usize] as usize;
-        let sprite: Vec<u8> = (0..n).map(|y| self.memory[(self.i + y as u16) as usize]).collect();
+    #[test]
+    pub fn vblank_hook_runs_once_per_frame() {
+        use std::cell::Cell;
 
-        match self.gpu.draw(x, y, sprite) {
-            gpu::DrawResult::NoCollision => self.v[0xF] = 0,
-            gpu::DrawResult::Collision => self.v[0xF] = 1
-        }
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::Jump(Chip8::PROGRAM_START),
+        ]));
+        let frames = Rc::new(Cell::new(0));
+        let hook_frames = frames.clone();
+        chip8.set_vblank_hook(Box::new(move || hook_frames.set(hook_frames.get() + 1)));
+
+        chip8.tick(chip8.timer_speed * 3).unwrap();
+        chip8.tick_cycles(1, 2).unwrap();
+        assert_eq!(frames.get(), 5);
+        assert_eq!(chip8.frame_count(), 5);
+
+        chip8.clear_vblank_hook();
+        chip8.tick_cycles(1, 1).unwrap();
+        assert_eq!(frames.get(), 5);
+        assert_eq!(chip8.frame_count(), 6);
     }
 
-    fn op_write_memory(&mut self, x: Register) {
-        for register in 0..=(x as usize) {
-            self.memory[self.i as usize + register] = self.v[register];
+    #[cfg(feature = "gif-recording")]
+    #[test]
+    pub fn record_gif() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0x0 });
+        rom.label("loop");
+        rom.push(Opcode::IndexFont { x: 0x0 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 });
+        rom.push(Opcode::AddConstant { x: 0x0, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+
+        let path = std::env::temp_dir().join(format!("chipper-recording-{}.gif", std::process::id()));
+        chip8.start_recording_gif(&path).unwrap();
+        chip8.tick_cycles(4, 3).unwrap();
+        chip8.tick(chip8.timer_speed * 2).unwrap();
+        chip8.stop_recording_gif().unwrap();
+        assert!(!chip8.is_recording_gif());
+
+        // Frames after stopping aren't recorded
+        chip8.tick_cycles(4, 3).unwrap();
+
+        let mut reader = gif::Decoder::new(std::fs::File::open(&path).unwrap()).read_info().unwrap();
+        assert_eq!((reader.width(), reader.height()), (64, 32));
+
+        let mut frames = 0;
+        while reader.read_next_frame().unwrap().is_some() {
+            frames += 1;
         }
+        std::fs::remove_file(&path).unwrap();
 
-        if self.read_write_increment_quirk == ReadWriteIncrementQuirk::IncrementIndex {
-            self.i += (x + 1) as u16;
-        }
+        assert_eq!(frames, 5);
     }
 
-    fn op_read_memory(&mut self, x: Register) {
-        for register in 0..=(x as usize) {
-            self.v[register] = self.memory[self.i as usize + register];
-        }
+    #[test]
+    pub fn present_on_vblank_hides_mid_frame_erase() {
+        // Draw a glyph, then erase and redraw it like a game animating a sprite in place
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexFont { x: 0x0 },
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 },
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 },
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 },
+            Opcode::Jump(Chip8::PROGRAM_START + 8),
+        ]));
+        chip8.gpu.set_present_on_vblank(true);
 
-        if self.read_write_increment_quirk == ReadWriteIncrementQuirk::IncrementIndex {
-            self.i += (x + 1) as u16;
-        }
-    }
-}
+        assert_eq!(chip8.tick_cycles(2, 1), Ok(Chip8Output::Redraw));
+        let glyph = chip8.gpu.to_rgba(Gpu::BLACK, Gpu::WHITE);
+        assert!(chip8.gpu.presented_pixels().iter().any(|pixel| *pixel != 0));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // The erase only reaches the back buffer
+        chip8.tick_cycles(1, 0).unwrap();
+        assert!(chip8.gpu.pixels().iter().all(|pixel| *pixel == 0));
+        assert_eq!(chip8.gpu.to_rgba(Gpu::BLACK, Gpu::WHITE), glyph);
+
+        chip8.tick_cycles(1, 1).unwrap();
+        assert_eq!(chip8.gpu.to_rgba(Gpu::BLACK, Gpu::WHITE), glyph);
+    }
 
     #[test]
-    pub fn program_counter_increases_after_cycle() {
+    pub fn draws_within_one_tick_need_a_single_redraw() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
-            Opcode::LoadConstant { x: 0x0, value: 0xF }
+            Opcode::IndexFont { x: 0x0 },
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 },
+            Opcode::Draw { x: 0x1, y: 0x0, n: 0x5 },
+            Opcode::Draw { x: 0x2, y: 0x0, n: 0x5 },
+            Opcode::Jump(Chip8::PROGRAM_START + 8),
         ]));
 
-        assert_eq!(chip8.pc, 0x200);
-        chip8.cycle().unwrap();
-        assert_eq!(chip8.pc, 0x202);
+        assert_eq!(chip8.tick_cycles(4, 0), Ok(Chip8Output::Redraw));
+        assert!(chip8.needs_redraw());
+
+        chip8.clear_redraw_flag();
+        chip8.tick_cycles(4, 0).unwrap();
+        assert!(!chip8.needs_redraw());
     }
 
     #[test]
-    pub fn tick_cycles_cpu_after_enough_time_has_passed() {
+    pub fn present_on_vblank_sets_redraw_flag_at_end_of_frame() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
-            Opcode::LoadConstant { x: 0x0, value: 0xF }
+            Opcode::Jump(Chip8::PROGRAM_START),
         ]));
+        chip8.gpu.set_present_on_vblank(true);
 
-        assert_eq!(chip8.v[0x0], 0x0);
-        chip8.tick(chip8.clock_speed).unwrap();
-        assert_eq!(chip8.v[0x0], 0xF);
+        chip8.tick_cycles(1, 0).unwrap();
+        assert!(!chip8.needs_redraw());
+
+        chip8.tick_cycles(0, 1).unwrap();
+        assert!(chip8.needs_redraw());
     }
 
     #[test]
-    pub fn tick_does_not_cycle_if_not_enough_time_has_passed() {
+    pub fn present_on_vblank_redraws_at_end_of_frame() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
-            Opcode::LoadConstant { x: 0x0, value: 0xF }
+            Opcode::Jump(Chip8::PROGRAM_START),
         ]));
+        chip8.gpu.set_present_on_vblank(true);
 
-        chip8.tick(Duration::new(0, 0)).unwrap();
-        assert_eq!(chip8.v[0x0], 0x0);
+        let events = chip8.tick_events(chip8.timer_speed).unwrap();
+        assert_eq!(events.iter().filter(|event| **event == Chip8Event::Redraw).count(), 1);
     }
 
     #[test]
-    pub fn tick_cycles_multiple_times_if_a_lot_of_time_has_passed() {
+    pub fn queued_key_applies_at_end_of_frame() {
+        let mut rom = RomBuilder::new();
+        rom.label("loop");
+        rom.push(Opcode::SkipIfKeyNotPressed { x: 0x0 });
+        rom.push(Opcode::AddConstant { x: 0x1, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.queue_key(0x0, true);
+
+        chip8.tick_cycles(30, 0).unwrap();
+        assert!(!chip8.keys[0x0]);
+        assert_eq!(chip8.v[0x1], 0);
+
+        chip8.tick_cycles(0, 1).unwrap();
+        assert!(chip8.keys[0x0]);
+
+        chip8.tick_cycles(3, 0).unwrap();
+        assert_eq!(chip8.v[0x1], 1);
+
+        chip8.queue_key(0x0, false);
+        chip8.queue_key(0x0, true);
+        chip8.queue_key(0x0, false);
+        chip8.tick(chip8.timer_speed).unwrap();
+        assert!(!chip8.keys[0x0]);
+
+        // Pressed again at the end of the frame, after the 8 cycles `tick` ran before it
+        assert_eq!(chip8.key_pressed_at(0x0), Some(41));
+    }
+
+    #[test]
+    pub fn poll_tick_advances_by_time_since_last_poll() {
+        let mut rom = RomBuilder::new();
+        rom.label("loop");
+        rom.push(Opcode::AddConstant { x: 0x1, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.clock_speed = Duration::from_millis(2);
+
+        let start = Instant::now();
+        assert_eq!(chip8.poll_tick(start).unwrap(), Chip8Output::None);
+        assert_eq!(chip8.cycle_count(), 0);
+
+        chip8.poll_tick(start + Duration::from_millis(10)).unwrap();
+        assert_eq!(chip8.cycle_count(), 5);
+
+        chip8.poll_tick(start + Duration::from_millis(15)).unwrap();
+        assert_eq!(chip8.cycle_count(), 7);
+    }
+
+    #[test]
+    pub fn run_cycles_ticks_timers_once_per_frame() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0x10 });
+        rom.push(Opcode::LoadRegisterIntoDelay { x: 0x0 });
+        rom.label("loop");
+        rom.push(Opcode::AddConstant { x: 0x1, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.clock_speed = Duration::from_millis(2);
+        chip8.timer_speed = Duration::from_millis(20);
+
+        chip8.run_cycles(25).unwrap();
+        assert_eq!(chip8.cycle_count(), 25);
+        assert_eq!(chip8.delay_timer, 0xE);
+        assert_eq!(chip8.v[0x1], 12);
+    }
+
+    #[test]
+    pub fn run_cycles_carries_time_between_calls() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
-            Opcode::LoadConstant { x: 0x0, value: 0x05 },
-            Opcode::LoadConstant { x: 0x1, value: 0xAA },
-            Opcode::LoadConstant { x: 0x2, value: 0xBB },
+            Opcode::AddConstant { x: 0x0, value: 0x1 },
+            Opcode::Jump(Chip8::PROGRAM_START),
         ]));
+        chip8.delay_timer = 0xFF;
 
-        chip8.tick(chip8.clock_speed * 3).unwrap();
-        assert_eq!(chip8.v[0x0], 0x05);
-        assert_eq!(chip8.v[0x1], 0xAA);
-        assert_eq!(chip8.v[0x2], 0xBB);
+        // One second at the default 500hz clock, which doesn't divide evenly into 60hz frames
+        for _ in 0..500 {
+            chip8.run_cycles(1).unwrap();
+        }
+
+        // `timer_speed` can't represent 1/60s exactly so the last frame may land just after one second
+        assert!((59..=60).contains(&chip8.frame_count()), "frame_count was {}", chip8.frame_count());
+        assert_eq!(chip8.delay_timer as u64, 0xFF - chip8.frame_count());
     }
 
     #[test]
-    pub fn tick_decreases_sound_timer_if_enough_time_has_passed() {
-        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
-            Opcode::LoadConstant { x: 0x0, value: 0x8 },
-            Opcode::LoadRegisterIntoSound { x: 0x0 },
+    pub fn tick_cycles_runs_a_fixed_budget() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0x10 });
+        rom.push(Opcode::LoadRegisterIntoDelay { x: 0x0 });
+        rom.push(Opcode::LoadRegisterIntoSound { x: 0x0 });
+        rom.label("loop");
+        rom.push(Opcode::AddConstant { x: 0x1, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        // The clock speed shouldn't matter
+        chip8.clock_speed = Duration::from_secs(1);
+
+        assert_eq!(chip8.tick_cycles(3, 0).unwrap(), Chip8Output::Tick);
+        assert_eq!(chip8.delay_timer, 0x10);
+
+        chip8.tick_cycles(10, 4).unwrap();
+        assert_eq!(chip8.v[0x1], 5);
+        assert_eq!(chip8.delay_timer, 0xC);
+        assert_eq!(chip8.sound_timer, 0xC);
+        assert_eq!(chip8.pc, Chip8::PROGRAM_START + 6);
+    }
 
-            // Infinite Loop because decreasing sound takes many cycles
-            Opcode::LoadConstant { x: 0x1, value: 0xFA },
-            Opcode::Jump(Chip8::PROGRAM_START + 3 * 2)
-        ]));
+    #[test]
+    pub fn tick_caps_cycles_and_drops_excess_time() {
+        let mut rom = RomBuilder::new();
+        rom.label("loop");
+        rom.push(Opcode::AddConstant { x: 0x0, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
 
-        chip8.tick(chip8.clock_speed * 2).unwrap();
-        assert_eq!(chip8.sound_timer, 0x8);
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.max_tick_duration = Duration::from_secs(2);
+        chip8.delay_timer = 0xFF;
 
-        chip8.tick(chip8.timer_speed).unwrap();
-        assert_eq!(chip8.sound_timer, 0x7);
+        let events = chip8.tick_events(Duration::from_secs(10)).unwrap();
+
+        assert_eq!(events.len(), 1000);
+        assert_eq!(chip8.clock_tick_accumulator, Duration::new(0, 0));
+
+        // The timers still count down through the dropped time
+        assert_eq!(chip8.delay_timer, 0x0);
+        assert!((599..=600).contains(&chip8.frame_count()), "frame_count was {}", chip8.frame_count());
+
+        // The dropped time doesn't carry over into the next tick
+        let events = chip8.tick_events(chip8.clock_speed).unwrap();
+        assert_eq!(events.len(), 1);
     }
 
     #[test]
-    pub fn tick_decreases_delay_timer_if_enough_time_has_passed() {
+    pub fn tick_skips_dropped_frames_at_once() {
+        use std::cell::Cell;
+
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
-            Opcode::LoadConstant { x: 0x0, value: 0x8 },
-            Opcode::LoadRegisterIntoDelay { x: 0x0 },
+            Opcode::AddConstant { x: 0x0, value: 0x1 },
+            Opcode::Jump(Chip8::PROGRAM_START),
+        ]));
+        chip8.delay_timer = 0xFF;
+        let frames = Rc::new(Cell::new(0));
+        let hook_frames = frames.clone();
+        chip8.set_vblank_hook(Box::new(move || hook_frames.set(hook_frames.get() + 1)));
+
+        chip8.tick(Duration::from_secs(60 * 60)).unwrap();
+
+        // Every frame of the hour counts but only the frames inside the cap, plus the last
+        // dropped frame, reach the hook
+        assert_eq!(chip8.delay_timer, 0x0);
+        assert!((215_999..=216_000).contains(&chip8.frame_count()), "frame_count was {}", chip8.frame_count());
+        assert!((15..=16).contains(&frames.get()), "hook ran {} times", frames.get());
+    }
 
-            // Infinite Loop because decreasing sound takes many cycles
-            Opcode::LoadConstant { x: 0x1, value: 0xFA },
-            Opcode::Jump(Chip8::PROGRAM_START + 3 * 2)
+    #[test]
+    pub fn zero_timer_speed_stops_the_timers() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::AddConstant { x: 0x0, value: 0x1 },
+            Opcode::Jump(Chip8::PROGRAM_START),
         ]));
+        chip8.timer_speed = Duration::new(0, 0);
+        chip8.delay_timer = 0xFF;
 
-        chip8.tick(chip8.clock_speed * 2).unwrap();
-        assert_eq!(chip8.delay_timer, 0x8);
+        chip8.tick(Duration::from_secs(10)).unwrap();
+        chip8.run_cycles(10).unwrap();
 
-        chip8.tick(chip8.timer_speed).unwrap();
-        assert_eq!(chip8.delay_timer, 0x7);
+        assert_eq!(chip8.delay_timer, 0xFF);
+        assert_eq!(chip8.frame_count(), 0);
+    }
+
+    #[test]
+    pub fn tick_cap_scales_with_clock_speed() {
+        let mut rom = RomBuilder::new();
+        rom.label("loop");
+        rom.push(Opcode::AddConstant { x: 0x0, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        // At 1MHz a normal 16ms frame runs every cycle, far more than a fixed cycle cap would allow
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.clock_speed = Duration::from_micros(1);
+
+        let events = chip8.tick_events(Duration::from_millis(16)).unwrap();
+        assert_eq!(events.iter().filter(|event| **event == Chip8Event::Tick).count(), 16_000);
     }
 
     /// When we call `tick` we may execute several cycles and decrease the timer several times.
@@ -569,14 +4132,16 @@ mod tests {
     /// We need to ensure the operations are correctly interleaved.
     #[test]
     pub fn tick_interleaves_cycles_and_timers_correctly() {
-        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
-            Opcode::LoadConstant { x: 0x0, value: 0x2 },
-            Opcode::LoadRegisterIntoDelay { x: 0x0 },
-            Opcode::LoadDelayIntoRegister { x: 0x0 },
-            Opcode::SkipNextIfEqual { x: 0x0, value: 0x0 },
-            Opcode::Jump(Chip8::PROGRAM_START + 2 * 2),
-            Opcode::LoadConstant { x: 0xA, value: 0xFF },
-        ]));
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0x2 });
+        rom.push(Opcode::LoadRegisterIntoDelay { x: 0x0 });
+        rom.label("wait");
+        rom.push(Opcode::LoadDelayIntoRegister { x: 0x0 });
+        rom.push(Opcode::SkipNextIfEqual { x: 0x0, value: 0x0 });
+        rom.push(Opcode::Jump(rom.resolve("wait")));
+        rom.push(Opcode::LoadConstant { x: 0xA, value: 0xFF });
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
 
         chip8.tick(chip8.clock_speed * 2 + chip8.timer_speed * 2 + chip8.clock_speed * 2).unwrap();
         assert_eq!(chip8.v[0xA], 0xFF);
@@ -584,19 +4149,19 @@ mod tests {
 
     #[test]
     pub fn op_call_subroutine_and_return() {
-        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
-            // Jump to Main
-            Opcode::Jump(0x200 + 6),
+        let mut rom = RomBuilder::new();
+        rom.push_ref("main", Opcode::Jump);
 
-            // Subroutine
-            Opcode::LoadConstant { x: 0xA, value: 0xAA },
-            Opcode::Return,
+        rom.label("subroutine");
+        rom.push(Opcode::LoadConstant { x: 0xA, value: 0xAA });
+        rom.push(Opcode::Return);
 
-            // Main
-            Opcode::LoadConstant { x: 0x1, value: 0xFF },
-            Opcode::CallSubroutine(0x200 + 2),
-            Opcode::LoadConstant { x: 0x2, value: 0xBB }
-        ]));
+        rom.label("main");
+        rom.push(Opcode::LoadConstant { x: 0x1, value: 0xFF });
+        rom.push(Opcode::CallSubroutine(rom.resolve("subroutine")));
+        rom.push(Opcode::LoadConstant { x: 0x2, value: 0xBB });
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
 
         chip8.cycle_n(6).unwrap();
 
@@ -605,13 +4170,97 @@ mod tests {
         assert_eq!(chip8.v[0x2], 0xBB);
     }
 
+    #[test]
+    pub fn step_n_runs_in_debug_mode() {
+        let mut rom = RomBuilder::new();
+        rom.label("loop");
+        rom.push(Opcode::AddConstant { x: 0x0, value: 1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.debug_mode = true;
+
+        assert_eq!(chip8.step_n(10), Ok(Chip8Output::Tick));
+        assert_eq!(chip8.cycle_count(), 10);
+        assert_eq!(chip8.v[0x0], 5);
+    }
+
+    #[test]
+    pub fn step_over_subroutine() {
+        let mut rom = RomBuilder::new();
+        rom.push_ref("main", Opcode::Jump);
+
+        rom.label("subroutine");
+        rom.push(Opcode::LoadConstant { x: 0xA, value: 0xAA });
+        rom.push(Opcode::Return);
+
+        rom.label("main");
+        rom.push(Opcode::CallSubroutine(rom.resolve("subroutine")));
+        rom.label("after_call");
+        rom.push(Opcode::LoadConstant { x: 0x2, value: 0xBB });
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, rom.resolve("main"));
+
+        chip8.step_over().unwrap();
+        assert_eq!(chip8.pc, rom.resolve("after_call"));
+        assert_eq!(chip8.v[0xA], 0xAA);
+        assert!(chip8.stack.is_empty());
+    }
+
+    #[test]
+    pub fn step_over_gives_up_on_subroutine_that_never_returns() {
+        let mut rom = RomBuilder::new();
+        rom.push_ref("subroutine", Opcode::CallSubroutine);
+        rom.label("subroutine");
+        rom.push(Opcode::AddConstant { x: 0x0, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("subroutine")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+
+        chip8.step_over().unwrap();
+
+        assert_eq!(chip8.stack.len(), 1);
+    }
+
+    #[test]
+    pub fn step_out_of_subroutine() {
+        let mut rom = RomBuilder::new();
+        rom.push_ref("main", Opcode::Jump);
+
+        rom.label("subroutine");
+        rom.push(Opcode::LoadConstant { x: 0xA, value: 0xAA });
+        rom.label("subroutine_middle");
+        rom.push(Opcode::LoadConstant { x: 0xB, value: 0xBB });
+        rom.push(Opcode::Return);
+
+        rom.label("main");
+        rom.push(Opcode::CallSubroutine(rom.resolve("subroutine")));
+        rom.label("after_call");
+        rom.push(Opcode::LoadConstant { x: 0x2, value: 0xCC });
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+
+        chip8.cycle_n(3).unwrap();
+        assert_eq!(chip8.pc, rom.resolve("subroutine_middle"));
+
+        chip8.step_out().unwrap();
+        assert_eq!(chip8.pc, rom.resolve("after_call"));
+        assert_eq!(chip8.v[0xB], 0xBB);
+        assert!(chip8.stack.is_empty());
+    }
+
     #[test]
     pub fn op_jump() {
-        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
-            Opcode::Jump(0x200 + 4),
-            Opcode::LoadConstant { x: 0x0, value: 0xAA },
-            Opcode::LoadConstant { x: 0x1, value: 0xFF }
-        ]));
+        let mut rom = RomBuilder::new();
+        rom.push_ref("target", Opcode::Jump);
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0xAA });
+        rom.label("target");
+        rom.push(Opcode::LoadConstant { x: 0x1, value: 0xFF });
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
 
         chip8.cycle_n(2).unwrap();
 
@@ -634,6 +4283,55 @@ mod tests {
         assert_eq!(chip8.v[0x2], 0xFF);
     }
 
+    #[test]
+    pub fn op_jump_with_offset_past_end_of_memory_wraps() {
+        let mut chip8 = Chip8::new_with_rom(vec![]);
+        chip8.v[0x0] = 0x10;
+
+        chip8.execute(Opcode::JumpWithOffset(0xFFE)).unwrap();
+        assert_eq!(chip8.pc, 0x00E);
+    }
+
+    #[test]
+    pub fn op_jump_with_offset_past_end_of_memory_errors() {
+        let mut chip8 = Chip8::new_with_rom(vec![])
+            .with_memory_bounds_quirk(MemoryBoundsQuirk::Error);
+        chip8.v[0x0] = 0x10;
+
+        assert_eq!(chip8.execute(Opcode::JumpWithOffset(0xFFE)), Err(Chip8Error::MemoryOutOfBounds(0x100E)));
+        assert_eq!(chip8.pc, Chip8::PROGRAM_START);
+    }
+
+    #[test]
+    pub fn fetch_from_odd_address() {
+        let mut chip8 = Chip8::new_with_rom(vec![
+            0x12, 0x03, // JUMP 0x203
+            0x00,
+            0x60, 0x2A, // LOAD V0, 0x2A
+            0x12, 0x05, // JUMP 0x205
+        ]);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.pc, 0x203);
+        assert_eq!(chip8.peek_next_opcode(), Ok(Opcode::LoadConstant { x: 0x0, value: 0x2A }));
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.v[0x0], 0x2A);
+        assert_eq!(chip8.pc, 0x205);
+        assert!(chip8.is_halted());
+    }
+
+    #[test]
+    pub fn fetch_from_last_byte_of_memory_wraps() {
+        let mut chip8 = Chip8::new_with_rom(vec![]);
+        chip8.memory[0xFFF] = 0x60;
+        chip8.memory[0x000] = 0x11;
+        chip8.pc = 0xFFF;
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.v[0x0], 0x11);
+    }
+
     #[test]
     pub fn op_skip_next_if_equal() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
@@ -710,6 +4408,23 @@ mod tests {
         assert_eq!(chip8.v[0x2], 0xB);
     }
 
+    #[test]
+    pub fn op_skip_if_key_pressed_masks_key() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x20 },
+            Opcode::SkipIfKeyPressed { x: 0x0 },
+            Opcode::LoadConstant { x: 0x1, value: 0xA },
+            Opcode::SkipIfKeyNotPressed { x: 0x0 },
+            Opcode::LoadConstant { x: 0x2, value: 0xB },
+        ]));
+
+        chip8.press_key(0x0);
+        chip8.cycle_n(4).unwrap();
+
+        assert_eq!(chip8.v[0x1], 0x0);
+        assert_eq!(chip8.v[0x2], 0xB);
+    }
+
     #[test]
     pub fn op_skip_if_key_not_pressed() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
@@ -743,6 +4458,23 @@ mod tests {
         assert_eq!(chip8.v[0xA], 0x3);
     }
 
+    #[test]
+    pub fn op_wait_for_key_release_uses_each_buffered_release_once() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::WaitForKeyRelease { x: 0xA },
+            Opcode::WaitForKeyRelease { x: 0xB },
+            Opcode::LoadConstant { x: 0x1, value: 0xA }
+        ])).with_input_buffering(true);
+
+        chip8.cycle().unwrap();
+        chip8.press_key(0x3);
+        chip8.release_key(0x3);
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.v[0xA], 0x3);
+        assert_eq!(chip8.v[0x1], 0x0);
+    }
+
     #[test]
     pub fn op_store_constant() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
@@ -797,6 +4529,19 @@ mod tests {
         assert_eq!(chip8.i, 0x2);
     }
 
+    #[test]
+    pub fn op_add_address_wraps() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x2 },
+            Opcode::AddAddress { x: 0x0 }
+        ]));
+        chip8.i = 0xFFFF;
+
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.i, 0x0001);
+    }
+
     #[test]
     pub fn op_store_bcd_one_digit() {
         let address = 0x200 + 100;
@@ -934,6 +4679,34 @@ mod tests {
         assert_eq!(chip8.v[0x0], 0b00000000);
     }
 
+    #[test]
+    pub fn op_and_keeps_vf_by_default() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0xF, value: 0x1 },
+            Opcode::LoadConstant { x: 0x0, value: 0b11110000 },
+            Opcode::And { x: 0x0, y: 0xF }
+        ]));
+
+        chip8.cycle_n(3).unwrap();
+
+        assert_eq!(chip8.v[0x0], 0b00000000);
+        assert_eq!(chip8.v[0xF], 0x1);
+    }
+
+    #[test]
+    pub fn op_and_reset_vf_quirk() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0xF, value: 0x1 },
+            Opcode::LoadConstant { x: 0x0, value: 0b11110001 },
+            Opcode::And { x: 0x0, y: 0xF }
+        ])).with_logic_quirk(LogicQuirk::ResetVf);
+
+        chip8.cycle_n(3).unwrap();
+
+        assert_eq!(chip8.v[0x0], 0b00000001);
+        assert_eq!(chip8.v[0xF], 0x0);
+    }
+
     #[test]
     pub fn op_xor() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
@@ -975,6 +4748,32 @@ mod tests {
         assert_eq!(chip8.v[0xF], 0x1);
     }
 
+    #[test]
+    pub fn op_add_into_vf_keeps_carry() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0xF, value: 0x1 },
+            Opcode::LoadConstant { x: 0x0, value: 0x2 },
+            Opcode::Add { x: 0xF, y: 0x0 }
+        ]));
+
+        chip8.cycle_n(3).unwrap();
+
+        assert_eq!(chip8.v[0xF], 0x0);
+    }
+
+    #[test]
+    pub fn op_add_into_vf_overflow_keeps_carry() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0xF, value: 0xFF },
+            Opcode::LoadConstant { x: 0x0, value: 0x2 },
+            Opcode::Add { x: 0xF, y: 0x0 }
+        ]));
+
+        chip8.cycle_n(3).unwrap();
+
+        assert_eq!(chip8.v[0xF], 0x1);
+    }
+
     #[test]
     pub fn op_subtract_x_y() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
@@ -1031,6 +4830,32 @@ mod tests {
         assert_eq!(chip8.v[0xF], 0x0);
     }
 
+    #[test]
+    pub fn op_subtract_x_y_into_vf_keeps_borrow() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0xF, value: 0x5 },
+            Opcode::LoadConstant { x: 0x0, value: 0x3 },
+            Opcode::SubtractXY { x: 0xF, y: 0x0 }
+        ]));
+
+        chip8.cycle_n(3).unwrap();
+
+        assert_eq!(chip8.v[0xF], 0x1);
+    }
+
+    #[test]
+    pub fn op_subtract_y_x_into_vf_keeps_borrow() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0xF, value: 0x5 },
+            Opcode::LoadConstant { x: 0x0, value: 0x3 },
+            Opcode::SubtractYX { x: 0xF, y: 0x0 }
+        ]));
+
+        chip8.cycle_n(3).unwrap();
+
+        assert_eq!(chip8.v[0xF], 0x0);
+    }
+
     #[test]
     pub fn op_shift_right() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
@@ -1072,6 +4897,18 @@ mod tests {
         assert_eq!(chip8.v[0xF], 0x1);
     }
 
+    #[test]
+    pub fn op_shift_right_into_vf_keeps_shifted_bit() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0xF, value: 0b00000010 },
+            Opcode::ShiftRight { x: 0xF, y: 0xF }
+        ]));
+
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.v[0xF], 0x0);
+    }
+
     #[test]
     pub fn op_shift_left() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
@@ -1113,16 +4950,27 @@ mod tests {
         assert_eq!(chip8.v[0xF], 0x1);
     }
 
+    #[test]
+    pub fn op_shift_left_into_vf_keeps_shifted_bit() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0xF, value: 0b10000001 },
+            Opcode::ShiftLeft { x: 0xF, y: 0xF }
+        ]));
+
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.v[0xF], 0x1);
+    }
+
     #[test]
     pub fn op_clear_screen() {
-        let mut rom: Vec<u8> = Opcode::to_rom(vec![
-            Opcode::IndexAddress(0x200 + (2 * 3)), // Store the address of the first byte below
-            Opcode::Draw { x: 0, y: 0, n: 0x1 },
-            Opcode::ClearScreen
-        ]);
-        rom.extend(vec![0b11110000]);
+        let mut rom = RomBuilder::new();
+        rom.push_ref("sprite", Opcode::IndexAddress);
+        rom.push(Opcode::Draw { x: 0, y: 0, n: 0x1 });
+        rom.push(Opcode::ClearScreen);
+        rom.data("sprite", &[0b11110000]);
 
-        let mut chip8 = Chip8::new_with_rom(rom);
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
         chip8.cycle_n(3).unwrap();
 
         assert_eq!(chip8.gpu.to_gfx_slice(0, 8, 0, 1), [[0,0,0,0,0,0,0,0]]);
@@ -1148,6 +4996,121 @@ mod tests {
         ]);
     }
 
+    #[test]
+    pub fn op_draw_matches_reference_framebuffer() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::IndexFont { x: 0x2 });
+        rom.label("loop");
+        rom.push(Opcode::Draw { x: 0x0, y: 0x1, n: 0x5 });
+        rom.push(Opcode::AddConstant { x: 0x0, value: 0x3 });
+        rom.push(Opcode::AddConstant { x: 0x1, value: 0x1 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.tick_cycles(1 + 4 * 500, 0).unwrap();
+
+        // XOR the same sprite onto a plain framebuffer, wrapping at the edges of the screen
+        let sprite = &chip8.memory[chip8.index() as usize..chip8.index() as usize + 5];
+        let mut expected = vec![0; Gpu::SCREEN_PIXELS];
+        for draw in 0..500 {
+            let (x, y) = ((draw * 3) % 256, draw % 256);
+            for (row, byte) in sprite.iter().enumerate() {
+                for column in 0..8 {
+                    let pixel = ((y + row) % 32) * 64 + (x + column) % 64;
+                    expected[pixel] ^= (byte >> (7 - column)) & 0x1;
+                }
+            }
+        }
+
+        assert_eq!(chip8.gpu.pixels(), expected.as_slice());
+    }
+
+    #[test]
+    pub fn op_draw_counts_collided_and_clipped_rows_with_quirk() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::HighRes);
+        rom.push_ref("sprite", Opcode::IndexAddress);
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0 });
+        rom.push(Opcode::LoadConstant { x: 0x1, value: 60 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x1, n: 0x2 });
+        // Collides with both rows drawn above and clips 4 rows off the bottom
+        rom.push(Opcode::Draw { x: 0x0, y: 0x1, n: 0x8 });
+        rom.label("halt");
+        rom.push(Opcode::Jump(rom.resolve("halt")));
+        rom.data("sprite", &[0xFF; 8]);
+        let rom = rom.build().unwrap();
+
+        let clip = SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: false };
+        let mut chip8 = Chip8::new_with_rom(rom.clone())
+            .with_sprite_wrap_quirk(clip)
+            .with_collision_count_quirk(CollisionCountQuirk::CountRows);
+
+        chip8.cycle_n(5).unwrap();
+        assert_eq!(chip8.v[0xF], 0);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.gpu.last_draw_rows(), DrawRows { collided: 2, clipped: 4 });
+        assert_eq!(chip8.v[0xF], 6);
+
+        let mut chip8 = Chip8::new_with_rom(rom).with_sprite_wrap_quirk(clip);
+        chip8.cycle_n(6).unwrap();
+        assert_eq!(chip8.v[0xF], 1);
+    }
+
+    #[test]
+    pub fn op_draw_only_counts_rows_in_high_res() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 },
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 },
+        ])).with_collision_count_quirk(CollisionCountQuirk::CountRows);
+        chip8.i = 0x50;
+
+        chip8.cycle_n(2).unwrap();
+        assert_eq!(chip8.v[0xF], 1);
+    }
+
+    #[test]
+    pub fn op_draw_16x16_sprite_in_high_res() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x300),
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x0 },
+        ]));
+        let sprite: Vec<u8> = (0..32).map(|byte| byte * 7).collect();
+        chip8.write_memory_region(0x300, &sprite).unwrap();
+        chip8.gpu.set_resolution(Resolution::HighRes);
+        chip8.cycle().unwrap();
+
+        let sprite = chip8.current_sprite().unwrap();
+        chip8.cycle().unwrap();
+
+        let expected: Vec<Vec<u8>> = sprite.chunks(2)
+            .map(|row| (0..16).map(|x| (row[x / 8] >> (7 - x % 8)) & 0x1).collect())
+            .collect();
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 16, 0, 16), expected);
+        assert_eq!(chip8.v[0xF], 0);
+    }
+
+    #[test]
+    pub fn op_draw_every_font_digit() {
+        for digit in 0x0..=0xF {
+            let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+                Opcode::LoadConstant { x: 0x1, value: digit },
+                Opcode::IndexFont { x: 0x1 },
+                Opcode::LoadConstant { x: 0x0, value: 0 },
+                Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 }
+            ]));
+
+            chip8.cycle_n(4).unwrap();
+
+            let glyph = &Chip8::FONTSET[digit as usize * 5..][..5];
+            let expected: Vec<Vec<u8>> = glyph.iter()
+                .map(|row| (0..8).map(|bit| (row >> (7 - bit)) & 0x1).collect())
+                .collect();
+
+            assert_eq!(chip8.gpu.to_gfx_slice(0, 8, 0, 5), expected, "digit {:X}", digit);
+        }
+    }
+
     #[test]
     pub fn op_draw_at_offset() {
         let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
@@ -1170,38 +5133,243 @@ mod tests {
     }
 
     #[test]
-    pub fn op_draw_xors_overlapping_pixels() {
-        let mut rom: Vec<u8> = Opcode::to_rom(vec![
-            Opcode::IndexAddress(0x200 + (2 * 5)), // Store the address of the first byte below
-            Opcode::LoadConstant { x: 0x0, value: 0 },
-            Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 },
-            Opcode::IndexAddress(0x200 + (2 * 5) + 1), // Store the address of the second byte below
-            Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 },
+    pub fn op_draw_wraps_starting_coordinate_then_clips() {
+        let mut rom = RomBuilder::new();
+        rom.push_ref("sprite", Opcode::IndexAddress);
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 68 });
+        rom.push(Opcode::LoadConstant { x: 0x1, value: 0 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x1, n: 0x1 });
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 124 });
+        rom.push(Opcode::LoadConstant { x: 0x1, value: 1 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x1, n: 0x1 });
+        rom.label("sprite");
+        let mut rom_bytes = rom.build().unwrap();
+        rom_bytes.push(0b11111111);
+
+        let mut chip8 = Chip8::new_with_rom(rom_bytes)
+            .with_sprite_wrap_quirk(SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: false });
+        chip8.cycle_n(7).unwrap();
+
+        // 68 wraps to 4 once, and 124 wraps to 60 after which the sprite is clipped at the right edge
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 16, 0, 1), [[0,0,0,0,1,1,1,1,1,1,1,1,0,0,0,0]]);
+        assert_eq!(chip8.gpu.to_gfx_slice(56, 8, 1, 1), [[0,0,0,0,1,1,1,1]]);
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 4, 1, 1), [[0,0,0,0]]);
+    }
+
+    #[test]
+    pub fn op_draw_labeled_sprite() {
+        let mut rom = RomBuilder::new();
+        rom.push_ref("sprite", Opcode::IndexAddress);
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 2 });
+        rom.push(Opcode::LoadConstant { x: 0x1, value: 1 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x1, n: 0x2 });
+        rom.label("halt");
+        rom.push(Opcode::Jump(rom.resolve("halt")));
+        rom.data("sprite", &[0b11110000, 0b10010000]);
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.cycle_n(4).unwrap();
+
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 8, 0, 4), [
+            [0,0,0,0,0,0,0,0],
+            [0,0,1,1,1,1,0,0],
+            [0,0,1,0,0,1,0,0],
+            [0,0,0,0,0,0,0,0],
+        ]);
+    }
+
+    fn resolution_switch_rom() -> Vec<u8> {
+        let mut rom = RomBuilder::new();
+        rom.push_ref("sprite", Opcode::IndexAddress);
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 1 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x1, n: 0x1 });
+        rom.push(Opcode::HighRes);
+        rom.label("halt");
+        rom.push(Opcode::Jump(rom.resolve("halt")));
+        rom.data("sprite", &[0b11000000]);
+
+        rom.build().unwrap()
+    }
+
+    #[test]
+    pub fn op_high_res_clears_display() {
+        let mut chip8 = Chip8::new_with_rom(resolution_switch_rom());
+
+        chip8.cycle_n(3).unwrap();
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 4, 0, 1), [[0, 1, 1, 0]]);
+
+        assert_eq!(chip8.cycle(), Ok(Chip8Output::Redraw));
+        assert_eq!(chip8.screen_size(), (128, 64));
+        assert!(chip8.gpu.pixels().iter().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    pub fn op_high_res_scales_display_with_quirk() {
+        let mut chip8 = Chip8::new_with_rom(resolution_switch_rom())
+            .with_resolution_switch_quirk(ResolutionSwitchQuirk::Scale);
+
+        chip8.cycle_n(4).unwrap();
+
+        assert_eq!(chip8.screen_size(), (128, 64));
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 8, 0, 3), [
+            [0, 0, 1, 1, 1, 1, 0, 0],
+            [0, 0, 1, 1, 1, 1, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0],
+        ]);
+    }
+
+    #[test]
+    pub fn quirks_exercised() {
+        let rom = Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x4 },
+            Opcode::ShiftRight { x: 0x0, y: 0x1 },
+            Opcode::AddConstant { x: 0x0, value: 0x1 },
         ]);
-        rom.extend(vec![0b11110000, 0b01101111]);
+
+        let mut chip8 = Chip8::new_with_rom(rom.clone()).with_quirk_tracking(true);
+        chip8.cycle_n(3).unwrap();
+        assert_eq!(chip8.quirks_exercised(), [Quirk::BitShift].iter().cloned().collect());
 
         let mut chip8 = Chip8::new_with_rom(rom);
         chip8.cycle_n(3).unwrap();
+        assert!(chip8.quirks_exercised().is_empty());
+    }
+
+    #[test]
+    pub fn strict_profile_rejects_unsupported_opcodes() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![Opcode::HighRes]))
+            .with_strict_profile(Some(QuirkProfile::Chip8));
+
+        assert_eq!(chip8.cycle(), Err(Chip8Error::UnsupportedOpcode(0x00FF)));
+        assert_eq!(chip8.screen_size(), (64, 32));
+
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![Opcode::HighRes]))
+            .with_strict_profile(Some(QuirkProfile::SuperChip));
+
+        assert_eq!(chip8.cycle(), Ok(Chip8Output::Redraw));
+        assert_eq!(chip8.screen_size(), (128, 64));
+    }
+
+    #[test]
+    pub fn resolution_switch_quirk_sets_gpu_scaling() {
+        let chip8 = Chip8::new().with_resolution_switch_quirk(ResolutionSwitchQuirk::Scale);
+        assert!(chip8.gpu.scale_on_switch());
+
+        let restored = Chip8::load_state(&chip8.save_state()).unwrap();
+        assert!(restored.gpu.scale_on_switch());
+
+        let chip8 = chip8.with_quirks(QuirkProfile::SuperChip.quirks());
+        assert!(!chip8.gpu.scale_on_switch());
+    }
+
+    #[test]
+    pub fn op_low_res_always_clears_display() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::HighRes,
+            Opcode::IndexFont { x: 0x0 },
+            Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 },
+            Opcode::LowRes,
+        ])).with_resolution_switch_quirk(ResolutionSwitchQuirk::Scale);
+
+        chip8.cycle_n(3).unwrap();
+        assert!(chip8.gpu.pixels().iter().any(|pixel| *pixel != 0));
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.screen_size(), (64, 32));
+        assert!(chip8.gpu.pixels().iter().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    pub fn op_draw_xors_overlapping_pixels() {
+        let mut rom = RomBuilder::new();
+        rom.push_ref("first", Opcode::IndexAddress);
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 });
+        rom.push_ref("second", Opcode::IndexAddress);
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 });
+        rom.data("first", &[0b11110000]);
+        rom.data("second", &[0b01101111]);
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.cycle_n(3).unwrap();
         assert_eq!(chip8.gpu.to_gfx_slice(0, 8, 0, 1), [[1, 1, 1, 1, 0, 0, 0, 0]]);
         chip8.cycle_n(2).unwrap();
         assert_eq!(chip8.gpu.to_gfx_slice(0, 8, 0, 1), [[1, 0, 0, 1, 1, 1, 1, 1]]);
     }
 
+    #[test]
+    pub fn op_draw_both_planes_combines_collision() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::SelectPlane { planes: 0x3 });
+        rom.push_ref("sprites", Opcode::IndexAddress);
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 });
+        // Plane 1 and plane 2 overlap on the first pixel only
+        rom.data("sprites", &[0b10000000, 0b11000000]);
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+
+        chip8.cycle_n(3).unwrap();
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 3, 0, 1), [[3, 2, 0]]);
+        assert_eq!(chip8.v[0xF], 0);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 3, 0, 1), [[0, 0, 0]]);
+        assert_eq!(chip8.v[0xF], 1);
+    }
+
+    #[test]
+    pub fn op_draw_collision_only_in_selected_planes() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::SelectPlane { planes: 0x2 });
+        rom.push_ref("sprite", Opcode::IndexAddress);
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 });
+        rom.push(Opcode::SelectPlane { planes: 0x1 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 });
+        rom.data("sprite", &[0b11110000]);
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        chip8.cycle_n(5).unwrap();
+
+        assert_eq!(chip8.v[0xF], 0);
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 4, 0, 1), [[3, 3, 3, 3]]);
+    }
+
+    #[test]
+    pub fn op_draw_or_mode_keeps_pixels_set() {
+        let mut rom = RomBuilder::new();
+        rom.push_ref("sprite", Opcode::IndexAddress);
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 });
+        rom.data("sprite", &[0b11110000]);
+        let rom = rom.build().unwrap();
+
+        let mut chip8 = Chip8::new_with_rom(rom.clone()).with_draw_mode_quirk(DrawModeQuirk::Or);
+        chip8.cycle_n(3).unwrap();
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 8, 0, 1), [[1, 1, 1, 1, 0, 0, 0, 0]]);
+        assert_eq!(chip8.v[0xF], 0);
+
+        let mut chip8 = Chip8::new_with_rom(rom);
+        chip8.cycle_n(3).unwrap();
+        assert_eq!(chip8.gpu.to_gfx_slice(0, 8, 0, 1), [[0, 0, 0, 0, 0, 0, 0, 0]]);
+        assert_eq!(chip8.v[0xF], 1);
+    }
+
     /// When `draw` overlaps a sprite we expect it to delete the existing pixels and sets `VF` to `1`.
     ///
     /// This behavior is commonly used for collision detection
     #[test]
     pub fn op_draw_collision_detection() {
-        let mut rom: Vec<u8> = Opcode::to_rom(vec![
-            Opcode::IndexAddress(0x200 + (2 * 5)), // Store the address of the first byte below
-            Opcode::LoadConstant { x: 0x0, value: 0 },
-            Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 },
-            Opcode::IndexAddress(0x200 + (2 * 5) + 1), // Store the address of the second byte below
-            Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 },
-        ]);
-        rom.extend(vec![0b11110000, 0b01101111]);
+        let mut rom = RomBuilder::new();
+        rom.push_ref("first", Opcode::IndexAddress);
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 });
+        rom.push_ref("second", Opcode::IndexAddress);
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x1 });
+        rom.data("first", &[0b11110000]);
+        rom.data("second", &[0b01101111]);
 
-        let mut chip8 = Chip8::new_with_rom(rom);
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
 
         chip8.cycle_n(3).unwrap();
         assert_eq!(chip8.v[0xF], 0);
@@ -1266,6 +5434,70 @@ mod tests {
     }
 
 
+    #[test]
+    pub fn op_save_range_ascending() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x300),
+            Opcode::SaveRange { x: 0x2, y: 0x4 },
+        ]));
+        chip8.v[0x2..=0x4].copy_from_slice(&[0x11, 0x22, 0x33]);
+
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.memory[0x300..0x304], [0x11, 0x22, 0x33, 0x00]);
+        assert_eq!(chip8.i, 0x300);
+    }
+
+    #[test]
+    pub fn op_save_range_descending() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x300),
+            Opcode::SaveRange { x: 0x4, y: 0x2 },
+        ]));
+        chip8.v[0x2..=0x4].copy_from_slice(&[0x11, 0x22, 0x33]);
+
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.memory[0x300..0x304], [0x33, 0x22, 0x11, 0x00]);
+    }
+
+    #[test]
+    pub fn op_load_range_ascending() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x300),
+            Opcode::LoadRange { x: 0x1, y: 0x3 },
+        ]));
+        chip8.write_memory_region(0x300, &[0xAA, 0xBB, 0xCC, 0xDD]).unwrap();
+
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.v[0x0..=0x4], [0x00, 0xAA, 0xBB, 0xCC, 0x00]);
+        assert_eq!(chip8.i, 0x300);
+    }
+
+    #[test]
+    pub fn op_load_range_descending() {
+        let mut chip8 = Chip8::new_with_rom(Opcode::to_rom(vec![
+            Opcode::IndexAddress(0x300),
+            Opcode::LoadRange { x: 0x3, y: 0x1 },
+        ]));
+        chip8.write_memory_region(0x300, &[0xAA, 0xBB, 0xCC, 0xDD]).unwrap();
+
+        chip8.cycle_n(2).unwrap();
+
+        assert_eq!(chip8.v[0x0..=0x4], [0x00, 0xCC, 0xBB, 0xAA, 0x00]);
+    }
+
+    #[test]
+    pub fn op_save_range_past_end_of_memory_errors() {
+        let mut chip8 = Chip8::new_with_rom(vec![])
+            .with_memory_bounds_quirk(MemoryBoundsQuirk::Error);
+        chip8.i = 0xFFE;
+
+        assert_eq!(chip8.execute(Opcode::SaveRange { x: 0x0, y: 0x2 }), Err(Chip8Error::MemoryOutOfBounds(0x1000)));
+        assert_eq!(chip8.execute(Opcode::SaveRange { x: 0x1, y: 0x0 }), Ok(Chip8Output::None));
+    }
+
     /// When using multiple `Opcode::ReadMemory`'s sequentually we expect it to start reading from
     /// where the previous read stopped.
     #[test]
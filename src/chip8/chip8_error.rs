@@ -1,10 +1,41 @@
 use std::fmt;
 use std::error;
+use std::io;
 
 #[derive(Debug, PartialEq)]
 pub enum Chip8Error {
     UnsupportedOpcode(u16),
-    StackUnderflow
+    StackUnderflow,
+
+    /// An IO operation failed. We keep the message rather than the `io::Error` so `Chip8Error`
+    /// can remain `PartialEq`.
+    Io(String),
+
+    /// An access to the given address fell outside of memory
+    MemoryOutOfBounds(usize),
+
+    /// A ROM of the given size in bytes does not fit in program memory
+    RomTooLarge(usize),
+
+    ParseError(String),
+
+    /// An opcode argument does not fit in the bits available to it
+    ArgumentOutOfRange { argument: &'static str, value: u16, max: u16 },
+
+    /// A save state could not be loaded, e.g. because it was written by an incompatible version
+    InvalidSaveState(String),
+
+    /// The given register index is not one of V0 through VF
+    InvalidRegister(u8),
+
+    /// Two machines running in `Lockstep` can no longer stay in sync
+    Desync(String),
+
+    /// A memory dump of `actual` bytes can't be loaded into `expected` bytes of memory
+    MemorySizeMismatch { expected: usize, actual: usize },
+
+    /// An image can't be converted into a sprite, e.g. because it isn't a sprite's size
+    InvalidImage(String),
 }
 
 impl fmt::Display for Chip8Error {
@@ -12,6 +43,18 @@ impl fmt::Display for Chip8Error {
         match self {
             Chip8Error::UnsupportedOpcode(value) => write!(f, "unsupported opcode: {:x}", value),
             Chip8Error::StackUnderflow => write!(f, "stack underflow!"),
+            Chip8Error::Io(message) => write!(f, "io error: {}", message),
+            Chip8Error::MemoryOutOfBounds(address) => write!(f, "memory access out of bounds: {:x}", address),
+            Chip8Error::RomTooLarge(size) => write!(f, "rom too large: {} bytes", size),
+            Chip8Error::ParseError(message) => write!(f, "parse error: {}", message),
+            Chip8Error::ArgumentOutOfRange { argument, value, max } =>
+                write!(f, "{} out of range: {:x} (max {:x})", argument, value, max),
+            Chip8Error::InvalidSaveState(message) => write!(f, "invalid save state: {}", message),
+            Chip8Error::InvalidRegister(x) => write!(f, "invalid register: V{:X}", x),
+            Chip8Error::Desync(message) => write!(f, "desync: {}", message),
+            Chip8Error::MemorySizeMismatch { expected, actual } =>
+                write!(f, "memory size mismatch: {} bytes (expected {})", actual, expected),
+            Chip8Error::InvalidImage(message) => write!(f, "invalid image: {}", message),
         }
     }
 }
@@ -20,6 +63,46 @@ impl error::Error for Chip8Error {
         match *self {
             Chip8Error::UnsupportedOpcode(_) => None,
             Chip8Error::StackUnderflow => None,
+            Chip8Error::Io(_) => None,
+            Chip8Error::MemoryOutOfBounds(_) => None,
+            Chip8Error::RomTooLarge(_) => None,
+            Chip8Error::ParseError(_) => None,
+            Chip8Error::ArgumentOutOfRange { .. } => None,
+            Chip8Error::InvalidSaveState(_) => None,
+            Chip8Error::InvalidRegister(_) => None,
+            Chip8Error::Desync(_) => None,
+            Chip8Error::MemorySizeMismatch { .. } => None,
+            Chip8Error::InvalidImage(_) => None,
         }
     }
 }
+
+impl From<io::Error> for Chip8Error {
+    fn from(error: io::Error) -> Chip8Error {
+        Chip8Error::Io(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn from_io_error() {
+        let io_error = io::Error::new(io::ErrorKind::NotFound, "missing rom");
+
+        let error: Chip8Error = io_error.into();
+
+        assert_eq!(error, Chip8Error::Io("missing rom".to_string()));
+        assert_eq!(error.to_string(), "io error: missing rom");
+    }
+
+    #[test]
+    pub fn question_mark_converts_io_errors() {
+        fn read_rom() -> Result<Vec<u8>, Chip8Error> {
+            Ok(std::fs::read("/this/rom/does/not/exist")?)
+        }
+
+        assert!(matches!(read_rom(), Err(Chip8Error::Io(_))));
+    }
+}
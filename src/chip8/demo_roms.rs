@@ -0,0 +1,71 @@
+use crate::chip8::Chip8;
+
+/// ROMs from the [Chip-8 Games Pack](https://www.zophar.net/pdroms/chip8/chip-8-games-pack.html)
+/// embedded directly into the binary.
+const DEMO_ROMS: [(&str, &[u8]); 23] = [
+    ("15PUZZLE", include_bytes!("../../roms/15PUZZLE")),
+    ("BLINKY", include_bytes!("../../roms/BLINKY")),
+    ("BLITZ", include_bytes!("../../roms/BLITZ")),
+    ("BRIX", include_bytes!("../../roms/BRIX")),
+    ("CONNECT4", include_bytes!("../../roms/CONNECT4")),
+    ("GUESS", include_bytes!("../../roms/GUESS")),
+    ("HIDDEN", include_bytes!("../../roms/HIDDEN")),
+    ("INVADERS", include_bytes!("../../roms/INVADERS")),
+    ("KALEID", include_bytes!("../../roms/KALEID")),
+    ("MAZE", include_bytes!("../../roms/MAZE")),
+    ("MERLIN", include_bytes!("../../roms/MERLIN")),
+    ("MISSILE", include_bytes!("../../roms/MISSILE")),
+    ("PONG", include_bytes!("../../roms/PONG")),
+    ("PONG2", include_bytes!("../../roms/PONG2")),
+    ("PUZZLE", include_bytes!("../../roms/PUZZLE")),
+    ("SYZYGY", include_bytes!("../../roms/SYZYGY")),
+    ("TANK", include_bytes!("../../roms/TANK")),
+    ("TETRIS", include_bytes!("../../roms/TETRIS")),
+    ("TICTAC", include_bytes!("../../roms/TICTAC")),
+    ("UFO", include_bytes!("../../roms/UFO")),
+    ("VBRIX", include_bytes!("../../roms/VBRIX")),
+    ("VERS", include_bytes!("../../roms/VERS")),
+    ("WIPEOFF", include_bytes!("../../roms/WIPEOFF")),
+];
+
+impl Chip8 {
+    /// Returns a `Chip8` loaded with the built-in ROM called `name`, e.g. `"PONG"`.
+    ///
+    /// Names are matched case-insensitively. Returns `None` if no ROM with that name exists.
+    pub fn demo(name: &str) -> Option<Chip8> {
+        DEMO_ROMS.iter()
+            .find(|(rom_name, _)| rom_name.eq_ignore_ascii_case(name))
+            .map(|(_, rom)| Chip8::new_with_rom_slice(rom))
+    }
+
+    /// Returns the names of all built-in ROMs available via `Chip8::demo`.
+    pub fn demo_names() -> impl Iterator<Item = &'static str> {
+        DEMO_ROMS.iter().map(|(name, _)| *name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn demo_loads_rom_by_name() {
+        let chip8 = Chip8::demo("pong").unwrap();
+
+        let pong = include_bytes!("../../roms/PONG");
+        let rom_start = chip8.start_address() as usize;
+        assert_eq!(chip8.memory[rom_start..rom_start + pong.len()], pong[..]);
+    }
+
+    #[test]
+    pub fn demo_returns_none_for_unknown_rom() {
+        assert!(Chip8::demo("NOT_A_ROM").is_none());
+    }
+
+    #[test]
+    pub fn demo_names_can_all_be_loaded() {
+        for name in Chip8::demo_names() {
+            assert!(Chip8::demo(name).is_some());
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use crate::chip8::{Chip8, Chip8Output, Chip8Result};
+
+/// An input event reported by a `DisplayBackend`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BackendEvent {
+    /// The Chip-8 key with the given index was pressed
+    KeyDown(u8),
+
+    /// The Chip-8 key with the given index was released
+    KeyUp(u8),
+
+    /// The user asked to stop emulation, e.g. by closing the window
+    Quit,
+}
+
+/// `DisplayBackend` is implemented by front-ends that want `run_loop` to drive emulation.
+///
+/// `run_loop` calls `poll_events` once per frame and `present` whenever the display changes.
+/// Backends are responsible for pacing frames, e.g. by waiting for vsync in `present`.
+pub trait DisplayBackend {
+    /// Show `framebuffer` which contains `width * height` pixels in row-major order.
+    /// `0x0` is an empty pixel, anything else is filled.
+    fn present(&mut self, framebuffer: &[u8], width: usize, height: usize);
+
+    /// Return every input event that happened since the last call.
+    fn poll_events(&mut self) -> Vec<BackendEvent>;
+}
+
+/// The amount of emulated time that passes for each frame of `run_loop`
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Run `chip8` until `backend` reports `BackendEvent::Quit`, advancing it by `FRAME_DURATION` per frame.
+///
+/// The current display is presented once before the first frame.
+pub fn run_loop<B: DisplayBackend>(chip8: &mut Chip8, backend: &mut B) -> Chip8Result<()> {
+    let (width, height) = chip8.screen_size();
+    backend.present(chip8.gpu.pixels(), width, height);
+
+    loop {
+        for event in backend.poll_events() {
+            match event {
+                BackendEvent::KeyDown(key) => chip8.press_key(key),
+                BackendEvent::KeyUp(key) => chip8.release_key(key),
+                BackendEvent::Quit => return Ok(()),
+            }
+        }
+
+        if chip8.tick(FRAME_DURATION)? == Chip8Output::Redraw {
+            let (width, height) = chip8.screen_size();
+            backend.present(chip8.gpu.pixels(), width, height);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::{Opcode, RomBuilder};
+
+    /// Records every presented frame and replays a fixed list of events per frame
+    struct MockBackend {
+        frames: Vec<Vec<u8>>,
+        events: Vec<Vec<BackendEvent>>,
+    }
+
+    impl DisplayBackend for MockBackend {
+        fn present(&mut self, framebuffer: &[u8], width: usize, height: usize) {
+            assert_eq!(framebuffer.len(), width * height);
+            self.frames.push(framebuffer.to_vec());
+        }
+
+        fn poll_events(&mut self) -> Vec<BackendEvent> {
+            if self.events.is_empty() {
+                vec![BackendEvent::Quit]
+            } else {
+                self.events.remove(0)
+            }
+        }
+    }
+
+    #[test]
+    pub fn run_loop_presents_frames_until_quit() {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x0, value: 0x0 });
+        rom.push(Opcode::IndexFont { x: 0x0 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x5 });
+        rom.label("loop");
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        let mut chip8 = Chip8::new_with_rom(rom.build().unwrap());
+        let mut backend = MockBackend { frames: Vec::new(), events: vec![Vec::new(), Vec::new()] };
+
+        run_loop(&mut chip8, &mut backend).unwrap();
+
+        assert_eq!(backend.frames.len(), 2);
+        assert!(backend.frames[0].iter().all(|pixel| *pixel == 0));
+        assert_eq!(backend.frames[1][0..4], [1, 1, 1, 1]);
+    }
+
+    #[test]
+    pub fn run_loop_forwards_key_events() {
+        let mut chip8 = Chip8::new_with_default_rom();
+        let mut backend = MockBackend {
+            frames: Vec::new(),
+            events: vec![vec![BackendEvent::KeyDown(0xA), BackendEvent::KeyDown(0x1)], vec![BackendEvent::KeyUp(0x1)]],
+        };
+
+        run_loop(&mut chip8, &mut backend).unwrap();
+
+        assert!(chip8.keys[0xA]);
+        assert!(!chip8.keys[0x1]);
+    }
+}
@@ -0,0 +1,111 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use gif::{Encoder, ExtensionData, Frame, Repeat};
+
+use crate::chip8::{Gpu, Chip8Result};
+
+/// `GifRecorder` writes the display to an animated GIF, one frame per 60hz frame.
+///
+/// The size of the GIF is fixed by the resolution when recording starts. Frames drawn at a
+/// different resolution are scaled to fit with nearest-neighbour sampling.
+pub struct GifRecorder {
+    encoder: Encoder<SharedWriter>,
+
+    /// The file `encoder` writes to, kept so `finish` can flush it and report any error
+    writer: SharedWriter,
+
+    width: usize,
+    height: usize,
+    frames: u64,
+
+    /// The first error from writing a frame, returned by `finish`
+    error: Option<io::Error>,
+}
+
+impl GifRecorder {
+    /// Empty pixels are black and filled pixels are white
+    const PALETTE: [u8; 6] = [0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF];
+
+    pub fn create<P: AsRef<Path>>(path: P, gpu: &Gpu) -> Chip8Result<GifRecorder> {
+        let writer = SharedWriter(Rc::new(RefCell::new(BufWriter::new(File::create(path)?))));
+        let mut encoder = Encoder::new(writer.clone(), gpu.width() as u16, gpu.height() as u16, &GifRecorder::PALETTE)?;
+        encoder.write_extension(ExtensionData::Repetitions(Repeat::Infinite))?;
+
+        Ok(GifRecorder {
+            encoder,
+            writer,
+            width: gpu.width(),
+            height: gpu.height(),
+            frames: 0,
+            error: None,
+        })
+    }
+
+    /// Append the current contents of `gpu`. Errors are kept until `finish` so a failing disk
+    /// doesn't interrupt the program being recorded.
+    pub fn add_frame(&mut self, gpu: &Gpu) {
+        if self.error.is_some() {
+            return;
+        }
+
+        // GIF delays are in hundredths of a second, so alternate between 1 and 2 to average 60hz
+        self.frames += 1;
+        let delay = (self.frames * 100 / 60) - ((self.frames - 1) * 100 / 60);
+
+        let mut buffer = Vec::with_capacity(self.width * self.height);
+        for y in 0..self.height {
+            let source_y = y * gpu.height() / self.height;
+            for x in 0..self.width {
+                let source_x = x * gpu.width() / self.width;
+                let pixel = gpu.pixels()[source_y * gpu.width() + source_x];
+                buffer.push(if pixel == 0 { 0 } else { 1 });
+            }
+        }
+
+        let frame = Frame {
+            width: self.width as u16,
+            height: self.height as u16,
+            delay: delay as u16,
+            buffer: buffer.into(),
+            ..Frame::default()
+        };
+
+        if let Err(e) = self.encoder.write_frame(&frame) {
+            self.error = Some(e);
+        }
+    }
+
+    /// Finish the GIF, returning the first error that happened while recording or while
+    /// flushing the file
+    pub fn finish(self) -> Chip8Result<()> {
+        let GifRecorder { encoder, mut writer, error, .. } = self;
+
+        // The encoder only writes the end of the GIF when it's dropped
+        drop(encoder);
+        if let Some(e) = error {
+            return Err(e.into());
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// A writer shared between the encoder and `GifRecorder`. `Encoder` never gives its writer back
+// and ignores errors when it's dropped, so this is the only way to flush it ourselves.
+#[derive(Clone)]
+struct SharedWriter(Rc<RefCell<BufWriter<File>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
@@ -1,23 +1,125 @@
-use arrayvec::ArrayVec;
 use std::fmt;
 
+use crate::chip8::{SpriteWrapQuirk, DrawModeQuirk};
+
 /// `Gpu` represents the Chip-8 display. The Chip-8 has a 64x32 display consisting of an
-/// empty colour and a filled colour.
+/// empty colour and a filled colour. SUPER-CHIP adds a 128x64 high resolution mode, see
+/// `set_resolution`.
 ///
-/// If `pixels[y * Chip8::SCREEN_WIDTH + x]` is `0x0` then the pixel at `(x, y)` should be empty,
+/// If `pixels[y * width + x]` is `0x0` then the pixel at `(x, y)` should be empty,
 /// otherwise it should be filled.
 ///
+/// XO-CHIP extends the display with a second bit plane. Each pixel is a bitmask where bit 0 is
+/// plane 1 and bit 1 is plane 2. Drawing and clearing only affect the selected planes, which
+/// defaults to plane 1 only.
+///
 /// The specific colour of "filled" and "empty" should be defined by the rendering system.
 pub struct Gpu {
-    pixels: [u8; Gpu::SCREEN_PIXELS]
+    pixels: Vec<u8>,
+
+    resolution: Resolution,
+
+    /// Upscale the display instead of clearing it when switching from low to high resolution
+    scale_on_switch: bool,
+
+    /// Bitmask of the planes affected by `draw` and `clear`
+    selected_planes: u8,
+
+    /// The `(x, y, row_bytes, planes, wrap)` of the most recent `draw`, used by `undo_last_draw`
+    last_draw: Option<(usize, usize, usize, u8, SpriteWrapQuirk)>,
+
+    /// The sprite of the most recent `draw`. Reused between draws so drawing doesn't allocate
+    last_draw_sprite: Vec<u8>,
+
+    /// Whether each row of the sprite being drawn `(collided, clipped)`, reused between draws
+    draw_rows_scratch: Vec<(bool, bool)>,
+
+    /// Record where each `draw` collided in `last_collision_pixels`
+    track_collisions: bool,
+
+    last_collision_pixels: Vec<(usize, usize)>,
+
+    last_draw_rows: DrawRows,
+
+    last_draw_result: DrawResult,
+
+    /// The display as of the last `present`, when presenting on vblank. `None` means every
+    /// change is shown immediately.
+    front: Option<(Resolution, Vec<u8>)>,
+
+    /// Whether each row of `pixels` changed since the last `clear_dirty_rows`, or since the last
+    /// `present` when presenting on vblank
+    dirty_rows: Vec<bool>,
+
+    /// Whether each row of the front buffer changed since the last `clear_dirty_rows`. Only used
+    /// when presenting on vblank.
+    presented_dirty_rows: Vec<bool>,
+}
+
+/// The display resolutions supported by `Gpu`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Resolution {
+    /// The original 64x32 Chip-8 display
+    LowRes,
+
+    /// The 128x64 SUPER-CHIP display
+    HighRes,
+}
+
+impl Resolution {
+    pub fn width(&self) -> usize {
+        match self {
+            Resolution::LowRes => Gpu::SCREEN_WIDTH,
+            Resolution::HighRes => Gpu::SCREEN_WIDTH * 2,
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        match self {
+            Resolution::LowRes => Gpu::SCREEN_HEIGHT,
+            Resolution::HighRes => Gpu::SCREEN_HEIGHT * 2,
+        }
+    }
+
+    pub fn pixels(&self) -> usize {
+        self.width() * self.height()
+    }
+}
+
+impl Default for Resolution {
+    fn default() -> Resolution {
+        Resolution::LowRes
+    }
+}
+
+/// How the rows of the most recent `draw` were affected, see `Gpu::last_draw_rows`.
+///
+/// Rows are sprite rows, so with several planes selected a row that collides in more than one
+/// plane is only counted once.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct DrawRows {
+    /// The number of rows where at least one pixel was turned off
+    pub collided: usize,
+
+    /// The number of rows that were clipped off the bottom of the screen
+    pub clipped: usize,
 }
 
-pub enum DrawResult {
-    NoCollision,
-    Collision
+/// The outcome of a `Gpu::draw`
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct DrawResult {
+    /// True if at least one pixel was turned off, which is what `DRAW` reports in `VF`
+    pub collision: bool,
+
+    /// The number of pixels that were turned on or off. Zero means the display didn't change,
+    /// e.g. because the sprite was empty or clipped entirely.
+    ///
+    /// A pixel drawn in several planes is counted once for each plane.
+    pub pixels_changed: u32,
 }
 
 impl Gpu {
+    /// The dimensions of the low resolution display, see `Resolution` for high resolution
     pub const SCREEN_WIDTH: usize = 64;
     pub const SCREEN_HEIGHT: usize = 32;
     pub const SCREEN_PIXELS: usize = Gpu::SCREEN_WIDTH * Gpu::SCREEN_HEIGHT;
@@ -25,44 +127,524 @@ impl Gpu {
     pub const BLACK: [u8; 4] = [0x00, 0x00, 0x00, 0x00];
     pub const WHITE: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
 
+    pub const PLANE_1: u8 = 0b01;
+    pub const PLANE_2: u8 = 0b10;
+
     pub fn new() -> Gpu {
         Gpu {
-            pixels: [0; Gpu::SCREEN_PIXELS]
+            pixels: vec![0; Gpu::SCREEN_PIXELS],
+            resolution: Resolution::LowRes,
+            scale_on_switch: false,
+            selected_planes: Gpu::PLANE_1,
+            last_draw: None,
+            last_draw_sprite: Vec::new(),
+            draw_rows_scratch: Vec::new(),
+            track_collisions: false,
+            last_collision_pixels: Vec::new(),
+            last_draw_rows: DrawRows::default(),
+            last_draw_result: DrawResult::default(),
+            front: None,
+            dirty_rows: vec![true; Gpu::SCREEN_HEIGHT],
+            presented_dirty_rows: vec![true; Gpu::SCREEN_HEIGHT],
+        }
+    }
+
+    /// Upscale the existing display instead of clearing it when `set_resolution` switches from
+    /// low to high resolution.
+    pub fn with_scale_on_switch(mut self, scale_on_switch: bool) -> Self {
+        self.scale_on_switch = scale_on_switch;
+        self
+    }
+
+    /// Like `with_scale_on_switch`. A `Chip8` sets this from its `ResolutionSwitchQuirk`.
+    pub fn set_scale_on_switch(&mut self, scale_on_switch: bool) {
+        self.scale_on_switch = scale_on_switch;
+    }
+
+    pub fn scale_on_switch(&self) -> bool {
+        self.scale_on_switch
+    }
+
+    /// Record the pixels each `draw` collides with, see `last_collision_pixels`.
+    pub fn with_collision_tracking(mut self, track_collisions: bool) -> Self {
+        self.set_collision_tracking(track_collisions);
+        self
+    }
+
+    /// Turn collision tracking on or off. Tracking is off by default since most hosts only need
+    /// the `VF` flag.
+    pub fn set_collision_tracking(&mut self, track_collisions: bool) {
+        self.track_collisions = track_collisions;
+        self.last_collision_pixels.clear();
+    }
+
+    pub fn collision_tracking(&self) -> bool {
+        self.track_collisions
+    }
+
+    /// The `(x, y)` of every pixel the most recent `draw` turned off, in the order they were
+    /// drawn. Always empty unless collision tracking is enabled with `with_collision_tracking`.
+    ///
+    /// A pixel that collides in both planes is only reported once.
+    pub fn last_collision_pixels(&self) -> &[(usize, usize)] {
+        &self.last_collision_pixels
+    }
+
+    /// How many rows of the most recent `draw` collided or were clipped. Super Chip-8 1.1
+    /// reports the sum of these in `VF`, see `CollisionCountQuirk`.
+    pub fn last_draw_rows(&self) -> DrawRows {
+        self.last_draw_rows
+    }
+
+    /// The result of the most recent `draw`, including when it was drawn by a `DRAW` opcode. A
+    /// host can skip uploading the display when `pixels_changed` is zero.
+    pub fn last_draw_result(&self) -> DrawResult {
+        self.last_draw_result
+    }
+
+    /// Only show changes to the display when `present` is called, see `set_present_on_vblank`.
+    pub fn with_present_on_vblank(mut self, present_on_vblank: bool) -> Self {
+        self.set_present_on_vblank(present_on_vblank);
+        self
+    }
+
+    /// When enabled, drawing only changes a back buffer and `to_rgba` keeps showing the display
+    /// as it was at the last `present`. `Chip8` presents at the end of every 60hz frame, so a
+    /// sprite that is erased and redrawn within a frame never appears to flicker.
+    ///
+    /// Enabling this presents the current display.
+    pub fn set_present_on_vblank(&mut self, present_on_vblank: bool) {
+        self.front = if present_on_vblank {
+            Some((self.resolution, self.pixels.clone()))
+        } else {
+            None
+        };
+
+        // The host may have been showing a different buffer, so everything has to be redrawn
+        self.mark_all_rows_dirty();
+        if present_on_vblank {
+            let height = self.height();
+            self.presented_dirty_rows = std::mem::replace(&mut self.dirty_rows, vec![false; height]);
+        }
+    }
+
+    pub fn present_on_vblank(&self) -> bool {
+        self.front.is_some()
+    }
+
+    /// Copy the back buffer to the front buffer. Does nothing unless presenting on vblank.
+    pub fn present(&mut self) {
+        if let Some((resolution, front)) = self.front.as_mut() {
+            *resolution = self.resolution;
+            front.clear();
+            front.extend_from_slice(&self.pixels);
+
+            if self.presented_dirty_rows.len() == self.dirty_rows.len() {
+                for (presented, dirty) in self.presented_dirty_rows.iter_mut().zip(self.dirty_rows.iter()) {
+                    *presented |= *dirty;
+                }
+            } else {
+                self.presented_dirty_rows = vec![true; self.dirty_rows.len()];
+            }
+
+            self.dirty_rows = vec![false; self.dirty_rows.len()];
+        }
+    }
+
+    /// The rows of `presented_pixels` that changed since the last `clear_dirty_rows`, in
+    /// ascending order. A row is dirty if any pixel in it was drawn, even if drawing it twice
+    /// left it as it was.
+    ///
+    /// Hosts can use this to only re-render the changed scanlines, e.g. for CRT effects. Every
+    /// row starts out dirty, and clearing, switching resolution or loading a display dirties them
+    /// all. When presenting on vblank, changes only become dirty rows once they're presented.
+    pub fn dirty_rows(&self) -> Vec<usize> {
+        let rows = match self.front {
+            Some(_) => &self.presented_dirty_rows,
+            None => &self.dirty_rows,
+        };
+
+        rows.iter()
+            .enumerate()
+            .filter(|(_, dirty)| **dirty)
+            .map(|(row, _)| row)
+            .collect()
+    }
+
+    /// Acknowledge that the host has rendered the current `dirty_rows`
+    pub fn clear_dirty_rows(&mut self) {
+        let rows = match self.front {
+            Some(_) => &mut self.presented_dirty_rows,
+            None => &mut self.dirty_rows,
+        };
+
+        for row in rows.iter_mut() {
+            *row = false;
         }
     }
 
+    fn mark_all_rows_dirty(&mut self) {
+        self.dirty_rows = vec![true; self.height()];
+    }
+
+    /// The pixels that should be shown, in the same format as `pixels`. This is the front buffer
+    /// when presenting on vblank and the display itself otherwise.
+    pub fn presented_pixels(&self) -> &[u8] {
+        match &self.front {
+            Some((_, front)) => front,
+            None => &self.pixels,
+        }
+    }
+
+    /// The `(width, height)` of `presented_pixels`, which can lag behind `resolution` until the
+    /// next `present`.
+    pub fn presented_size(&self) -> (usize, usize) {
+        let resolution = match &self.front {
+            Some((resolution, _)) => *resolution,
+            None => self.resolution,
+        };
+
+        (resolution.width(), resolution.height())
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Switch the display to `resolution`.
+    ///
+    /// Switching resolution clears the display in every plane. With `with_scale_on_switch`,
+    /// switching from low to high resolution instead scales each low resolution pixel up to a
+    /// 2x2 block so the existing image is preserved. Setting the current resolution does nothing.
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.switch_resolution(resolution, self.scale_on_switch);
+    }
+
+    /// Like `set_resolution` but `scale` decides whether switching from low to high resolution
+    /// preserves the image, ignoring `with_scale_on_switch`.
+    pub fn switch_resolution(&mut self, resolution: Resolution, scale: bool) {
+        if resolution == self.resolution {
+            return;
+        }
+
+        let mut pixels = vec![0; resolution.pixels()];
+        if scale && resolution == Resolution::HighRes {
+            let width = resolution.width();
+            for (index, pixel) in pixels.iter_mut().enumerate() {
+                let (x, y) = (index % width, index / width);
+                *pixel = self.pixels[(y / 2) * self.width() + (x / 2)];
+            }
+        }
+
+        self.pixels = pixels;
+        self.resolution = resolution;
+        self.last_draw = None;
+        self.mark_all_rows_dirty();
+    }
+
+    /// Select which planes `draw` and `clear` affect. `planes` is a bitmask of `PLANE_1` and `PLANE_2`.
+    pub fn select_planes(&mut self, planes: u8) {
+        self.selected_planes = planes & (Gpu::PLANE_1 | Gpu::PLANE_2);
+    }
+
+    pub fn selected_planes(&self) -> u8 {
+        self.selected_planes
+    }
+
+    /// The number of planes that will be drawn to by `draw`
+    pub fn selected_plane_count(&self) -> usize {
+        self.selected_planes.count_ones() as usize
+    }
+
+    /// The width in pixels of the active display resolution
+    pub fn width(&self) -> usize {
+        self.resolution.width()
+    }
+
+    /// The height in pixels of the active display resolution
+    pub fn height(&self) -> usize {
+        self.resolution.height()
+    }
+
+    /// Clear the selected planes
     pub fn clear(&mut self) {
-        self.pixels = [0; Gpu::SCREEN_PIXELS];
+        for pixel in self.pixels.iter_mut() {
+            *pixel &= !self.selected_planes;
+        }
+        self.last_draw = None;
+        self.mark_all_rows_dirty();
+    }
+
+    /// The raw display, one byte per pixel in row-major order. `0x0` is empty, anything else is filled.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
     }
 
+    /// The pixel at `(x, y)`, which marks row `y` as dirty. See `dirty_rows`.
     pub fn pixel(&mut self, x: usize, y: usize) -> &mut u8 {
-        &mut self.pixels[(y * Gpu::SCREEN_WIDTH) + x]
+        let width = self.width();
+        self.dirty_rows[y] = true;
+        &mut self.pixels[(y * width) + x]
+    }
+
+    /// XOR `sprite` onto the selected planes at `(x, y)`.
+    ///
+    /// When several planes are selected `sprite` holds the rows for each plane in turn, e.g.
+    /// with both planes selected the first half of `sprite` is drawn to plane 1 and the second
+    /// half to plane 2. A collision in any selected plane is reported as a collision.
+    ///
+    /// `(x, y)` is always wrapped onto the screen first. `wrap` then decides whether pixels past
+    /// each edge of the screen wrap around or are clipped.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8], wrap: SpriteWrapQuirk) -> DrawResult {
+        self.draw_with_mode(x, y, sprite, wrap, DrawModeQuirk::Xor)
+    }
+
+    /// Like `draw` but `mode` decides how the sprite is combined with the display.
+    ///
+    /// `DrawModeQuirk::Or` only ever sets pixels, so it never collides and can't be undone with
+    /// `undo_last_draw`.
+    pub fn draw_with_mode(&mut self, x: usize, y: usize, sprite: &[u8], wrap: SpriteWrapQuirk, mode: DrawModeQuirk) -> DrawResult {
+        self.draw_rows(x, y, sprite, 1, wrap, mode)
+    }
+
+    /// Like `draw_with_mode` but draws the SUPER-CHIP 16x16 sprite, where each row is 2 bytes
+    /// wide. `sprite` holds 32 bytes for each selected plane.
+    pub fn draw_16x16(&mut self, x: usize, y: usize, sprite: &[u8], wrap: SpriteWrapQuirk, mode: DrawModeQuirk) -> DrawResult {
+        self.draw_rows(x, y, sprite, 2, wrap, mode)
     }
 
-    pub fn draw(&mut self, x: usize, y: usize, sprite: Vec<u8>) -> DrawResult {
-        let mut draw_result: DrawResult = DrawResult::NoCollision;
+    // Draw `sprite` as rows that are each `row_bytes` wide, see `draw_with_mode`
+    fn draw_rows(&mut self, x: usize, y: usize, sprite: &[u8], row_bytes: usize, wrap: SpriteWrapQuirk, mode: DrawModeQuirk) -> DrawResult {
+        if mode == DrawModeQuirk::Or {
+            let pixels_changed = self.or_sprite(x, y, sprite, row_bytes, wrap);
+            self.last_collision_pixels.clear();
+            self.last_draw_rows = DrawRows::default();
+            self.last_draw = None;
+            self.last_draw_result = DrawResult { collision: false, pixels_changed };
+            return self.last_draw_result;
+        }
+
+        let mut collisions = if self.track_collisions { Some(Vec::new()) } else { None };
+        let (draw_result, rows) = self.xor_sprite(x, y, sprite, row_bytes, self.selected_planes, wrap, collisions.as_mut());
+        self.last_draw_rows = rows;
+        if let Some(collisions) = collisions {
+            self.last_collision_pixels = collisions;
+        }
 
-        for (pixel_y, row_sprite) in sprite.iter().enumerate() {
-            let y = (y + pixel_y) as usize % Gpu::SCREEN_HEIGHT;
+        self.last_draw_sprite.clear();
+        self.last_draw_sprite.extend_from_slice(sprite);
+        self.last_draw = Some((x, y, row_bytes, self.selected_planes, wrap));
+        self.last_draw_result = draw_result;
+        draw_result
+    }
 
-            for pixel_x in 0..8 {
-                let bit = (row_sprite >> (7 - pixel_x)) & 0x1;
-                if bit != 0 {
-                    let x = (x + pixel_x) as usize % Gpu::SCREEN_WIDTH;
-                    let pixel = self.pixel(x, y);
-                    if *pixel == 1 {
-                        draw_result = DrawResult::Collision;
+    /// Reverse the most recent `draw` by drawing the same sprite again, which XORs it back out.
+    ///
+    /// Returns false if there was nothing to undo, either because nothing has been drawn or
+    /// because the display was cleared since the last draw.
+    pub fn undo_last_draw(&mut self) -> bool {
+        match self.last_draw.take() {
+            Some((x, y, row_bytes, planes, wrap)) => {
+                let sprite = std::mem::take(&mut self.last_draw_sprite);
+                self.xor_sprite(x, y, &sprite, row_bytes, planes, wrap, None);
+                self.last_draw_sprite = sprite;
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Split `sprite` into the bytes for each plane in `planes`, see `draw`
+    fn plane_sprites(sprite: &[u8], planes: u8) -> impl Iterator<Item = (u8, &[u8])> {
+        let bytes_per_plane = Gpu::plane_sprite_bytes(sprite, planes);
+        [Gpu::PLANE_1, Gpu::PLANE_2].iter()
+            .copied()
+            .filter(move |plane| planes & plane != 0)
+            .enumerate()
+            .map(move |(i, plane)| (plane, &sprite[i * bytes_per_plane..(i + 1) * bytes_per_plane]))
+    }
+
+    // The number of bytes `sprite` has in each of `planes`
+    fn plane_sprite_bytes(sprite: &[u8], planes: u8) -> usize {
+        match (planes & (Gpu::PLANE_1 | Gpu::PLANE_2)).count_ones() as usize {
+            0 => 0,
+            plane_count => sprite.len() / plane_count,
+        }
+    }
+
+    // Returns the screen coordinate `offset` pixels past `start`, or `None` if it's clipped.
+    //
+    // The starting coordinate always wraps onto the screen, `wrap` only decides what happens to
+    // the pixels that extend past the edge from there.
+    fn sprite_coordinate(start: usize, offset: usize, size: usize, wrap: bool) -> Option<usize> {
+        let coordinate = (start % size) + offset;
+        if wrap {
+            Some(coordinate % size)
+        } else if coordinate < size {
+            Some(coordinate)
+        } else {
+            None
+        }
+    }
+
+    // XOR `sprite` onto `planes` as rows of `row_bytes`, adding the coordinates of any collisions
+    // to `collisions`
+    #[allow(clippy::too_many_arguments)]
+    fn xor_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+        row_bytes: usize,
+        planes: u8,
+        wrap: SpriteWrapQuirk,
+        mut collisions: Option<&mut Vec<(usize, usize)>>,
+    ) -> (DrawResult, DrawRows) {
+        let mut draw_result = DrawResult::default();
+
+        // Whether each sprite row collided or was clipped, in any plane
+        let mut rows = std::mem::take(&mut self.draw_rows_scratch);
+        rows.clear();
+        rows.resize(Gpu::plane_sprite_bytes(sprite, planes) / row_bytes, (false, false));
+
+        for (plane, plane_sprite) in Gpu::plane_sprites(sprite, planes) {
+            for (pixel_y, row) in plane_sprite.chunks(row_bytes).enumerate() {
+                let y = match Gpu::sprite_coordinate(y, pixel_y, self.height(), wrap.vertical_wrap) {
+                    Some(y) => y,
+                    None => {
+                        rows[pixel_y].1 = true;
+                        continue;
+                    },
+                };
+
+                for (byte_x, row_sprite) in row.iter().copied().enumerate() {
+                    let byte_x = byte_x * 8;
+
+                    // Bytes that fit on screen don't need any per-pixel wrapping or clipping. Collision
+                    // tracking needs the coordinates of each collision so always takes the slow path.
+                    let start_x = (x % self.width()) + byte_x;
+                    if collisions.is_none() && start_x + 8 <= self.width() {
+                        if self.xor_row(start_x, y, row_sprite, plane) {
+                            draw_result.collision = true;
+                            rows[pixel_y].0 = true;
+                        }
+                        draw_result.pixels_changed += row_sprite.count_ones();
+                        continue;
                     }
 
-                    *pixel ^= 1;
+                    for pixel_x in 0..8 {
+                        let bit = (row_sprite >> (7 - pixel_x)) & 0x1;
+                        if bit == 0 {
+                            continue;
+                        }
+
+                        let x = match Gpu::sprite_coordinate(x, byte_x + pixel_x, self.width(), wrap.horizontal_wrap) {
+                            Some(x) => x,
+                            None => continue,
+                        };
+                        let pixel = self.pixel(x, y);
+                        if *pixel & plane != 0 {
+                            draw_result.collision = true;
+                            rows[pixel_y].0 = true;
+
+                            if let Some(collisions) = collisions.as_mut() {
+                                if !collisions.contains(&(x, y)) {
+                                    collisions.push((x, y));
+                                }
+                            }
+                        }
+
+                        *pixel ^= plane;
+                        draw_result.pixels_changed += 1;
+                    }
                 }
             }
         }
 
-        draw_result
+        let draw_rows = DrawRows {
+            collided: rows.iter().filter(|(collided, _)| *collided).count(),
+            clipped: rows.iter().filter(|(_, clipped)| *clipped).count(),
+        };
+        self.draw_rows_scratch = rows;
+
+        (draw_result, draw_rows)
     }
 
-    /// Convert the current display to a RGBA texture.
+    // XOR all 8 pixels of `row_sprite` onto `plane` at `(x, y)` at once, without checking each
+    // bit. The whole row must fit on screen. Returns true if any pixel was turned off.
+    fn xor_row(&mut self, x: usize, y: usize, row_sprite: u8, plane: u8) -> bool {
+        if row_sprite == 0 {
+            return false;
+        }
+
+        let start = (y * self.width()) + x;
+        self.dirty_rows[y] = true;
+
+        let mut collided = 0;
+        for (pixel_x, pixel) in self.pixels[start..start + 8].iter_mut().enumerate() {
+            let mask = ((row_sprite >> (7 - pixel_x)) & 0x1) * plane;
+            collided |= *pixel & mask;
+            *pixel ^= mask;
+        }
+
+        collided != 0
+    }
+
+    // OR `sprite` onto the selected planes as rows of `row_bytes`, returning the number of pixels
+    // that were turned on
+    fn or_sprite(&mut self, x: usize, y: usize, sprite: &[u8], row_bytes: usize, wrap: SpriteWrapQuirk) -> u32 {
+        let mut pixels_changed = 0;
+        for (plane, plane_sprite) in Gpu::plane_sprites(sprite, self.selected_planes) {
+            for (pixel_y, row) in plane_sprite.chunks(row_bytes).enumerate() {
+                let y = match Gpu::sprite_coordinate(y, pixel_y, self.height(), wrap.vertical_wrap) {
+                    Some(y) => y,
+                    None => continue,
+                };
+
+                for pixel_x in 0..row.len() * 8 {
+                    let bit = (row[pixel_x / 8] >> (7 - pixel_x % 8)) & 0x1;
+                    let x = Gpu::sprite_coordinate(x, pixel_x, self.width(), wrap.horizontal_wrap);
+                    if let (1, Some(x)) = (bit, x) {
+                        let pixel = self.pixel(x, y);
+                        if *pixel & plane == 0 {
+                            *pixel |= plane;
+                            pixels_changed += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        pixels_changed
+    }
+
+    /// Returns true if drawing `sprite` at `(x, y)` would collide with a filled pixel.
+    ///
+    /// This performs the same collision check as `draw` without modifying the display.
+    pub fn would_collide(&self, x: usize, y: usize, sprite: &[u8], wrap: SpriteWrapQuirk) -> bool {
+        for (plane, plane_sprite) in Gpu::plane_sprites(sprite, self.selected_planes) {
+            for (pixel_y, row_sprite) in plane_sprite.iter().enumerate() {
+                let y = match Gpu::sprite_coordinate(y, pixel_y, self.height(), wrap.vertical_wrap) {
+                    Some(y) => y,
+                    None => continue,
+                };
+
+                for pixel_x in 0..8 {
+                    let bit = (row_sprite >> (7 - pixel_x)) & 0x1;
+                    let x = Gpu::sprite_coordinate(x, pixel_x, self.width(), wrap.horizontal_wrap);
+                    if let (1, Some(x)) = (bit, x) {
+                        if self.pixels[(y * self.width()) + x] & plane != 0 {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Convert the presented display to a RGBA texture, see `presented_pixels`.
     ///
     /// Arguments:
     ///
@@ -73,13 +655,53 @@ impl Gpu {
         &self,
         empty: [u8; 4],
         filled: [u8; 4],
-    ) -> ArrayVec<[u8; Gpu::SCREEN_PIXELS * 4]> {
-        self.pixels.iter().flat_map(|pixel| {
-            match pixel {
-                0 => ArrayVec::from(empty),
-                _ => ArrayVec::from(filled),
-            }
-        }).collect()
+    ) -> Vec<u8> {
+        let mut rgba = vec![0; self.presented_pixels().len() * 4];
+        self.to_rgba_into(&mut rgba, empty, filled);
+        rgba
+    }
+
+    /// Like `to_rgba` but writes into `rgba` instead of allocating, so a caller that redraws
+    /// every frame can keep reusing the same buffer.
+    ///
+    /// Panics if `rgba` isn't exactly 4 bytes for each pixel of the presented display.
+    pub fn to_rgba_into(&self, rgba: &mut [u8], empty: [u8; 4], filled: [u8; 4]) {
+        let pixels = self.presented_pixels();
+        assert_eq!(rgba.len(), pixels.len() * 4, "RGBA buffer doesn't match the display size");
+
+        for (pixel, rgba) in pixels.iter().zip(rgba.chunks_exact_mut(4)) {
+            rgba.copy_from_slice(match pixel {
+                0 => &empty,
+                _ => &filled,
+            });
+        }
+    }
+
+    /// Returns the `(x, y)` of every pixel that differs between `self` and `other`, in row-major
+    /// order. Pixels differ if any plane differs.
+    ///
+    /// Panics if the displays have different resolutions.
+    pub fn diff(&self, other: &Gpu) -> Vec<(usize, usize)> {
+        assert_eq!(self.resolution, other.resolution, "can't diff displays with different resolutions");
+
+        let width = self.width();
+        self.pixels.iter()
+            .zip(other.pixels.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(index, _)| (index % width, index / width))
+            .collect()
+    }
+
+    /// Render the display as text, one line per row with `#` for filled pixels and `.` for empty ones.
+    pub fn to_ascii(&self) -> String {
+        let mut ascii = String::with_capacity((self.width() + 1) * self.height());
+        for row in self.pixels.chunks(self.width()) {
+            ascii.extend(row.iter().map(|pixel| if *pixel == 0 { '.' } else { '#' }));
+            ascii.push('\n');
+        }
+
+        ascii
     }
 
     pub fn to_gfx_slice(&self, x_start: u8, columns: u8, y_start: u8, rows: u8) -> Vec<Vec<u8>> {
@@ -91,7 +713,7 @@ impl Gpu {
             for x in x_start..(x_start + columns) {
                 let y = y as usize;
                 let x = x as usize;
-                row.push(self.pixels[y * Gpu::SCREEN_WIDTH + x] as u8);
+                row.push(self.pixels[y * self.width() + x]);
             }
 
             gfx_slice.push(row);
@@ -104,21 +726,533 @@ impl Gpu {
 
 impl fmt::Debug for Gpu {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut row = 0;
-        loop {
-            if row > (2048 - 64) { break; }
-
-            let s: String = self.pixels[row..row+64]
-                .into_iter()
+        for row in self.pixels.chunks(self.width()) {
+            let s: String = row
+                .iter()
                 .map(|x| ('0' as u8) + x)
                 .map(|x| x as char)
                 .collect();
             f.write_str(&s)?;
             f.write_str("\n")?;
-
-            row += 64;
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn draw_multi_byte_sprite() {
+        let mut gpu = Gpu::new();
+        let result = gpu.draw(2, 1, &[0b11110000, 0b10010000, 0b01100000], SpriteWrapQuirk::default());
+
+        assert!(!result.collision);
+        assert_eq!(gpu.to_gfx_slice(0, 8, 0, 5), [
+            [0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 1, 1, 1, 1, 0, 0],
+            [0, 0, 1, 0, 0, 1, 0, 0],
+            [0, 0, 0, 1, 1, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0],
+        ]);
+    }
+
+    #[test]
+    pub fn draw_16x16_clips_the_right_byte() {
+        let mut gpu = Gpu::new();
+        gpu.set_resolution(Resolution::HighRes);
+        let mut sprite = [0x00; 32];
+        sprite[0] = 0xFF;
+        sprite[1] = 0xFF;
+
+        let clip = SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: false };
+        let result = gpu.draw_16x16(120, 0, &sprite, clip, DrawModeQuirk::Xor);
+
+        assert_eq!(result.pixels_changed, 8);
+        assert_eq!(gpu.to_gfx_slice(120, 8, 0, 1), [[1; 8]]);
+        assert_eq!(gpu.to_gfx_slice(0, 8, 0, 1), [[0; 8]]);
+    }
+
+    #[test]
+    pub fn draw_counts_changed_pixels() {
+        let mut gpu = Gpu::new();
+        let sprite = [0b10110001, 0b01111110];
+
+        let result = gpu.draw(4, 4, &sprite, SpriteWrapQuirk::default());
+        assert_eq!(result, DrawResult { collision: false, pixels_changed: 10 });
+
+        // Wrapping past the right edge takes the per-pixel path
+        let result = gpu.draw(60, 10, &sprite, SpriteWrapQuirk::default());
+        assert_eq!(result.pixels_changed, 10);
+
+        let clip = SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: false };
+        let result = gpu.draw(60, 20, &sprite, clip);
+        assert_eq!(result.pixels_changed, 6);
+
+        let result = gpu.draw(4, 4, &[0b00000000], SpriteWrapQuirk::default());
+        assert_eq!(result.pixels_changed, 0);
+
+        let result = gpu.draw(4, 4, &sprite, SpriteWrapQuirk::default());
+        assert_eq!(result, DrawResult { collision: true, pixels_changed: 10 });
+    }
+
+    #[test]
+    pub fn draw_xor_toggles_overlapping_pixels() {
+        let mut gpu = Gpu::new();
+        gpu.draw(0, 0, &[0b11110000], SpriteWrapQuirk::default());
+        let result = gpu.draw(0, 0, &[0b00111100], SpriteWrapQuirk::default());
+
+        assert!(result.collision);
+        assert_eq!(gpu.to_gfx_slice(0, 8, 0, 1), [[1, 1, 0, 0, 1, 1, 0, 0]]);
+
+        let result = gpu.draw(0, 0, &[0b11001100], SpriteWrapQuirk::default());
+
+        assert!(result.collision);
+        assert!(gpu.pixels().iter().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    pub fn draw_without_overlap_does_not_collide() {
+        let mut gpu = Gpu::new();
+        gpu.draw(0, 0, &[0b11110000], SpriteWrapQuirk::default());
+        let result = gpu.draw(0, 0, &[0b00001111], SpriteWrapQuirk::default());
+
+        assert!(!result.collision);
+        assert_eq!(gpu.to_gfx_slice(0, 8, 0, 1), [[1, 1, 1, 1, 1, 1, 1, 1]]);
+    }
+
+    #[test]
+    pub fn draw_at_exact_bottom_right_edge() {
+        let mut gpu = Gpu::new();
+        let x = Gpu::SCREEN_WIDTH - 8;
+        let y = Gpu::SCREEN_HEIGHT - 2;
+        gpu.draw(x, y, &[0b10000001, 0b10000001], SpriteWrapQuirk::default());
+
+        assert_eq!(gpu.to_gfx_slice(x as u8, 8, y as u8, 2), [
+            [1, 0, 0, 0, 0, 0, 0, 1],
+            [1, 0, 0, 0, 0, 0, 0, 1],
+        ]);
+
+        // Nothing spilled over onto the opposite edges
+        assert_eq!(gpu.to_gfx_slice(0, 1, 0, Gpu::SCREEN_HEIGHT as u8), vec![vec![0]; Gpu::SCREEN_HEIGHT]);
+        assert_eq!(gpu.to_gfx_slice(0, Gpu::SCREEN_WIDTH as u8, 0, 1), vec![vec![0; Gpu::SCREEN_WIDTH]]);
+    }
+
+    #[test]
+    pub fn draw_across_bottom_right_corner_wraps() {
+        let mut gpu = Gpu::new();
+        let x = Gpu::SCREEN_WIDTH - 4;
+        let y = Gpu::SCREEN_HEIGHT - 2;
+        gpu.draw(x, y, &[0b11000011, 0b10000001, 0b10000001, 0b11000011], SpriteWrapQuirk::default());
+
+        // The left half of the sprite stays in the bottom right corner
+        assert_eq!(gpu.to_gfx_slice(x as u8, 4, y as u8, 2), [
+            [1, 1, 0, 0],
+            [1, 0, 0, 0],
+        ]);
+
+        // The right half wraps to the left edge and the bottom half to the top edge
+        assert_eq!(gpu.to_gfx_slice(0, 4, y as u8, 2), [
+            [0, 0, 1, 1],
+            [0, 0, 0, 1],
+        ]);
+        assert_eq!(gpu.to_gfx_slice(x as u8, 4, 0, 2), [
+            [1, 0, 0, 0],
+            [1, 1, 0, 0],
+        ]);
+        assert_eq!(gpu.to_gfx_slice(0, 4, 0, 2), [
+            [0, 0, 0, 1],
+            [0, 0, 1, 1],
+        ]);
+    }
+
+    #[test]
+    pub fn draw_with_or_mode_never_clears_pixels() {
+        let mut gpu = Gpu::new();
+        let sprite = vec![0b11110000];
+
+        gpu.draw_with_mode(0, 0, &sprite, SpriteWrapQuirk::default(), DrawModeQuirk::Or);
+        let result = gpu.draw_with_mode(0, 0, &[0b01100110], SpriteWrapQuirk::default(), DrawModeQuirk::Or);
+
+        assert!(!result.collision);
+        assert_eq!(gpu.to_gfx_slice(0, 8, 0, 1), [[1, 1, 1, 1, 0, 1, 1, 0]]);
+        assert!(!gpu.undo_last_draw());
+    }
+
+    #[test]
+    pub fn last_collision_pixels() {
+        let mut gpu = Gpu::new().with_collision_tracking(true);
+        gpu.draw(10, 10, &[0b11110000, 0b11110000], SpriteWrapQuirk::default());
+        assert!(gpu.last_collision_pixels().is_empty());
+
+        // Overlaps the right half of the first sprite on its second row only
+        gpu.draw(12, 11, &[0b11110000], SpriteWrapQuirk::default());
+
+        assert_eq!(gpu.last_collision_pixels(), &[(12, 11), (13, 11)]);
+    }
+
+    #[test]
+    pub fn last_collision_pixels_is_empty_without_tracking() {
+        let mut gpu = Gpu::new();
+        gpu.draw(0, 0, &[0b10000000], SpriteWrapQuirk::default());
+        let result = gpu.draw(0, 0, &[0b10000000], SpriteWrapQuirk::default());
+
+        assert!(result.collision);
+        assert!(gpu.last_collision_pixels().is_empty());
+    }
+
+    #[test]
+    pub fn undo_last_draw() {
+        let mut gpu = Gpu::new();
+        gpu.draw(10, 10, &[0b11110000, 0b10010000], SpriteWrapQuirk::default());
+
+        assert!(gpu.undo_last_draw());
+
+        assert!(gpu.pixels().iter().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    pub fn undo_last_draw_only_undoes_once() {
+        let mut gpu = Gpu::new();
+        gpu.draw(0, 0, &[0b11110000], SpriteWrapQuirk::default());
+        gpu.draw(0, 0, &[0b00001111], SpriteWrapQuirk::default());
+
+        assert!(gpu.undo_last_draw());
+        assert!(!gpu.undo_last_draw());
+
+        assert_eq!(gpu.to_gfx_slice(0, 8, 0, 1), [[1, 1, 1, 1, 0, 0, 0, 0]]);
+    }
+
+    #[test]
+    pub fn undo_last_draw_after_clear_does_nothing() {
+        let mut gpu = Gpu::new();
+        gpu.draw(0, 0, &[0b11110000], SpriteWrapQuirk::default());
+        gpu.clear();
+
+        assert!(!gpu.undo_last_draw());
+
+        assert!(gpu.pixels().iter().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    pub fn draw_to_both_planes() {
+        let mut gpu = Gpu::new();
+        gpu.select_planes(Gpu::PLANE_1 | Gpu::PLANE_2);
+
+        gpu.draw(0, 0, &[0b11000000, 0b01100000], SpriteWrapQuirk::default());
+
+        assert_eq!(gpu.to_gfx_slice(0, 4, 0, 1), [[1, 3, 2, 0]]);
+    }
+
+    #[test]
+    pub fn draw_collision_in_unselected_plane_is_ignored() {
+        let mut gpu = Gpu::new();
+        gpu.select_planes(Gpu::PLANE_2);
+        gpu.draw(0, 0, &[0b11110000], SpriteWrapQuirk::default());
+
+        gpu.select_planes(Gpu::PLANE_1);
+        let result = gpu.draw(0, 0, &[0b11110000], SpriteWrapQuirk::default());
+
+        assert!(!result.collision);
+        assert_eq!(gpu.to_gfx_slice(0, 4, 0, 1), [[3, 3, 3, 3]]);
+    }
+
+    #[test]
+    pub fn clear_only_clears_selected_planes() {
+        let mut gpu = Gpu::new();
+        gpu.select_planes(Gpu::PLANE_1 | Gpu::PLANE_2);
+        gpu.draw(0, 0, &[0b10000000, 0b10000000], SpriteWrapQuirk::default());
+
+        gpu.select_planes(Gpu::PLANE_2);
+        gpu.clear();
+
+        assert_eq!(gpu.to_gfx_slice(0, 1, 0, 1), [[1]]);
+    }
+
+    // A 2x2 square drawn in the bottom-right corner so it overflows both edges
+    fn draw_in_corner(horizontal_wrap: bool, vertical_wrap: bool) -> Gpu {
+        let mut gpu = Gpu::new();
+        let wrap = SpriteWrapQuirk { horizontal_wrap, vertical_wrap };
+        gpu.draw(Gpu::SCREEN_WIDTH - 1, Gpu::SCREEN_HEIGHT - 1, &[0b11000000, 0b11000000], wrap);
+        gpu
+    }
+
+    fn filled_pixels(gpu: &Gpu) -> Vec<(usize, usize)> {
+        gpu.pixels().iter()
+            .enumerate()
+            .filter(|(_, pixel)| **pixel != 0)
+            .map(|(index, _)| (index % gpu.width(), index / gpu.width()))
+            .collect()
+    }
+
+    #[test]
+    pub fn draw_wraps_both_axes() {
+        let gpu = draw_in_corner(true, true);
+
+        assert_eq!(filled_pixels(&gpu), vec![(0, 0), (63, 0), (0, 31), (63, 31)]);
+    }
+
+    #[test]
+    pub fn draw_wraps_horizontally_and_clips_vertically() {
+        let gpu = draw_in_corner(true, false);
+
+        assert_eq!(filled_pixels(&gpu), vec![(0, 31), (63, 31)]);
+    }
+
+    #[test]
+    pub fn draw_clips_horizontally_and_wraps_vertically() {
+        let gpu = draw_in_corner(false, true);
+
+        assert_eq!(filled_pixels(&gpu), vec![(63, 0), (63, 31)]);
+    }
+
+    #[test]
+    pub fn draw_clips_both_axes() {
+        let gpu = draw_in_corner(false, false);
+
+        assert_eq!(filled_pixels(&gpu), vec![(63, 31)]);
+    }
+
+    #[test]
+    pub fn draw_wraps_starting_coordinate_when_clipping() {
+        let mut gpu = Gpu::new();
+        let clip = SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: false };
+
+        gpu.draw(Gpu::SCREEN_WIDTH + 4, Gpu::SCREEN_HEIGHT + 2, &[0b10000000], clip);
+
+        assert_eq!(filled_pixels(&gpu), vec![(4, 2)]);
+    }
+
+    #[test]
+    pub fn would_collide_respects_clipping() {
+        let gpu = draw_in_corner(true, true);
+        let clip = SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: false };
+
+        assert!(gpu.would_collide(Gpu::SCREEN_WIDTH - 1, 0, &[0b11000000], SpriteWrapQuirk::default()));
+        assert!(!gpu.would_collide(Gpu::SCREEN_WIDTH - 1, 1, &[0b01000000], clip));
+    }
+
+    #[test]
+    pub fn present_on_vblank_only_shows_presented_display() {
+        let mut gpu = Gpu::new().with_present_on_vblank(true);
+        gpu.draw(0, 0, &[0b10000000], SpriteWrapQuirk::default());
+
+        assert_eq!(gpu.pixels()[0], 1);
+        assert_eq!(gpu.presented_pixels()[0], 0);
+
+        gpu.present();
+        assert_eq!(gpu.presented_pixels()[0], 1);
+        assert_eq!(gpu.to_rgba(Gpu::BLACK, Gpu::WHITE)[0..4], Gpu::WHITE);
+
+        gpu.set_resolution(Resolution::HighRes);
+        assert_eq!(gpu.presented_size(), (64, 32));
+        gpu.present();
+        assert_eq!(gpu.presented_size(), (128, 64));
+    }
+
+    #[test]
+    pub fn fast_and_slow_draw_paths_match() {
+        // Collision tracking always draws pixel by pixel, so `slow` never takes the fast path
+        let mut fast = Gpu::new();
+        let mut slow = Gpu::new().with_collision_tracking(true);
+        fast.select_planes(Gpu::PLANE_1 | Gpu::PLANE_2);
+        slow.select_planes(Gpu::PLANE_1 | Gpu::PLANE_2);
+
+        let wraps = [
+            SpriteWrapQuirk::default(),
+            SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: false },
+        ];
+
+        let mut seed: u32 = 0x1234_5678;
+        let mut random = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as u8
+        };
+
+        for draw in 0..2_000 {
+            let (x, y) = (random() as usize, random() as usize);
+            let sprite = [random(), random(), random(), random(), random(), random()];
+            let wrap = wraps[draw % 2];
+
+            let fast_result = fast.draw(x, y, &sprite, wrap);
+            let slow_result = slow.draw(x, y, &sprite, wrap);
+
+            assert_eq!(fast_result, slow_result);
+            assert_eq!(fast.last_draw_rows(), slow.last_draw_rows());
+            assert_eq!(fast.pixels(), slow.pixels());
+        }
+    }
+
+    #[test]
+    pub fn fast_draw_path_marks_only_drawn_rows_dirty() {
+        let mut gpu = Gpu::new();
+        gpu.clear_dirty_rows();
+
+        gpu.draw(8, 4, &[0b11111111, 0b00000000, 0b00000001], SpriteWrapQuirk::default());
+
+        assert_eq!(gpu.dirty_rows(), vec![4, 6]);
+    }
+
+    #[test]
+    pub fn to_rgba_into_matches_to_rgba() {
+        let mut gpu = Gpu::new();
+        gpu.draw(3, 1, &[0b10110001, 0b01001110], SpriteWrapQuirk::default());
+
+        let mut rgba = vec![0xAA; Gpu::SCREEN_PIXELS * 4];
+        gpu.to_rgba_into(&mut rgba, Gpu::BLACK, Gpu::WHITE);
+        assert_eq!(rgba, gpu.to_rgba(Gpu::BLACK, Gpu::WHITE));
+
+        gpu.set_resolution(Resolution::HighRes);
+        gpu.draw(120, 60, &[0b11111111], SpriteWrapQuirk::default());
+
+        let mut rgba = vec![0xAA; gpu.presented_pixels().len() * 4];
+        gpu.to_rgba_into(&mut rgba, Gpu::BLACK, Gpu::WHITE);
+        assert_eq!(rgba, gpu.to_rgba(Gpu::BLACK, Gpu::WHITE));
+    }
+
+    #[test]
+    pub fn without_present_on_vblank_changes_show_immediately() {
+        let mut gpu = Gpu::new();
+        gpu.draw(0, 0, &[0b10000000], SpriteWrapQuirk::default());
+
+        assert_eq!(gpu.presented_pixels()[0], 1);
+    }
+
+    #[test]
+    pub fn draw_marks_sprite_rows_dirty() {
+        let mut gpu = Gpu::new();
+        assert_eq!(gpu.dirty_rows().len(), Gpu::SCREEN_HEIGHT);
+
+        gpu.clear_dirty_rows();
+        assert!(gpu.dirty_rows().is_empty());
+
+        gpu.draw(4, 10, &[0b11110000, 0b00000000, 0b10010000], SpriteWrapQuirk::default());
+        assert_eq!(gpu.dirty_rows(), vec![10, 12]);
+
+        gpu.clear_dirty_rows();
+        gpu.clear();
+        assert_eq!(gpu.dirty_rows().len(), Gpu::SCREEN_HEIGHT);
+    }
+
+    #[test]
+    pub fn dirty_rows_wait_for_present_on_vblank() {
+        let mut gpu = Gpu::new().with_present_on_vblank(true);
+        gpu.clear_dirty_rows();
+
+        gpu.draw(0, 3, &[0b10000000], SpriteWrapQuirk::default());
+        assert!(gpu.dirty_rows().is_empty());
+
+        gpu.present();
+        assert_eq!(gpu.dirty_rows(), vec![3]);
+
+        gpu.present();
+        assert_eq!(gpu.dirty_rows(), vec![3]);
+
+        gpu.clear_dirty_rows();
+        assert!(gpu.dirty_rows().is_empty());
+    }
+
+    #[test]
+    pub fn diff() {
+        let blank = Gpu::new();
+        let mut gpu = Gpu::new();
+        gpu.draw(2, 1, &[0b10100000, 0b01000000], SpriteWrapQuirk::default());
+
+        assert_eq!(blank.diff(&gpu), vec![(2, 1), (4, 1), (3, 2)]);
+        assert_eq!(gpu.diff(&blank), blank.diff(&gpu));
+        assert!(gpu.diff(&gpu).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn diff_different_resolutions_panics() {
+        let mut gpu = Gpu::new();
+        gpu.set_resolution(Resolution::HighRes);
+
+        Gpu::new().diff(&gpu);
+    }
+
+    #[test]
+    pub fn to_ascii() {
+        let mut gpu = Gpu::new();
+        gpu.draw(1, 1, &[0b10100000], SpriteWrapQuirk::default());
+
+        let ascii = gpu.to_ascii();
+        let lines: Vec<&str> = ascii.lines().collect();
+
+        assert_eq!(lines.len(), Gpu::SCREEN_HEIGHT);
+        assert_eq!(lines[0], ".".repeat(Gpu::SCREEN_WIDTH));
+        assert_eq!(lines[1], format!(".#.#{}", ".".repeat(Gpu::SCREEN_WIDTH - 4)));
+    }
+
+    fn draw_low_res_pattern(gpu: &mut Gpu) {
+        gpu.draw(0, 0, &[0b10000000, 0b01000000], SpriteWrapQuirk::default());
+    }
+
+    #[test]
+    pub fn set_resolution_clears_display() {
+        let mut gpu = Gpu::new();
+        draw_low_res_pattern(&mut gpu);
+
+        gpu.set_resolution(Resolution::HighRes);
+
+        assert_eq!((gpu.width(), gpu.height()), (128, 64));
+        assert_eq!(gpu.pixels().len(), 128 * 64);
+        assert!(gpu.pixels().iter().all(|pixel| *pixel == 0));
+        assert!(!gpu.undo_last_draw());
+    }
+
+    #[test]
+    pub fn set_resolution_scales_on_switch() {
+        let mut gpu = Gpu::new().with_scale_on_switch(true);
+        draw_low_res_pattern(&mut gpu);
+
+        gpu.set_resolution(Resolution::HighRes);
+
+        assert_eq!(filled_pixels(&gpu), vec![(0, 0), (1, 0), (0, 1), (1, 1), (2, 2), (3, 2), (2, 3), (3, 3)]);
+    }
+
+    #[test]
+    pub fn set_resolution_to_low_res_always_clears() {
+        let mut gpu = Gpu::new().with_scale_on_switch(true);
+        gpu.set_resolution(Resolution::HighRes);
+        draw_low_res_pattern(&mut gpu);
+
+        gpu.set_resolution(Resolution::LowRes);
+
+        assert_eq!((gpu.width(), gpu.height()), (64, 32));
+        assert!(gpu.pixels().iter().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    pub fn set_resolution_to_current_resolution_keeps_display() {
+        let mut gpu = Gpu::new();
+        draw_low_res_pattern(&mut gpu);
+
+        gpu.set_resolution(Resolution::LowRes);
+
+        assert_eq!(filled_pixels(&gpu), vec![(0, 0), (1, 1)]);
+    }
+
+    #[test]
+    pub fn would_collide() {
+        let mut gpu = Gpu::new();
+        gpu.draw(10, 10, &[0b11110000, 0b10010000], SpriteWrapQuirk::default());
+
+        assert!(gpu.would_collide(10, 10, &[0b11110000, 0b10010000], SpriteWrapQuirk::default()));
+        assert!(gpu.would_collide(13, 11, &[0b10000000], SpriteWrapQuirk::default()));
+        assert!(!gpu.would_collide(40, 20, &[0b11110000, 0b10010000], SpriteWrapQuirk::default()));
+    }
+
+    #[test]
+    pub fn would_collide_does_not_modify_display() {
+        let mut gpu = Gpu::new();
+        gpu.draw(0, 0, &[0b11110000], SpriteWrapQuirk::default());
+
+        gpu.would_collide(0, 0, &[0b11111111], SpriteWrapQuirk::default());
+
+        assert_eq!(gpu.to_gfx_slice(0, 8, 0, 1), [[1, 1, 1, 1, 0, 0, 0, 0]]);
+    }
+}
@@ -0,0 +1,115 @@
+use std::str::FromStr;
+
+use crate::chip8::{Chip8, Chip8Result, Chip8Error};
+
+/// `InputScript` is a timed sequence of key presses and releases, which keeps input-dependent
+/// tests and demos readable. Install one with `Chip8::set_input_script`.
+///
+/// Scripts are a list of commands separated by `;` or newlines:
+///
+/// - `press KEYS` and `release KEYS` change every key in `KEYS`, e.g. `press 5` or `press 5A`
+/// - `wait N` lets `N` cycles pass before the next command
+///
+/// ```ignore
+/// let script: InputScript = "press 5; wait 10; release 5".parse()?;
+/// assert_eq!(script.events(), &[(0, 0x5, true), (10, 0x5, false)]);
+/// ```
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct InputScript {
+    /// `(cycle_offset, key, pressed)` in the order they happen
+    events: Vec<(u64, u8, bool)>,
+}
+
+impl InputScript {
+    /// Every `(cycle_offset, key, pressed)` in the script, where `cycle_offset` counts cycles from
+    /// when the script starts
+    pub fn events(&self) -> &[(u64, u8, bool)] {
+        &self.events
+    }
+
+    /// The state of every key `cycle_offset` cycles into the script. Keys change at the start of
+    /// their cycle, so a key pressed and released at the same offset is never seen as pressed.
+    pub fn keypad_at(&self, cycle_offset: u64) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for (_, key, pressed) in self.events.iter().take_while(|(offset, _, _)| *offset <= cycle_offset) {
+            keys[*key as usize] = *pressed;
+        }
+
+        keys
+    }
+}
+
+impl FromStr for InputScript {
+    type Err = Chip8Error;
+
+    fn from_str(script: &str) -> Chip8Result<InputScript> {
+        let mut events = Vec::new();
+        let mut cycle_offset = 0;
+
+        let commands = script.split([';', '\n'])
+            .map(str::trim)
+            .filter(|command| !command.is_empty());
+
+        for command in commands {
+            let mut parts = command.split_whitespace();
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("wait"), Some(cycles), None) => {
+                    cycle_offset += cycles.parse::<u64>()
+                        .map_err(|_| Chip8Error::ParseError(format!("invalid number of cycles: {}", cycles)))?;
+                },
+                (Some(action @ "press"), Some(keys), None) | (Some(action @ "release"), Some(keys), None) => {
+                    let keypad = Chip8::keypad_from_hex_string(keys)?;
+                    for key in (0..16).filter(|key| keypad[*key as usize]) {
+                        events.push((cycle_offset, key, action == "press"));
+                    }
+                },
+                _ => return Err(Chip8Error::ParseError(format!("invalid input command: {}", command))),
+            }
+        }
+
+        Ok(InputScript { events })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn parse_input_script() {
+        let script: InputScript = "press 5; wait 10; release 5\nwait 2\npress A3;".parse().unwrap();
+
+        assert_eq!(script.events(), &[
+            (0, 0x5, true),
+            (10, 0x5, false),
+            (12, 0x3, true),
+            (12, 0xA, true),
+        ]);
+    }
+
+    #[test]
+    pub fn parse_input_script_errors() {
+        assert_eq!(
+            "press 5; hold 5".parse::<InputScript>(),
+            Err(Chip8Error::ParseError("invalid input command: hold 5".to_string()))
+        );
+        assert_eq!(
+            "wait soon".parse::<InputScript>(),
+            Err(Chip8Error::ParseError("invalid number of cycles: soon".to_string()))
+        );
+        assert_eq!(
+            "press G".parse::<InputScript>(),
+            Err(Chip8Error::ParseError("invalid key: G".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn keypad_at() {
+        let script: InputScript = "press 5; wait 10; release 5; press 1".parse().unwrap();
+
+        assert!(script.keypad_at(0)[0x5]);
+        assert!(script.keypad_at(9)[0x5]);
+        assert!(!script.keypad_at(10)[0x5]);
+        assert!(script.keypad_at(10)[0x1]);
+    }
+}
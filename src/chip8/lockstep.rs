@@ -0,0 +1,186 @@
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::chip8::{Chip8, Chip8Error, Chip8Output, Chip8Result};
+
+/// The key changes one player made before the frame starting at `cycle`.
+///
+/// Every player sends exactly one `LockstepInput` per frame, even if no keys changed, so the
+/// other side knows when it has received all input for that frame.
+#[derive(PartialEq, Debug, Clone)]
+pub struct LockstepInput {
+    pub cycle: u64,
+
+    /// `(key, pressed)` for every key that changed, in the order they changed
+    pub keys: Vec<(u8, bool)>,
+}
+
+/// `Lockstep` keeps two `Chip8` instances on different machines in sync by exchanging input
+/// rather than state.
+///
+/// Each side runs its own `Chip8`, which must start from the same ROM, seed and quirks. Every
+/// frame both sides send their local input to each other, apply both players' input in player
+/// order and then advance by a fixed budget with `Chip8::tick_cycles`. Since the interpreter is
+/// deterministic both machines end up in the same state without the frame rate mattering.
+pub struct Lockstep {
+    chip8: Chip8,
+
+    /// Either 0 or 1, decides whose input is applied first
+    player: usize,
+
+    cycles_per_frame: u32,
+
+    /// The number of cycles executed so far
+    cycle: u64,
+
+    local: Sender<LockstepInput>,
+    remote: Receiver<LockstepInput>,
+}
+
+impl Lockstep {
+    /// 500hz at 60 frames per second, the same speed as `Chip8::clock_speed` defaults to
+    pub const DEFAULT_CYCLES_PER_FRAME: u32 = 500 / 60;
+
+    /// Run `chip8` as `player` (0 or 1). Local input is sent to `local` and the other player's
+    /// input is received from `remote`.
+    pub fn new(chip8: Chip8, player: usize, local: Sender<LockstepInput>, remote: Receiver<LockstepInput>) -> Lockstep {
+        Lockstep {
+            chip8,
+            player,
+            cycles_per_frame: Lockstep::DEFAULT_CYCLES_PER_FRAME,
+            cycle: 0,
+            local,
+            remote,
+        }
+    }
+
+    /// Both players must use the same number of cycles per frame
+    pub fn with_cycles_per_frame(mut self, cycles_per_frame: u32) -> Self {
+        self.cycles_per_frame = cycles_per_frame;
+        self
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// Advance one frame with the local player's key changes in `keys`.
+    ///
+    /// Blocks until the other player's input for this frame arrives. Returns
+    /// `Chip8Error::Desync` if the other player disconnected or sent input for a different frame.
+    pub fn advance_frame(&mut self, keys: Vec<(u8, bool)>) -> Chip8Result<Chip8Output> {
+        let local_input = LockstepInput { cycle: self.cycle, keys };
+        self.local.send(local_input.clone())
+            .map_err(|_| Chip8Error::Desync("remote player disconnected".to_string()))?;
+
+        let remote_input = self.remote.recv()
+            .map_err(|_| Chip8Error::Desync("remote player disconnected".to_string()))?;
+        if remote_input.cycle != self.cycle {
+            let message = format!("expected input for cycle {} but got {}", self.cycle, remote_input.cycle);
+            return Err(Chip8Error::Desync(message));
+        }
+
+        let inputs = match self.player {
+            0 => [local_input, remote_input],
+            _ => [remote_input, local_input],
+        };
+        for input in inputs.iter() {
+            for (key, pressed) in &input.keys {
+                self.chip8.key(*key, *pressed);
+            }
+        }
+
+        self.cycle += self.cycles_per_frame as u64;
+        self.chip8.tick_cycles(self.cycles_per_frame, 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use crate::chip8::{Opcode, RomBuilder};
+
+    fn rom() -> Vec<u8> {
+        let mut rom = RomBuilder::new();
+        rom.push(Opcode::LoadConstant { x: 0x2, value: 0x1 });
+        rom.push(Opcode::LoadConstant { x: 0x3, value: 0xC });
+        rom.push(Opcode::IndexFont { x: 0x2 });
+        rom.label("loop");
+        rom.push(Opcode::Random { x: 0x0, mask: 0x3F });
+        rom.push(Opcode::Random { x: 0x1, mask: 0x1F });
+        rom.push(Opcode::SkipIfKeyNotPressed { x: 0x2 });
+        rom.push(Opcode::Draw { x: 0x0, y: 0x1, n: 0x5 });
+        rom.push(Opcode::SkipIfKeyNotPressed { x: 0x3 });
+        rom.push(Opcode::Draw { x: 0x1, y: 0x0, n: 0x5 });
+        rom.push(Opcode::Jump(rom.resolve("loop")));
+
+        rom.build().unwrap()
+    }
+
+    // Each frame's key changes for one player
+    fn script(player: usize) -> Vec<Vec<(u8, bool)>> {
+        let key = if player == 0 { 0x1 } else { 0xC };
+        let offset = player * 3;
+
+        (0..20).map(|frame| match (frame + offset) % 7 {
+            0 => vec![(key, true)],
+            2 => vec![(key, false)],
+            _ => vec![],
+        }).collect()
+    }
+
+    #[test]
+    pub fn two_players_stay_in_sync() {
+        let (to_player_1, from_player_0) = channel();
+        let (to_player_0, from_player_1) = channel();
+
+        let players = vec![(0, to_player_1, from_player_1), (1, to_player_0, from_player_0)];
+        let handles: Vec<_> = players.into_iter().map(|(player, local, remote)| {
+            thread::spawn(move || {
+                let chip8 = Chip8::new_with_rom(rom()).with_seed(42);
+                let mut lockstep = Lockstep::new(chip8, player, local, remote);
+
+                for keys in script(player) {
+                    lockstep.advance_frame(keys).unwrap();
+                }
+
+                lockstep.chip8().gpu.pixels().to_vec()
+            })
+        }).collect();
+
+        let framebuffers: Vec<Vec<u8>> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+        assert!(framebuffers[0].iter().any(|pixel| *pixel != 0));
+        assert_eq!(framebuffers[0], framebuffers[1]);
+    }
+
+    #[test]
+    pub fn advance_frame_fails_if_remote_disconnects() {
+        let (local, _) = channel();
+        let (_, remote) = channel();
+        let mut lockstep = Lockstep::new(Chip8::new_with_rom(rom()), 0, local, remote);
+
+        assert_eq!(
+            lockstep.advance_frame(vec![]),
+            Err(Chip8Error::Desync("remote player disconnected".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn advance_frame_fails_on_mismatched_cycle() {
+        let (local, _from_local) = channel();
+        let (to_remote, remote) = channel();
+        to_remote.send(LockstepInput { cycle: 100, keys: vec![] }).unwrap();
+        let mut lockstep = Lockstep::new(Chip8::new_with_rom(rom()), 0, local, remote);
+
+        assert_eq!(
+            lockstep.advance_frame(vec![]),
+            Err(Chip8Error::Desync("expected input for cycle 0 but got 100".to_string()))
+        );
+    }
+}
@@ -0,0 +1,18 @@
+/// `MemoryBus` sits between the program and memory, e.g. to map addresses to custom
+/// peripherals. Install one with `Chip8::set_memory_bus`.
+///
+/// Every data access the program makes goes through the bus: `READ`, `WRITE`, `BCD` and the
+/// sprite data read by `DRAW`. Fetching opcodes, loading ROMs and the debugging views read
+/// memory directly.
+///
+/// `address` is always within `memory`. The default methods access `memory` like a plain
+/// array, so a bus only needs to implement the accesses it wants to intercept.
+pub trait MemoryBus {
+    fn read(&mut self, memory: &[u8], address: usize) -> u8 {
+        memory[address]
+    }
+
+    fn write(&mut self, memory: &mut [u8], address: usize, value: u8) {
+        memory[address] = value;
+    }
+}
@@ -3,11 +3,40 @@ mod chip8_error;
 mod opcode;
 mod quirks;
 mod gpu;
+mod rom_builder;
+mod display_backend;
+mod save_state;
+mod lockstep;
+mod rom_database;
+mod analysis;
+mod trace;
+mod memory_bus;
+mod octo;
+mod input_script;
 
-pub use self::chip8::{Chip8, Chip8Output};
-pub use self::opcode::Opcode;
+#[cfg(feature = "demo-roms")]
+mod demo_roms;
+
+#[cfg(feature = "gif-recording")]
+mod gif_recorder;
+
+#[cfg(feature = "sprite-import")]
+mod sprite_import;
+
+pub use self::chip8::{Chip8, Chip8Output, Chip8Event, Beep, MemorySize, CustomOpcodeHandler};
+pub use self::opcode::{Opcode, OpcodeCategory, OpcodeInfo, AssemblyStyle, NumberFormat};
 pub use self::chip8_error::Chip8Error;
-pub use self::gpu::Gpu;
+pub use self::gpu::{Gpu, Resolution, DrawRows, DrawResult};
+pub use self::rom_builder::RomBuilder;
+pub use self::memory_bus::MemoryBus;
+pub use self::display_backend::{DisplayBackend, BackendEvent, run_loop};
+pub use self::trace::{read_binary_trace, TraceRecord};
+pub use self::analysis::{analyze, ControlFlowGraph, BasicBlock, Edge, RomWarning};
+pub use self::lockstep::{Lockstep, LockstepInput};
+pub use self::input_script::InputScript;
+#[cfg(feature = "sprite-import")]
+pub use self::sprite_import::sprite_from_image;
+pub use self::quirks::{Quirks, ReadWriteIncrementQuirk, BitShiftQuirk, SpriteWrapQuirk, LogicQuirk, MemoryBoundsQuirk, ResolutionSwitchQuirk, DrawModeQuirk, CollisionCountQuirk, Quirk, QuirkProfile};
 
 pub type Chip8Result<T> = Result<T, Chip8Error>;
 pub type Register = u8;
@@ -0,0 +1,352 @@
+use crate::chip8::{Opcode, Register, Address, RomBuilder, Chip8Result, Chip8Error};
+
+/// Parses `source` written in the Octo assembly dialect and pushes the resulting opcodes
+/// onto `builder`. See `RomBuilder::push_octo`.
+pub(crate) fn assemble(source: &str, builder: &mut RomBuilder) -> Chip8Result<()> {
+    let tokens = source.lines()
+        .flat_map(|line| line.split('#').next().unwrap_or("").split_whitespace())
+        .collect();
+
+    let mut parser = Parser { tokens, position: 0 };
+    while parser.position < parser.tokens.len() {
+        parser.statement(builder)?;
+    }
+
+    Ok(())
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Chip8Result<&'a str> {
+        let token = self.tokens.get(self.position)
+            .ok_or_else(|| Chip8Error::ParseError("unexpected end of input".to_string()))?;
+
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn expect(&mut self, expected: &str) -> Chip8Result<()> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(Chip8Error::ParseError(format!("expected '{}' but found '{}'", expected, token)))
+        }
+    }
+
+    fn register(&mut self) -> Chip8Result<Register> {
+        let token = self.next()?;
+        parse_register(token)
+            .ok_or_else(|| Chip8Error::ParseError(format!("invalid register: {}", token)))
+    }
+
+    fn value(&mut self) -> Chip8Result<u16> {
+        let token = self.next()?;
+        parse_number(token)
+            .ok_or_else(|| Chip8Error::ParseError(format!("invalid number: {}", token)))
+    }
+
+    fn byte(&mut self) -> Chip8Result<u8> {
+        let value = self.value()?;
+        if value > 0xFF {
+            Err(Chip8Error::ParseError(format!("value out of range: {:X}", value)))
+        } else {
+            Ok(value as u8)
+        }
+    }
+
+    fn statement(&mut self, builder: &mut RomBuilder) -> Chip8Result<()> {
+        let token = self.next()?;
+        if let Some(x) = parse_register(token) {
+            return self.register_statement(builder, x);
+        }
+
+        match token {
+            ":" => {
+                let name = self.next()?;
+                builder.label(name);
+                return Ok(());
+            },
+            ":call" => return self.target(builder, Opcode::CallSubroutine),
+            "jump" => return self.target(builder, Opcode::Jump),
+            "jump0" => return self.target(builder, Opcode::JumpWithOffset),
+            "if" => return self.condition(builder),
+            "i" => return self.index_statement(builder),
+            _ => {}
+        }
+
+        let opcode = match token {
+            "return" | ";" => Opcode::Return,
+            "clear" => Opcode::ClearScreen,
+            "hires" => Opcode::HighRes,
+            "lores" => Opcode::LowRes,
+            "exit" => Opcode::Exit,
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                Opcode::Draw { x, y, n: self.byte()? }
+            },
+            "save" | "load" => {
+                let x = self.register()?;
+                let range = if self.peek() == Some("-") {
+                    self.next()?;
+                    Some(self.register()?)
+                } else {
+                    None
+                };
+
+                match (token == "save", range) {
+                    (true, None) => Opcode::WriteMemory { x },
+                    (false, None) => Opcode::ReadMemory { x },
+                    (true, Some(y)) => Opcode::SaveRange { x, y },
+                    (false, Some(y)) => Opcode::LoadRange { x, y },
+                }
+            },
+            "bcd" => Opcode::WriteBCD { x: self.register()? },
+            "plane" => Opcode::SelectPlane { planes: self.byte()? },
+            "delay" => {
+                self.expect(":=")?;
+                Opcode::LoadRegisterIntoDelay { x: self.register()? }
+            },
+            "buzzer" => {
+                self.expect(":=")?;
+                Opcode::LoadRegisterIntoSound { x: self.register()? }
+            },
+            name if is_label(name) => {
+                builder.push_ref(name, Opcode::CallSubroutine);
+                return Ok(());
+            },
+            _ => return Err(Chip8Error::ParseError(format!("unexpected token '{}'", token))),
+        };
+
+        push(builder, opcode)
+    }
+
+    /// `vX op value` statements, `token` has already been parsed into `x`
+    fn register_statement(&mut self, builder: &mut RomBuilder, x: Register) -> Chip8Result<()> {
+        let operator = self.next()?;
+        let source = self.peek().and_then(parse_register);
+        if source.is_some() {
+            self.next()?;
+        }
+
+        let opcode = match (operator, source) {
+            (":=", Some(y)) => Opcode::Load { x, y },
+            ("+=", Some(y)) => Opcode::Add { x, y },
+            ("-=", Some(y)) => Opcode::SubtractXY { x, y },
+            ("=-", Some(y)) => Opcode::SubtractYX { x, y },
+            ("|=", Some(y)) => Opcode::Or { x, y },
+            ("&=", Some(y)) => Opcode::And { x, y },
+            ("^=", Some(y)) => Opcode::Xor { x, y },
+            (">>=", Some(y)) => Opcode::ShiftRight { x, y },
+            ("<<=", Some(y)) => Opcode::ShiftLeft { x, y },
+            (":=", None) => match self.peek() {
+                Some("delay") => {
+                    self.next()?;
+                    Opcode::LoadDelayIntoRegister { x }
+                },
+                Some("key") => {
+                    self.next()?;
+                    Opcode::WaitForKeyRelease { x }
+                },
+                Some("random") => {
+                    self.next()?;
+                    Opcode::Random { x, mask: self.byte()? }
+                },
+                _ => Opcode::LoadConstant { x, value: self.byte()? },
+            },
+            ("+=", None) => Opcode::AddConstant { x, value: self.byte()? },
+            ("-=", None) => Opcode::AddConstant { x, value: self.byte()?.wrapping_neg() },
+            _ => return Err(Chip8Error::ParseError(format!("unexpected token '{}'", operator))),
+        };
+
+        push(builder, opcode)
+    }
+
+    fn index_statement(&mut self, builder: &mut RomBuilder) -> Chip8Result<()> {
+        match self.next()? {
+            ":=" if self.peek() == Some("hex") => {
+                self.next()?;
+                let x = self.register()?;
+                push(builder, Opcode::IndexFont { x })
+            },
+            ":=" => self.target(builder, Opcode::IndexAddress),
+            "+=" => {
+                let x = self.register()?;
+                push(builder, Opcode::AddAddress { x })
+            },
+            token => Err(Chip8Error::ParseError(format!("unexpected token '{}'", token))),
+        }
+    }
+
+    /// `if vX op value then`, which skips the next statement unless the condition holds
+    fn condition(&mut self, builder: &mut RomBuilder) -> Chip8Result<()> {
+        let x = self.register()?;
+        let operator = self.next()?;
+
+        let opcode = match operator {
+            "key" => Opcode::SkipIfKeyNotPressed { x },
+            "-key" => Opcode::SkipIfKeyPressed { x },
+            "==" | "!=" => {
+                let token = self.peek().unwrap_or("");
+                match (operator, parse_register(token)) {
+                    ("==", Some(y)) => Opcode::SkipNextIfRegisterNotEqual { x, y },
+                    (_, Some(y)) => Opcode::SkipNextIfRegisterEqual { x, y },
+                    ("==", None) => Opcode::SkipNextIfNotEqual { x, value: self.byte()? },
+                    (_, None) => Opcode::SkipNextIfEqual { x, value: self.byte()? },
+                }
+            },
+            _ => return Err(Chip8Error::ParseError(format!("unsupported condition '{}'", operator))),
+        };
+
+        if let Opcode::SkipNextIfRegisterEqual { .. } | Opcode::SkipNextIfRegisterNotEqual { .. } = opcode {
+            self.next()?;
+        }
+
+        self.expect("then")?;
+        push(builder, opcode)
+    }
+
+    /// Pushes `opcode` with the address that follows, which may be a number or a label
+    fn target(&mut self, builder: &mut RomBuilder, opcode: fn(Address) -> Opcode) -> Chip8Result<()> {
+        let token = self.next()?;
+        if let Some(address) = parse_number(token) {
+            push(builder, opcode(address))
+        } else if is_label(token) {
+            builder.push_ref(token, opcode);
+            Ok(())
+        } else {
+            Err(Chip8Error::ParseError(format!("invalid target: {}", token)))
+        }
+    }
+}
+
+fn push(builder: &mut RomBuilder, opcode: Opcode) -> Chip8Result<()> {
+    opcode.validate()
+        .map_err(|e| Chip8Error::ParseError(e.to_string()))?;
+
+    builder.push(opcode);
+    Ok(())
+}
+
+fn parse_register(token: &str) -> Option<Register> {
+    if token.len() == 2 && (token.starts_with('v') || token.starts_with('V')) {
+        u8::from_str_radix(&token[1..], 16).ok()
+    } else {
+        None
+    }
+}
+
+/// Octo numbers are decimal, `0x` hexadecimal or `0b` binary. Negative decimals wrap to a byte.
+fn parse_number(token: &str) -> Option<u16> {
+    if let Some(digits) = token.strip_prefix("0x") {
+        u16::from_str_radix(digits, 16).ok()
+    } else if let Some(digits) = token.strip_prefix("0b") {
+        u16::from_str_radix(digits, 2).ok()
+    } else if let Some(digits) = token.strip_prefix('-') {
+        match digits.parse::<u16>() {
+            Ok(value) if value <= 0x80 => Some((value as u8).wrapping_neg() as u16),
+            _ => None,
+        }
+    } else {
+        token.parse().ok()
+    }
+}
+
+fn is_label(token: &str) -> bool {
+    token.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn octo(source: &str) -> Chip8Result<Vec<u8>> {
+        let mut builder = RomBuilder::new();
+        builder.push_octo(source)?;
+        builder.build()
+    }
+
+    #[test]
+    pub fn octo_add_constant() {
+        assert_eq!(octo("v1 += 5"), Ok(Opcode::to_rom(vec![Opcode::AddConstant { x: 0x1, value: 0x5 }])));
+    }
+
+    #[test]
+    pub fn octo_jump_to_label() {
+        let source = "
+            jump main
+            : sub
+                return
+            : main  # entry point
+                sub
+                jump main
+        ";
+
+        assert_eq!(octo(source), Ok(Opcode::to_rom(vec![
+            Opcode::Jump(0x204),
+            Opcode::Return,
+            Opcode::CallSubroutine(0x202),
+            Opcode::Jump(0x204),
+        ])));
+    }
+
+    #[test]
+    pub fn octo_statements() {
+        let source = "
+            v0 := 0x1F  va := vb  v2 := random 0b1111  v3 := key  v4 := delay
+            v5 -= v6  v5 =- v6  v7 >>= v7  v8 -= 1
+            i := 0x300  i := hex v9  i += va
+            sprite v0 v1 5  save v2  load v3 - v4  bcd v5
+            delay := v6  buzzer := v7  clear
+            if v0 == 3 then v1 := 4
+            if v0 != v1 then exit
+            if v2 -key then jump0 0x200
+        ";
+
+        assert_eq!(octo(source), Ok(Opcode::to_rom(vec![
+            Opcode::LoadConstant { x: 0x0, value: 0x1F },
+            Opcode::Load { x: 0xA, y: 0xB },
+            Opcode::Random { x: 0x2, mask: 0xF },
+            Opcode::WaitForKeyRelease { x: 0x3 },
+            Opcode::LoadDelayIntoRegister { x: 0x4 },
+            Opcode::SubtractXY { x: 0x5, y: 0x6 },
+            Opcode::SubtractYX { x: 0x5, y: 0x6 },
+            Opcode::ShiftRight { x: 0x7, y: 0x7 },
+            Opcode::AddConstant { x: 0x8, value: 0xFF },
+            Opcode::IndexAddress(0x300),
+            Opcode::IndexFont { x: 0x9 },
+            Opcode::AddAddress { x: 0xA },
+            Opcode::Draw { x: 0x0, y: 0x1, n: 0x5 },
+            Opcode::WriteMemory { x: 0x2 },
+            Opcode::LoadRange { x: 0x3, y: 0x4 },
+            Opcode::WriteBCD { x: 0x5 },
+            Opcode::LoadRegisterIntoDelay { x: 0x6 },
+            Opcode::LoadRegisterIntoSound { x: 0x7 },
+            Opcode::ClearScreen,
+            Opcode::SkipNextIfNotEqual { x: 0x0, value: 0x3 },
+            Opcode::LoadConstant { x: 0x1, value: 0x4 },
+            Opcode::SkipNextIfRegisterEqual { x: 0x0, y: 0x1 },
+            Opcode::Exit,
+            Opcode::SkipIfKeyPressed { x: 0x2 },
+            Opcode::JumpWithOffset(0x200),
+        ])));
+    }
+
+    #[test]
+    pub fn octo_errors() {
+        assert_eq!(octo("v1 += 256"), Err(Chip8Error::ParseError("value out of range: 100".to_string())));
+        assert_eq!(octo("if v1 < 3 then"), Err(Chip8Error::ParseError("unsupported condition '<'".to_string())));
+        assert_eq!(octo("jump"), Err(Chip8Error::ParseError("unexpected end of input".to_string())));
+        assert_eq!(octo("jump missing"), Err(Chip8Error::ParseError("undefined label 'missing'".to_string())));
+    }
+}
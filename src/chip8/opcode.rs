@@ -1,4 +1,6 @@
-use crate::chip8::{Chip8Error, Chip8Result, Register, Address};
+use std::str::FromStr;
+
+use crate::chip8::{Chip8Error, Chip8Result, Register, Address, QuirkProfile, Quirk};
 
 /// `Opcode` represents a single instruction available on the Chip-8
 ///
@@ -13,6 +15,7 @@ use crate::chip8::{Chip8Error, Chip8Result, Register, Address};
 /// | 00EE   | RET               | Flow Control          | Return                                   |
 /// | 1nnn   | JUMP addr         | Flow Control          | Jump to Address                          |
 /// | Bnnn   | JUMP addr,V0      | Flow Control          | Jump to Address with Offset              |
+/// | 00FD   | EXIT              | Flow Control          | Stop the interpreter (SUPER-CHIP)        |
 /// | 3xnn   | SKIP.EQ Vx, value | Conditional Execution | Skip Next If Equal                       |
 /// | 5xy0   | SKIP.EQ Vx, Vy    | Conditional Execution | Skip Next If Registers Equal             |
 /// | 4xnn   | SKIP.NE Vx, value | Conditional Execution | Skip Next If Not Equal                   |
@@ -34,6 +37,8 @@ use crate::chip8::{Chip8Error, Chip8Result, Register, Address};
 /// | Fx55   | WRITE Vx          | Manipulate Memory     | Write values V0..Vx to memory at I       |
 /// | Fx33   | BCD Vx            | Manipulate Memory     | Write BCD of Vx to memory at I,I+1,I+2   |
 /// | Fx65   | READ Vx           | Manipulate Memory     | Read memory at I into V0..Vx             |
+/// | 5xy2   | WRITE Vx, Vy      | Manipulate Memory     | Write Vx..Vy to memory at I (XO-CHIP)    |
+/// | 5xy3   | READ Vx, Vy       | Manipulate Memory     | Read memory at I into Vx..Vy (XO-CHIP)   |
 /// | Ex9E   | SKIP.KEQ Vx       | IO (Keyboard)         | Skip next instruction if key pressed     |
 /// | ExA1   | SKIP.KNE Vx       | IO (Keyboard)         | Skip next instruction if key not pressed |
 /// | Fx0A   | KEY Vx            | IO (Keyboard)         | Wait for key release. Store key in Vx    |
@@ -43,6 +48,9 @@ use crate::chip8::{Chip8Error, Chip8Result, Register, Address};
 /// | Cxnn   | RAND Vx, value    | IO (Random)           | Load (random & value) into Vx            |
 /// | 00E0   | CLEAR             | IO (Display)          | Clear the display                        |
 /// | Dxyn   | DRAW x, y, n      | IO (Display)          | Draw sprite to display                   |
+/// | Fn01   | PLANE n           | IO (Display)          | Select drawing planes (XO-CHIP)          |
+/// | 00FE   | LOW               | IO (Display)          | Switch to 64x32 resolution (SUPER-CHIP)  |
+/// | 00FF   | HIGH              | IO (Display)          | Switch to 128x64 resolution (SUPER-CHIP) |
 /// ```
 ///
 /// For more info see the individual docs for each instruction.
@@ -245,6 +253,21 @@ pub enum Opcode {
     /// - Set `I` to `I + x + 1`.
     ReadMemory { x: Register },
 
+    /// Assembly: `WRITE Vx, Vy`
+    /// Opcode: `5xy2`
+    ///
+    /// XO-CHIP: Store the values of `Vx..Vy` (inclusive) in memory starting at address `I`.
+    /// The range counts down when `x > y`, so `Vx` is always stored at `I`. `I` is not changed.
+    SaveRange { x: Register, y: Register },
+
+    /// Assembly: `READ Vx, Vy`
+    /// Opcode: `5xy3`
+    ///
+    /// XO-CHIP: Fill registers `Vx..Vy` (inclusive) with the values stored in memory starting at
+    /// address `I`. The range counts down when `x > y`, so `Vx` is always read from `I`. `I` is
+    /// not changed.
+    LoadRange { x: Register, y: Register },
+
     // ============================================================================================
     // = IO Opcodes - Opcodes for interacting with the real world (drawing, input, sound, etc...) =
     // ============================================================================================
@@ -308,19 +331,269 @@ pub enum Opcode {
     ///
     /// When `Draw` is executed it also triggers a screen refresh
     Draw { x: Register, y: Register, n: u8 },
+
+    /// Assembly: `PLANE n`
+    /// Opcode: `Fn01`
+    ///
+    /// XO-CHIP: Select the planes affected by `DRAW` and `CLEAR`. `n` is a bitmask where `1`
+    /// is the first plane and `2` the second.
+    ///
+    /// When both planes are selected `DRAW` reads `2 * n` bytes from `I`: the sprite for the
+    /// first plane followed by the sprite for the second plane.
+    SelectPlane { planes: u8 },
+
+    /// Assembly: `LOW`
+    /// Opcode: `00FE`
+    ///
+    /// SUPER-CHIP: Switch the display to the 64x32 low resolution mode. This clears the display.
+    LowRes,
+
+    /// Assembly: `HIGH`
+    /// Opcode: `00FF`
+    ///
+    /// SUPER-CHIP: Switch the display to the 128x64 high resolution mode. Whether the display is
+    /// cleared or scaled up depends on `ResolutionSwitchQuirk`.
+    HighRes,
+
+    /// Assembly: `EXIT`
+    /// Opcode: `00FD`
+    ///
+    /// SUPER-CHIP: Stop the interpreter. Every following `cycle` does nothing and reports
+    /// `Chip8Output::Halted`.
+    Exit,
+}
+
+/// The groups opcodes are listed under in the `Opcode` table, see `Opcode::category`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum OpcodeCategory {
+    FlowControl,
+    Conditional,
+    ManipulateVx,
+    ManipulateI,
+    ManipulateMemory,
+    Io,
+}
+
+/// How `Opcode::to_assembly_styled` writes numbers such as addresses and constants
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NumberFormat {
+    /// Zero-padded hex with no prefix, e.g. `0AB`. This is what `to_assembly` uses.
+    Hex,
+
+    /// Hex with a `0x` prefix, e.g. `0xAB`, as used by Octo
+    PrefixedHex,
+
+    /// Hex with a `$` prefix, e.g. `$AB`
+    DollarHex,
+
+    /// Decimal, e.g. `171`
+    Decimal,
+}
+
+impl Default for NumberFormat {
+    fn default() -> NumberFormat {
+        NumberFormat::Hex
+    }
+}
+
+/// The conventions used by `Opcode::to_assembly_styled`. The default style matches `to_assembly`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct AssemblyStyle {
+    pub numbers: NumberFormat,
+
+    /// Write the whole instruction in lowercase, e.g. `jump 0xabc`
+    pub lowercase: bool,
+}
+
+impl AssemblyStyle {
+    pub fn with_numbers(mut self, numbers: NumberFormat) -> Self {
+        self.numbers = numbers;
+        self
+    }
+
+    pub fn with_lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+
+    // Write `value`, which is `digits` hex digits wide when zero-padded
+    fn number(&self, value: u16, digits: usize) -> String {
+        match self.numbers {
+            NumberFormat::Hex => format!("{:0digits$X}", value, digits = digits),
+            NumberFormat::PrefixedHex => format!("0x{:X}", value),
+            NumberFormat::DollarHex => format!("${:X}", value),
+            NumberFormat::Decimal => value.to_string(),
+        }
+    }
+}
+
+/// A row of the `Opcode` table, see `Opcode::all_variants`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct OpcodeInfo {
+    /// The nibble pattern the opcode is decoded from, e.g. `Dxyn`
+    pub pattern: &'static str,
+
+    /// The assembly name, as returned by `Opcode::to_assembly_name`
+    pub mnemonic: &'static str,
+
+    /// The assembly with placeholder arguments, e.g. `DRAW x, y, n`
+    pub assembly: &'static str,
+
+    pub category: OpcodeCategory,
+    pub description: &'static str,
+}
+
+macro_rules! opcode_info {
+    ($($pattern:literal $mnemonic:literal $assembly:literal $category:ident $description:literal;)*) => {
+        const OPCODE_INFO: &[OpcodeInfo] = &[
+            $(OpcodeInfo {
+                pattern: $pattern,
+                mnemonic: $mnemonic,
+                assembly: $assembly,
+                category: OpcodeCategory::$category,
+                description: $description,
+            },)*
+        ];
+    };
+}
+
+opcode_info! {
+    "2nnn" "CALL" "CALL addr" FlowControl "Call Subroutine";
+    "00EE" "RET" "RET" FlowControl "Return";
+    "1nnn" "JUMP" "JUMP addr" FlowControl "Jump to Address";
+    "Bnnn" "JUMP" "JUMP addr,V0" FlowControl "Jump to Address with Offset";
+    "00FD" "EXIT" "EXIT" FlowControl "Stop the interpreter (SUPER-CHIP)";
+    "3xnn" "SKIP.EQ" "SKIP.EQ Vx, value" Conditional "Skip Next If Equal";
+    "5xy0" "SKIP.EQ" "SKIP.EQ Vx, Vy" Conditional "Skip Next If Registers Equal";
+    "4xnn" "SKIP.NE" "SKIP.NE Vx, value" Conditional "Skip Next If Not Equal";
+    "9xy0" "SKIP.NE" "SKIP.NE Vx, Vy" Conditional "Skip Next If Registers Not Equal";
+    "6xnn" "LOAD" "LOAD Vx, value" ManipulateVx "Load Value into Vx";
+    "8xy0" "LOAD" "LOAD Vx, Vy" ManipulateVx "Load Vy into Vx";
+    "8xy1" "OR" "OR Vx, Vy" ManipulateVx "Set Vx to Vx OR Vy";
+    "8xy2" "AND" "AND Vx, Vy" ManipulateVx "Set Vx to Vx AND Vy";
+    "8xy3" "XOR" "XOR Vx, Vy" ManipulateVx "Set Vx to Vx XOR Vy";
+    "8xy4" "ADD" "ADD Vx, Vy" ManipulateVx "Set Vx to Vx + Vy. Set VF to carry";
+    "7xnn" "ADD" "ADD Vx, value" ManipulateVx "Set Vx to Vx + value";
+    "8xy5" "SUBXY" "SUBXY Vx, Vy" ManipulateVx "Set Vx to Vx - Vy. Set VF to carry";
+    "8xy7" "SUBYX" "SUBYX Vx, Vy" ManipulateVx "Set Vx to Vy - Vx. Set VF to carry";
+    "8xy6" "SHR" "SHR Vx" ManipulateVx "Set Vx to Vx >> 1. Set VF to LSB";
+    "8xyE" "SHL" "SHL Vx" ManipulateVx "Set Vx to Vx << 1. Set VF to MSB";
+    "Annn" "IDX" "IDX addr" ManipulateI "Set I to addr";
+    "Fx1E" "ADD" "ADD I, Vx" ManipulateI "Set I to I + Vx";
+    "Fx29" "FONT" "FONT Vx" ManipulateI "Set I to the font data representing Vx";
+    "Fx55" "WRITE" "WRITE Vx" ManipulateMemory "Write values V0..Vx to memory at I";
+    "Fx33" "BCD" "BCD Vx" ManipulateMemory "Write BCD of Vx to memory at I,I+1,I+2";
+    "Fx65" "READ" "READ Vx" ManipulateMemory "Read memory at I into V0..Vx";
+    "5xy2" "WRITE" "WRITE Vx, Vy" ManipulateMemory "Write Vx..Vy to memory at I (XO-CHIP)";
+    "5xy3" "READ" "READ Vx, Vy" ManipulateMemory "Read memory at I into Vx..Vy (XO-CHIP)";
+    "Ex9E" "SKIP.KEQ" "SKIP.KEQ Vx" Io "Skip next instruction if key pressed";
+    "ExA1" "SKIP.KNE" "SKIP.KNE Vx" Io "Skip next instruction if key not pressed";
+    "Fx0A" "KEY" "KEY Vx" Io "Wait for key release. Store key in Vx";
+    "Fx07" "LOAD" "LOAD Vx, DELAY" Io "Load DELAY register into Vx";
+    "Fx15" "LOAD" "LOAD DELAY, Vx" Io "Load Vx into DELAY register";
+    "Fx18" "LOAD" "LOAD SOUND, Vx" Io "Load Vx into SOUND register";
+    "Cxnn" "RAND" "RAND Vx, value" Io "Load (random & value) into Vx";
+    "00E0" "CLEAR" "CLEAR" Io "Clear the display";
+    "Dxyn" "DRAW" "DRAW x, y, n" Io "Draw sprite to display";
+    "Fn01" "PLANE" "PLANE n" Io "Select drawing planes (XO-CHIP)";
+    "00FE" "LOW" "LOW" Io "Switch to 64x32 resolution (SUPER-CHIP)";
+    "00FF" "HIGH" "HIGH" Io "Switch to 128x64 resolution (SUPER-CHIP)";
 }
 
 impl Opcode {
+    /// Every opcode the interpreter understands, in the order of the `Opcode` table.
+    pub fn all_variants() -> Vec<OpcodeInfo> {
+        OPCODE_INFO.to_vec()
+    }
+
     pub fn from_bytes(bytes: &[u8; 2]) -> Chip8Result<Opcode> {
         let opcode = u16::from_be_bytes(*bytes);
         Opcode::from_u16(opcode)
     }
 
     /// Return the byte representation of this opcode.
+    ///
+    /// Panics if any argument is out of range. See `validate`.
     pub fn to_bytes(&self) -> [u8; 2] {
+        if let Err(e) = self.validate() {
+            panic!("Cannot encode {:?}: {}", self, e);
+        }
+
         self.to_u16().to_be_bytes()
     }
 
+    /// Check that every argument of this opcode fits in the bits available to it:
+    ///
+    /// - Addresses are 12 bits
+    /// - Registers are 4 bits
+    /// - `Draw`'s `n` is 4 bits
+    ///
+    /// Opcodes with out of range arguments would otherwise be silently truncated or corrupt
+    /// neighbouring bits when encoded.
+    pub fn validate(&self) -> Chip8Result<()> {
+        let check = |argument: &'static str, value: u16, max: u16| {
+            if value > max {
+                Err(Chip8Error::ArgumentOutOfRange { argument, value, max })
+            } else {
+                Ok(())
+            }
+        };
+        let address = |address: &Address| check("address", *address, 0xFFF);
+        let register = |register: &Register| check("register", *register as u16, 0xF);
+
+        match self {
+            // Flow Control
+            Opcode::CallSubroutine(addr) => address(addr),
+            Opcode::Return => Ok(()),
+            Opcode::Jump(addr) => address(addr),
+            Opcode::JumpWithOffset(addr) => address(addr),
+
+            // Conditional Execution
+            Opcode::SkipNextIfEqual { x, value: _ } => register(x),
+            Opcode::SkipNextIfNotEqual { x, value: _ } => register(x),
+            Opcode::SkipNextIfRegisterEqual { x, y } => register(x).and(register(y)),
+            Opcode::SkipNextIfRegisterNotEqual { x, y } => register(x).and(register(y)),
+
+            // Manipulate Vx
+            Opcode::LoadConstant { x, value: _ } => register(x),
+            Opcode::Load { x, y } => register(x).and(register(y)),
+            Opcode::Or { x, y } => register(x).and(register(y)),
+            Opcode::And { x, y } => register(x).and(register(y)),
+            Opcode::Xor { x, y } => register(x).and(register(y)),
+            Opcode::Add { x, y } => register(x).and(register(y)),
+            Opcode::AddConstant { x, value: _ } => register(x),
+            Opcode::SubtractXY { x, y } => register(x).and(register(y)),
+            Opcode::SubtractYX { x, y } => register(x).and(register(y)),
+            Opcode::ShiftRight { x, y } => register(x).and(register(y)),
+            Opcode::ShiftLeft { x, y } => register(x).and(register(y)),
+
+            // Manipulate I
+            Opcode::IndexAddress(addr) => address(addr),
+            Opcode::AddAddress { x } => register(x),
+            Opcode::IndexFont { x } => register(x),
+
+            // Manipulate Memory
+            Opcode::WriteMemory { x } => register(x),
+            Opcode::WriteBCD { x } => register(x),
+            Opcode::ReadMemory { x } => register(x),
+            Opcode::SaveRange { x, y } => register(x).and(register(y)),
+            Opcode::LoadRange { x, y } => register(x).and(register(y)),
+
+            // IO
+            Opcode::SkipIfKeyPressed { x } => register(x),
+            Opcode::SkipIfKeyNotPressed { x } => register(x),
+            Opcode::WaitForKeyRelease { x } => register(x),
+            Opcode::LoadDelayIntoRegister { x } => register(x),
+            Opcode::LoadRegisterIntoDelay { x } => register(x),
+            Opcode::LoadRegisterIntoSound { x } => register(x),
+            Opcode::Random { x, mask: _ } => register(x),
+            Opcode::ClearScreen => Ok(()),
+            Opcode::Draw { x, y, n } => register(x).and(register(y)).and(check("n", *n as u16, 0xF)),
+            Opcode::SelectPlane { planes } => check("planes", *planes as u16, 0x3),
+            Opcode::LowRes | Opcode::HighRes | Opcode::Exit => Ok(()),
+        }
+    }
+
     pub fn to_rom(opcodes: Vec<Opcode>) -> Vec<u8> {
         opcodes.iter()
             .flat_map(|op| op.to_bytes().to_vec())
@@ -346,6 +619,8 @@ impl Opcode {
             (0x3, x, _, _) => Ok(Opcode::SkipNextIfEqual { x, value: (word & 0x00FF) as u8 }),
             (0x4, x, _, _) => Ok(Opcode::SkipNextIfNotEqual { x, value: (word & 0x00FF) as u8 }),
             (0x5, x, y, 0x0) => Ok(Opcode::SkipNextIfRegisterEqual { x, y }),
+            (0x5, x, y, 0x2) => Ok(Opcode::SaveRange { x, y }),
+            (0x5, x, y, 0x3) => Ok(Opcode::LoadRange { x, y }),
             (0x9, x, y, 0x0) => Ok(Opcode::SkipNextIfRegisterNotEqual { x, y }),
 
             // Manipulate Vx
@@ -381,6 +656,10 @@ impl Opcode {
             (0xC, x, _, _) => Ok(Opcode::Random { x, mask: (word & 0x00FF) as u8 }),
             (0x0, 0x0, 0xE, 0x0) => Ok(Opcode::ClearScreen),
             (0xD, x, y, n) => Ok(Opcode::Draw { x, y, n }),
+            (0xF, planes, 0x0, 0x1) if planes <= 0x3 => Ok(Opcode::SelectPlane { planes }),
+            (0x0, 0x0, 0xF, 0xD) => Ok(Opcode::Exit),
+            (0x0, 0x0, 0xF, 0xE) => Ok(Opcode::LowRes),
+            (0x0, 0x0, 0xF, 0xF) => Ok(Opcode::HighRes),
 
             _ => Err(Chip8Error::UnsupportedOpcode(word)),
         }
@@ -423,6 +702,8 @@ impl Opcode {
             Opcode::WriteMemory { x } => 0xF055 | ((*x as u16) << 8),
             Opcode::WriteBCD { x } => 0xF033 | ((*x as u16) << 8),
             Opcode::ReadMemory { x } => 0xF065 | ((*x as u16) << 8),
+            Opcode::SaveRange { x, y } => 0x5002 | ((*x as u16) << 8) | ((*y as u16) << 4),
+            Opcode::LoadRange { x, y } => 0x5003 | ((*x as u16) << 8) | ((*y as u16) << 4),
 
             // IO
             Opcode::SkipIfKeyPressed { x } => 0xE09E | ((*x as u16) << 8),
@@ -434,6 +715,10 @@ impl Opcode {
             Opcode::Random { x, mask } => 0xC000 | ((*x as u16) << 8) | (*mask as u16),
             Opcode::ClearScreen => 0x00E0,
             Opcode::Draw { x, y, n } => 0xD000 | ((*x as u16) << 8) | ((*y as u16) << 4) | (*n as u16),
+            Opcode::SelectPlane { planes } => 0xF001 | ((*planes as u16) << 8),
+            Opcode::LowRes => 0x00FE,
+            Opcode::HighRes => 0x00FF,
+            Opcode::Exit => 0x00FD,
         }
     }
 
@@ -474,6 +759,8 @@ impl Opcode {
             Opcode::WriteMemory { x: _ } => "WRITE",
             Opcode::WriteBCD { x: _ } => "BCD",
             Opcode::ReadMemory { x: _ } => "READ",
+            Opcode::SaveRange { x: _, y: _ } => "WRITE",
+            Opcode::LoadRange { x: _, y: _ } => "READ",
 
             // IO
             Opcode::SkipIfKeyPressed { x: _ } => "SKIP.KEQ",
@@ -485,12 +772,20 @@ impl Opcode {
             Opcode::Random { x: _, mask: _ } => "RAND",
             Opcode::ClearScreen => "CLEAR",
             Opcode::Draw { x: _, y: _, n: _ } => "DRAW",
+            Opcode::SelectPlane { planes: _ } => "PLANE",
+            Opcode::LowRes => "LOW",
+            Opcode::HighRes => "HIGH",
+            Opcode::Exit => "EXIT",
         }
     }
 
     pub fn to_assembly_args(&self) -> Option<String> {
-        let fmt_addr = |addr| Some(format!("{:03X}", addr));
-        let fmt_reg_value = |x, value| Some(format!("V{:X}, {:02X}", x, value));
+        self.to_assembly_args_styled(AssemblyStyle::default())
+    }
+
+    fn to_assembly_args_styled(&self, style: AssemblyStyle) -> Option<String> {
+        let fmt_addr = |addr: &u16| Some(style.number(*addr, 3));
+        let fmt_reg_value = |x, value: &u8| Some(format!("V{:X}, {}", x, style.number(*value as u16, 2)));
         let fmt_reg_reg = |x, y| Some(format!("V{:X}, V{:X}", x, y));
         let fmt_reg = |x| Some(format!("V{:X}", x));
 
@@ -529,6 +824,8 @@ impl Opcode {
             Opcode::WriteMemory { x } => fmt_reg(x),
             Opcode::WriteBCD { x } => fmt_reg(x),
             Opcode::ReadMemory { x } => fmt_reg(x),
+            Opcode::SaveRange { x, y } => fmt_reg_reg(x, y),
+            Opcode::LoadRange { x, y } => fmt_reg_reg(x, y),
 
             // // IO
             Opcode::SkipIfKeyPressed { x } => fmt_reg(x),
@@ -540,21 +837,267 @@ impl Opcode {
             Opcode::Random { x, mask } => fmt_reg_value(x, mask),
             Opcode::ClearScreen => None,
             Opcode::Draw { x, y, n } => Some(format!("V{:X}, V{:X}, V{:X}", x, y, n)),
+            Opcode::SelectPlane { planes } => Some(style.number(*planes as u16, 1)),
+            Opcode::LowRes => None,
+            Opcode::HighRes => None,
+            Opcode::Exit => None,
         }
     }
 
     pub fn to_assembly(&self) -> String {
+        self.to_assembly_styled(AssemblyStyle::default())
+    }
+
+    /// Like `to_assembly` but following the conventions in `style`, e.g. lowercase with `0x`
+    /// prefixed numbers. Register names are always hex.
+    pub fn to_assembly_styled(&self, style: AssemblyStyle) -> String {
         let mut assembly = self.to_assembly_name().to_string();
 
-        if let Some(mut args) = self.to_assembly_args() {
+        if let Some(mut args) = self.to_assembly_args_styled(style) {
             args.retain(|c| !c.is_whitespace());
 
             assembly += " ";
             assembly += &args;
         }
 
+        if style.lowercase {
+            assembly.make_ascii_lowercase();
+        }
+
         assembly
     }
+
+    /// The address this opcode transfers control to, if it can be known without running the program.
+    ///
+    /// `JUMP addr, V0` depends on `V0` so it has no fixed target.
+    pub fn target_address(&self) -> Option<Address> {
+        match self {
+            Opcode::CallSubroutine(address) | Opcode::Jump(address) => Some(*address),
+            _ => None,
+        }
+    }
+
+    /// Returns true if this opcode can continue execution anywhere other than the next opcode,
+    /// i.e. jumps, calls, returns, skips and `EXIT`.
+    pub fn is_control_flow(&self) -> bool {
+        matches!(self,
+            Opcode::CallSubroutine(_)
+            | Opcode::Return
+            | Opcode::Jump(_)
+            | Opcode::JumpWithOffset(_)
+            | Opcode::SkipNextIfEqual { .. }
+            | Opcode::SkipNextIfRegisterEqual { .. }
+            | Opcode::SkipNextIfNotEqual { .. }
+            | Opcode::SkipNextIfRegisterNotEqual { .. }
+            | Opcode::SkipIfKeyPressed { .. }
+            | Opcode::SkipIfKeyNotPressed { .. }
+            | Opcode::Exit
+        )
+    }
+
+    pub fn category(&self) -> OpcodeCategory {
+        match self {
+            Opcode::CallSubroutine(_)
+            | Opcode::Return
+            | Opcode::Jump(_)
+            | Opcode::JumpWithOffset(_)
+            | Opcode::Exit => OpcodeCategory::FlowControl,
+
+            Opcode::SkipNextIfEqual { .. }
+            | Opcode::SkipNextIfNotEqual { .. }
+            | Opcode::SkipNextIfRegisterEqual { .. }
+            | Opcode::SkipNextIfRegisterNotEqual { .. } => OpcodeCategory::Conditional,
+
+            Opcode::LoadConstant { .. }
+            | Opcode::Load { .. }
+            | Opcode::Or { .. }
+            | Opcode::And { .. }
+            | Opcode::Xor { .. }
+            | Opcode::Add { .. }
+            | Opcode::AddConstant { .. }
+            | Opcode::SubtractXY { .. }
+            | Opcode::SubtractYX { .. }
+            | Opcode::ShiftRight { .. }
+            | Opcode::ShiftLeft { .. } => OpcodeCategory::ManipulateVx,
+
+            Opcode::IndexAddress(_)
+            | Opcode::AddAddress { .. }
+            | Opcode::IndexFont { .. } => OpcodeCategory::ManipulateI,
+
+            Opcode::WriteMemory { .. }
+            | Opcode::WriteBCD { .. }
+            | Opcode::ReadMemory { .. }
+            | Opcode::SaveRange { .. }
+            | Opcode::LoadRange { .. } => OpcodeCategory::ManipulateMemory,
+
+            Opcode::SkipIfKeyPressed { .. }
+            | Opcode::SkipIfKeyNotPressed { .. }
+            | Opcode::WaitForKeyRelease { .. }
+            | Opcode::LoadDelayIntoRegister { .. }
+            | Opcode::LoadRegisterIntoDelay { .. }
+            | Opcode::LoadRegisterIntoSound { .. }
+            | Opcode::Random { .. }
+            | Opcode::ClearScreen
+            | Opcode::Draw { .. }
+            | Opcode::SelectPlane { .. }
+            | Opcode::LowRes
+            | Opcode::HighRes => OpcodeCategory::Io,
+        }
+    }
+
+    /// The quirks that can change what this opcode does. Most opcodes behave the same on every
+    /// interpreter and return an empty slice.
+    pub fn quirks(&self) -> &'static [Quirk] {
+        match self {
+            Opcode::ShiftRight { .. } | Opcode::ShiftLeft { .. } => &[Quirk::BitShift],
+            Opcode::Or { .. } | Opcode::And { .. } | Opcode::Xor { .. } => &[Quirk::Logic],
+            Opcode::WriteMemory { .. } | Opcode::ReadMemory { .. } => &[Quirk::ReadWriteIncrement, Quirk::MemoryBounds],
+            Opcode::Draw { .. } => &[Quirk::SpriteWrap, Quirk::DrawMode, Quirk::CollisionCount],
+            Opcode::HighRes => &[Quirk::ResolutionSwitch],
+            Opcode::JumpWithOffset(_) | Opcode::SaveRange { .. } | Opcode::LoadRange { .. } => &[Quirk::MemoryBounds],
+            _ => &[],
+        }
+    }
+
+    /// Returns true if the interpreter `profile` is named after understands this opcode.
+    ///
+    /// `QuirkProfile::Modern` supports every opcode, `QuirkProfile::SuperChip` adds the
+    /// SUPER-CHIP opcodes to the original set and `QuirkProfile::Chip8` only has the original set.
+    pub fn is_supported_in(&self, profile: QuirkProfile) -> bool {
+        match self {
+            Opcode::LowRes | Opcode::HighRes | Opcode::Exit => profile != QuirkProfile::Chip8,
+            Opcode::SelectPlane { .. } | Opcode::SaveRange { .. } | Opcode::LoadRange { .. } => {
+                profile == QuirkProfile::Modern
+            },
+            _ => true,
+        }
+    }
+}
+
+/// A single parsed argument of an assembly instruction
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum AssemblyArg {
+    Register(Register),
+    Value(u16),
+    Index,
+    Delay,
+    Sound,
+}
+
+impl AssemblyArg {
+    fn parse(arg: &str) -> Chip8Result<AssemblyArg> {
+        match arg {
+            "I" => return Ok(AssemblyArg::Index),
+            "DELAY" => return Ok(AssemblyArg::Delay),
+            "SOUND" => return Ok(AssemblyArg::Sound),
+            _ => {}
+        }
+
+        if arg.len() == 2 && arg.starts_with('V') {
+            return u8::from_str_radix(&arg[1..], 16)
+                .map(AssemblyArg::Register)
+                .map_err(|_| Chip8Error::ParseError(format!("invalid register: {}", arg)));
+        }
+
+        let digits = arg.trim_start_matches("0X");
+        u16::from_str_radix(digits, 16)
+            .map(AssemblyArg::Value)
+            .map_err(|_| Chip8Error::ParseError(format!("invalid argument: {}", arg)))
+    }
+}
+
+/// Parses a single instruction in the format produced by `Opcode::to_assembly`, e.g. `"DRAW VA,VB,V1"`.
+///
+/// Parsing is case-insensitive and ignores whitespace around arguments. Values and addresses
+/// are hexadecimal and may optionally be prefixed with `0x`.
+impl FromStr for Opcode {
+    type Err = Chip8Error;
+
+    fn from_str(assembly: &str) -> Chip8Result<Opcode> {
+        use AssemblyArg::*;
+
+        let assembly = assembly.trim().to_uppercase();
+        let mut parts = assembly.splitn(2, char::is_whitespace);
+        let name = parts.next().unwrap_or("");
+        let args = match parts.next() {
+            Some(args) => args.split(',')
+                .map(|arg| AssemblyArg::parse(arg.trim()))
+                .collect::<Chip8Result<Vec<AssemblyArg>>>()?,
+            None => Vec::new(),
+        };
+
+        let byte = |value: u16| -> Chip8Result<u8> {
+            if value > 0xFF {
+                Err(Chip8Error::ParseError(format!("value out of range: {:X}", value)))
+            } else {
+                Ok(value as u8)
+            }
+        };
+
+        let opcode = match (name, args.as_slice()) {
+            // Flow Control
+            ("CALL", [Value(addr)]) => Opcode::CallSubroutine(*addr),
+            ("RET", []) => Opcode::Return,
+            ("JUMP", [Value(addr)]) => Opcode::Jump(*addr),
+            ("JUMP", [Value(addr), Register(0x0)]) => Opcode::JumpWithOffset(*addr),
+
+            // Conditional Execution
+            ("SKIP.EQ", [Register(x), Value(value)]) => Opcode::SkipNextIfEqual { x: *x, value: byte(*value)? },
+            ("SKIP.NE", [Register(x), Value(value)]) => Opcode::SkipNextIfNotEqual { x: *x, value: byte(*value)? },
+            ("SKIP.EQ", [Register(x), Register(y)]) => Opcode::SkipNextIfRegisterEqual { x: *x, y: *y },
+            ("SKIP.NE", [Register(x), Register(y)]) => Opcode::SkipNextIfRegisterNotEqual { x: *x, y: *y },
+
+            // Manipulate Vx
+            ("LOAD", [Register(x), Value(value)]) => Opcode::LoadConstant { x: *x, value: byte(*value)? },
+            ("LOAD", [Register(x), Register(y)]) => Opcode::Load { x: *x, y: *y },
+            ("OR", [Register(x), Register(y)]) => Opcode::Or { x: *x, y: *y },
+            ("AND", [Register(x), Register(y)]) => Opcode::And { x: *x, y: *y },
+            ("XOR", [Register(x), Register(y)]) => Opcode::Xor { x: *x, y: *y },
+            ("ADD", [Register(x), Register(y)]) => Opcode::Add { x: *x, y: *y },
+            ("ADD", [Register(x), Value(value)]) => Opcode::AddConstant { x: *x, value: byte(*value)? },
+            ("SUBXY", [Register(x), Register(y)]) => Opcode::SubtractXY { x: *x, y: *y },
+            ("SUBYX", [Register(x), Register(y)]) => Opcode::SubtractYX { x: *x, y: *y },
+            ("SHR", [Register(x)]) => Opcode::ShiftRight { x: *x, y: *x },
+            ("SHR", [Register(x), Register(y)]) => Opcode::ShiftRight { x: *x, y: *y },
+            ("SHL", [Register(x)]) => Opcode::ShiftLeft { x: *x, y: *x },
+            ("SHL", [Register(x), Register(y)]) => Opcode::ShiftLeft { x: *x, y: *y },
+
+            // Manipulate I
+            ("IDX", [Value(addr)]) => Opcode::IndexAddress(*addr),
+            ("ADD", [Index, Register(x)]) => Opcode::AddAddress { x: *x },
+            ("FONT", [Register(x)]) => Opcode::IndexFont { x: *x },
+
+            // Manipulate Memory
+            ("WRITE", [Register(x)]) => Opcode::WriteMemory { x: *x },
+            ("BCD", [Register(x)]) => Opcode::WriteBCD { x: *x },
+            ("READ", [Register(x)]) => Opcode::ReadMemory { x: *x },
+            ("WRITE", [Register(x), Register(y)]) => Opcode::SaveRange { x: *x, y: *y },
+            ("READ", [Register(x), Register(y)]) => Opcode::LoadRange { x: *x, y: *y },
+
+            // IO
+            ("SKIP.KEQ", [Register(x)]) => Opcode::SkipIfKeyPressed { x: *x },
+            ("SKIP.KNE", [Register(x)]) => Opcode::SkipIfKeyNotPressed { x: *x },
+            ("KEY", [Register(x)]) => Opcode::WaitForKeyRelease { x: *x },
+            ("LOAD", [Register(x), Delay]) => Opcode::LoadDelayIntoRegister { x: *x },
+            ("LOAD", [Delay, Register(x)]) => Opcode::LoadRegisterIntoDelay { x: *x },
+            ("LOAD", [Sound, Register(x)]) => Opcode::LoadRegisterIntoSound { x: *x },
+            ("RAND", [Register(x), Value(mask)]) => Opcode::Random { x: *x, mask: byte(*mask)? },
+            ("CLEAR", []) => Opcode::ClearScreen,
+            ("DRAW", [Register(x), Register(y), Register(n)]) => Opcode::Draw { x: *x, y: *y, n: *n },
+            ("DRAW", [Register(x), Register(y), Value(n)]) => Opcode::Draw { x: *x, y: *y, n: byte(*n)? },
+            ("PLANE", [Value(planes)]) => Opcode::SelectPlane { planes: byte(*planes)? },
+            ("LOW", []) => Opcode::LowRes,
+            ("HIGH", []) => Opcode::HighRes,
+            ("EXIT", []) => Opcode::Exit,
+
+            _ => return Err(Chip8Error::ParseError(format!("unrecognized instruction: {}", assembly))),
+        };
+
+        opcode.validate()
+            .map_err(|e| Chip8Error::ParseError(e.to_string()))?;
+
+        Ok(opcode)
+    }
 }
 
 #[cfg(test)]
@@ -581,6 +1124,142 @@ mod tests {
         assert_eq!(rom, [0x00, 0xE0, 0x8A, 0xB4])
     }
 
+    #[test]
+    fn to_assembly_styled() {
+        let jump = Opcode::Jump(0xABC);
+
+        assert_eq!(jump.to_assembly_styled(AssemblyStyle::default()), "JUMP ABC");
+        assert_eq!(jump.to_assembly_styled(AssemblyStyle::default().with_numbers(NumberFormat::PrefixedHex).with_lowercase(true)), "jump 0xabc");
+        assert_eq!(jump.to_assembly_styled(AssemblyStyle::default().with_numbers(NumberFormat::DollarHex)), "JUMP $ABC");
+        assert_eq!(jump.to_assembly_styled(AssemblyStyle::default().with_numbers(NumberFormat::Decimal)), "JUMP 2748");
+
+        let load = Opcode::LoadConstant { x: 0xA, value: 0x0F };
+        assert_eq!(load.to_assembly_styled(AssemblyStyle::default()), "LOAD VA,0F");
+        assert_eq!(load.to_assembly_styled(AssemblyStyle::default().with_numbers(NumberFormat::Decimal)), "LOAD VA,15");
+    }
+
+    #[test]
+    fn all_variants_contains_draw() {
+        let draw = Opcode::all_variants().into_iter().find(|info| info.mnemonic == "DRAW").unwrap();
+
+        assert_eq!(draw.pattern, "Dxyn");
+        assert_eq!(draw.category, OpcodeCategory::Io);
+    }
+
+    #[test]
+    fn all_variants_match_decoded_opcodes() {
+        for info in Opcode::all_variants() {
+            let word = info.pattern.chars()
+                .map(|c| c.to_digit(16).filter(|_| !c.is_lowercase()).unwrap_or(0) as u16)
+                .fold(0, |word, nibble| (word << 4) | nibble);
+            let opcode = Opcode::from_u16(word).unwrap();
+
+            assert_eq!(opcode.to_assembly_name(), info.mnemonic, "{}", info.pattern);
+            assert_eq!(opcode.category(), info.category, "{}", info.pattern);
+        }
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(Opcode::Draw { x: 0xA, y: 0xB, n: 0xF }.validate(), Ok(()));
+        assert_eq!(Opcode::CallSubroutine(0xFFF).validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_address_out_of_range() {
+        assert_eq!(
+            Opcode::CallSubroutine(0x1ABC).validate(),
+            Err(Chip8Error::ArgumentOutOfRange { argument: "address", value: 0x1ABC, max: 0xFFF })
+        );
+    }
+
+    #[test]
+    fn validate_draw_n_out_of_range() {
+        assert_eq!(
+            Opcode::Draw { x: 0x0, y: 0x1, n: 0x10 }.validate(),
+            Err(Chip8Error::ArgumentOutOfRange { argument: "n", value: 0x10, max: 0xF })
+        );
+    }
+
+    #[test]
+    fn validate_register_out_of_range() {
+        assert_eq!(
+            Opcode::Load { x: 0x1, y: 0x10 }.validate(),
+            Err(Chip8Error::ArgumentOutOfRange { argument: "register", value: 0x10, max: 0xF })
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn to_bytes_panics_on_invalid_opcode() {
+        Opcode::Jump(0x1000).to_bytes();
+    }
+
+    #[test]
+    fn target_address() {
+        assert_eq!(Opcode::Jump(0x2AB).target_address(), Some(0x2AB));
+        assert_eq!(Opcode::CallSubroutine(0x3CD).target_address(), Some(0x3CD));
+        assert_eq!(Opcode::JumpWithOffset(0x2AB).target_address(), None);
+        assert_eq!(Opcode::Return.target_address(), None);
+    }
+
+    #[test]
+    fn is_control_flow() {
+        assert!(Opcode::Jump(0x2AB).is_control_flow());
+        assert!(Opcode::JumpWithOffset(0x2AB).is_control_flow());
+        assert!(Opcode::Return.is_control_flow());
+        assert!(Opcode::SkipIfKeyPressed { x: 0x1 }.is_control_flow());
+        assert!(!Opcode::ClearScreen.is_control_flow());
+        assert!(!Opcode::LoadConstant { x: 0x1, value: 0x2 }.is_control_flow());
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!("DRAW VA,VB,V1".parse::<Opcode>(), Ok(Opcode::Draw { x: 0xA, y: 0xB, n: 0x1 }));
+        assert_eq!("CALL ABC".parse::<Opcode>(), Ok(Opcode::CallSubroutine(0xABC)));
+        assert_eq!("JUMP ABC,V0".parse::<Opcode>(), Ok(Opcode::JumpWithOffset(0xABC)));
+        assert_eq!("ADD I,VA".parse::<Opcode>(), Ok(Opcode::AddAddress { x: 0xA }));
+        assert_eq!("SKIP.NE VA,15".parse::<Opcode>(), Ok(Opcode::SkipNextIfNotEqual { x: 0xA, value: 0x15 }));
+    }
+
+    #[test]
+    fn from_str_no_args() {
+        assert_eq!("RET".parse::<Opcode>(), Ok(Opcode::Return));
+        assert_eq!("CLEAR".parse::<Opcode>(), Ok(Opcode::ClearScreen));
+    }
+
+    #[test]
+    fn from_str_timer_keywords() {
+        assert_eq!("LOAD VA,DELAY".parse::<Opcode>(), Ok(Opcode::LoadDelayIntoRegister { x: 0xA }));
+        assert_eq!("LOAD DELAY,VA".parse::<Opcode>(), Ok(Opcode::LoadRegisterIntoDelay { x: 0xA }));
+        assert_eq!("LOAD SOUND,VA".parse::<Opcode>(), Ok(Opcode::LoadRegisterIntoSound { x: 0xA }));
+    }
+
+    #[test]
+    fn from_str_ignores_case_and_whitespace() {
+        assert_eq!("  load va, 0x10 ".parse::<Opcode>(), Ok(Opcode::LoadConstant { x: 0xA, value: 0x10 }));
+    }
+
+    #[test]
+    fn from_str_unrecognized_instruction() {
+        assert_eq!(
+            "JUMP VA".parse::<Opcode>(),
+            Err(Chip8Error::ParseError("unrecognized instruction: JUMP VA".to_string()))
+        );
+    }
+
+    #[test]
+    fn from_str_value_out_of_range() {
+        assert_eq!(
+            "LOAD VA,100".parse::<Opcode>(),
+            Err(Chip8Error::ParseError("value out of range: 100".to_string()))
+        );
+        assert_eq!(
+            "JUMP 1000".parse::<Opcode>(),
+            Err(Chip8Error::ParseError("address out of range: 1000 (max fff)".to_string()))
+        );
+    }
+
     /// `opcode_test` generates data-driven tests for all opcodes covering:
     ///
     /// - `Opcode::from_u16`
@@ -648,6 +1327,8 @@ mod tests {
     opcode_tests!(WriteBCD, Opcode::WriteBCD { x: 0xA }, 0xFA33, "BCD VA");
     opcode_tests!(WriteMemory, Opcode::WriteMemory { x: 0xA }, 0xFA55, "WRITE VA");
     opcode_tests!(ReadMemory, Opcode::ReadMemory { x: 0xA }, 0xFA65, "READ VA");
+    opcode_tests!(SaveRange, Opcode::SaveRange { x: 0x1, y: 0xA }, 0x51A2, "WRITE V1,VA");
+    opcode_tests!(LoadRange, Opcode::LoadRange { x: 0xA, y: 0x1 }, 0x5A13, "READ VA,V1");
 
     // IO
     opcode_tests!(SkipIfKeyPressed, Opcode::SkipIfKeyPressed { x: 0xA }, 0xEA9E, "SKIP.KEQ VA");
@@ -659,4 +1340,43 @@ mod tests {
     opcode_tests!(Random, Opcode::Random { x: 0x1, mask: 0x52 }, 0xC152, "RAND V1,52");
     opcode_tests!(ClearScreen, Opcode::ClearScreen, 0x00E0, "CLEAR");
     opcode_tests!(Draw, Opcode::Draw { x: 0xA, y: 0xB, n: 0x1 }, 0xDAB1, "DRAW VA,VB,V1");
+    opcode_tests!(SelectPlane, Opcode::SelectPlane { planes: 0x3 }, 0xF301, "PLANE 3");
+    opcode_tests!(LowRes, Opcode::LowRes, 0x00FE, "LOW");
+    opcode_tests!(HighRes, Opcode::HighRes, 0x00FF, "HIGH");
+    opcode_tests!(Exit, Opcode::Exit, 0x00FD, "EXIT");
+
+    #[test]
+    fn category() {
+        assert_eq!(Opcode::Jump(0x200).category(), OpcodeCategory::FlowControl);
+        assert_eq!(Opcode::SkipNextIfEqual { x: 0x0, value: 0x1 }.category(), OpcodeCategory::Conditional);
+        assert_eq!(Opcode::Add { x: 0x0, y: 0x1 }.category(), OpcodeCategory::ManipulateVx);
+        assert_eq!(Opcode::IndexFont { x: 0x0 }.category(), OpcodeCategory::ManipulateI);
+        assert_eq!(Opcode::WriteBCD { x: 0x0 }.category(), OpcodeCategory::ManipulateMemory);
+        assert_eq!(Opcode::Draw { x: 0x0, y: 0x1, n: 0x5 }.category(), OpcodeCategory::Io);
+    }
+
+    #[test]
+    fn quirks() {
+        assert_eq!(Opcode::ShiftLeft { x: 0x0, y: 0x1 }.quirks(), &[Quirk::BitShift]);
+        assert_eq!(Opcode::ReadMemory { x: 0x0 }.quirks(), &[Quirk::ReadWriteIncrement, Quirk::MemoryBounds]);
+        assert!(Opcode::AddConstant { x: 0x0, value: 0x1 }.quirks().is_empty());
+    }
+
+    #[test]
+    fn is_supported_in() {
+        let clear = Opcode::ClearScreen;
+        assert!(clear.is_supported_in(QuirkProfile::Chip8));
+        assert!(clear.is_supported_in(QuirkProfile::SuperChip));
+        assert!(clear.is_supported_in(QuirkProfile::Modern));
+
+        let high_res = Opcode::HighRes;
+        assert!(!high_res.is_supported_in(QuirkProfile::Chip8));
+        assert!(high_res.is_supported_in(QuirkProfile::SuperChip));
+        assert!(high_res.is_supported_in(QuirkProfile::Modern));
+
+        let plane = Opcode::SelectPlane { planes: 0x3 };
+        assert!(!plane.is_supported_in(QuirkProfile::Chip8));
+        assert!(!plane.is_supported_in(QuirkProfile::SuperChip));
+        assert!(plane.is_supported_in(QuirkProfile::Modern));
+    }
 }
@@ -1,7 +1,46 @@
+use std::str::FromStr;
+
+use crate::chip8::Chip8Error;
+
+/// `Quirks` collects every behavior that differs between Chip-8 interpreters into a single
+/// value so it can be copied, compared and restored as one unit.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Quirks {
+    pub read_write_increment: ReadWriteIncrementQuirk,
+
+    pub bit_shift: BitShiftQuirk,
+
+    pub sprite_wrap: SpriteWrapQuirk,
+
+    pub logic: LogicQuirk,
+
+    pub memory_bounds: MemoryBoundsQuirk,
+
+    pub resolution_switch: ResolutionSwitchQuirk,
+
+    pub draw_mode: DrawModeQuirk,
+
+    pub collision_count: CollisionCountQuirk,
+}
+
+/// Names a single field of `Quirks`, e.g. to report which quirks a ROM depends on. See
+/// `Chip8::quirks_exercised`.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum Quirk {
+    ReadWriteIncrement,
+    BitShift,
+    SpriteWrap,
+    Logic,
+    MemoryBounds,
+    ResolutionSwitch,
+    DrawMode,
+    CollisionCount,
+}
+
 /// The original Chip-8 would increment `I` after executing `READ` or `WRITE`.
 ///
 /// Most modern games assume that `I` is _not_ incremented as that's what Super Chip-8 1.1 does.
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum ReadWriteIncrementQuirk {
     /// Do nothing to `I` after executing `READ` or `WRITE`
     InvariantIndex,
@@ -22,7 +61,7 @@ impl Default for ReadWriteIncrementQuirk {
 ///
 /// - Original Chip-8: SHL: `Vx = Vy << 1`, SHR: `Vx = Vy >> 1`
 /// - Super Chip-8: SHL: `Vx = Vx << 1`, SHR: `Vx >> 1`
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum BitShiftQuirk {
     ShiftX,
 
@@ -34,3 +73,206 @@ impl Default for BitShiftQuirk {
         BitShiftQuirk::ShiftX
     }
 }
+
+/// The original Chip-8 reset `VF` to 0 after executing `OR`, `AND` or `XOR`.
+///
+/// Later interpreters leave `VF` untouched, which is what most modern games expect.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum LogicQuirk {
+    /// Leave `VF` untouched after `OR`, `AND` and `XOR`
+    KeepVf,
+
+    /// Set `VF` to 0 after `OR`, `AND` and `XOR`
+    ResetVf
+}
+
+impl Default for LogicQuirk {
+    fn default() -> LogicQuirk {
+        LogicQuirk::KeepVf
+    }
+}
+
+/// What happens when `READ` or `WRITE` would access memory past the end of memory, e.g. `WRITE V5`
+/// with `I = 0xFFE` on a machine with 4KB of memory, or `JUMP addr,V0` would jump past it.
+///
+/// Either way `I` wraps within memory when `ReadWriteIncrementQuirk::IncrementIndex` moves it
+/// past the end of memory.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MemoryBoundsQuirk {
+    /// Wrap the access or jump around to the start of memory
+    Wrap,
+
+    /// Fail with `Chip8Error::MemoryOutOfBounds` without accessing any memory or jumping
+    Error
+}
+
+impl Default for MemoryBoundsQuirk {
+    fn default() -> MemoryBoundsQuirk {
+        MemoryBoundsQuirk::Wrap
+    }
+}
+
+/// What happens to the display when `HIGH` switches from low to high resolution.
+///
+/// - Original Chip-8: Only has a single resolution
+/// - Super Chip-8 1.1 and most modern interpreters: The display is cleared
+/// - Super Chip-8 1.0 on the HP48: Low resolution pixels are drawn as 2x2 blocks on the high
+///   resolution display, so the existing image persists. Some games rely on this.
+///
+/// Switching back to low resolution with `LOW` always clears the display.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ResolutionSwitchQuirk {
+    /// Clear the display
+    Clear,
+
+    /// Scale each low resolution pixel up to a 2x2 block, see `Gpu::with_scale_on_switch`
+    Scale
+}
+
+impl Default for ResolutionSwitchQuirk {
+    fn default() -> ResolutionSwitchQuirk {
+        ResolutionSwitchQuirk::Clear
+    }
+}
+
+/// How `DRAW` combines a sprite with the display.
+///
+/// Every standard interpreter uses XOR, but a few homebrew variants draw additively.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DrawModeQuirk {
+    /// Toggle each pixel the sprite covers and set `VF` when a pixel is turned off
+    Xor,
+
+    /// Set each pixel the sprite covers. Pixels are never turned off so `VF` is always 0
+    Or
+}
+
+impl Default for DrawModeQuirk {
+    fn default() -> DrawModeQuirk {
+        DrawModeQuirk::Xor
+    }
+}
+
+/// What `DRAW` stores in `VF`.
+///
+/// Super Chip-8 1.1 counts rows in high resolution mode: `VF` is the number of sprite rows that
+/// collided plus the number of rows clipped off the bottom of the screen. In low resolution mode,
+/// and on every other interpreter, `VF` is just set to 1 on any collision.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum CollisionCountQuirk {
+    /// Set `VF` to 1 if any pixel collided, otherwise 0
+    Flag,
+
+    /// In high resolution, set `VF` to the number of rows that collided or were clipped
+    CountRows
+}
+
+impl Default for CollisionCountQuirk {
+    fn default() -> CollisionCountQuirk {
+        CollisionCountQuirk::Flag
+    }
+}
+
+/// Interpreters disagree on what happens to sprites drawn past the edge of the screen. Each axis
+/// can either wrap around to the opposite edge or clip the pixels that fall off screen.
+///
+/// The original Chip-8 clips in both directions while many later interpreters wrap.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SpriteWrapQuirk {
+    /// Wrap pixels past the right edge back to the left edge, otherwise clip them
+    pub horizontal_wrap: bool,
+
+    /// Wrap pixels past the bottom edge back to the top edge, otherwise clip them
+    pub vertical_wrap: bool,
+}
+
+impl Default for SpriteWrapQuirk {
+    fn default() -> SpriteWrapQuirk {
+        SpriteWrapQuirk { horizontal_wrap: true, vertical_wrap: true }
+    }
+}
+
+/// Named sets of `Quirks` matching well known interpreters, e.g. for choosing quirks from the
+/// command line.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum QuirkProfile {
+    /// `Quirks::default()`, which is what most modern games expect
+    Modern,
+
+    /// The original Chip-8 interpreter on the COSMAC VIP
+    Chip8,
+
+    /// Super Chip-8 1.1
+    SuperChip,
+}
+
+impl QuirkProfile {
+    pub fn quirks(&self) -> Quirks {
+        let clip = SpriteWrapQuirk { horizontal_wrap: false, vertical_wrap: false };
+
+        match self {
+            QuirkProfile::Modern => Quirks::default(),
+            QuirkProfile::Chip8 => Quirks {
+                read_write_increment: ReadWriteIncrementQuirk::IncrementIndex,
+                bit_shift: BitShiftQuirk::ShiftYIntoX,
+                sprite_wrap: clip,
+                logic: LogicQuirk::ResetVf,
+                memory_bounds: MemoryBoundsQuirk::Wrap,
+                resolution_switch: ResolutionSwitchQuirk::Clear,
+                draw_mode: DrawModeQuirk::Xor,
+                collision_count: CollisionCountQuirk::Flag,
+            },
+            QuirkProfile::SuperChip => Quirks {
+                read_write_increment: ReadWriteIncrementQuirk::InvariantIndex,
+                bit_shift: BitShiftQuirk::ShiftX,
+                sprite_wrap: clip,
+                logic: LogicQuirk::KeepVf,
+                memory_bounds: MemoryBoundsQuirk::Wrap,
+                resolution_switch: ResolutionSwitchQuirk::Clear,
+                draw_mode: DrawModeQuirk::Xor,
+                collision_count: CollisionCountQuirk::CountRows,
+            },
+        }
+    }
+}
+
+impl Default for QuirkProfile {
+    fn default() -> QuirkProfile {
+        QuirkProfile::Modern
+    }
+}
+
+impl FromStr for QuirkProfile {
+    type Err = Chip8Error;
+
+    /// Parse a profile name: `modern`, `chip8` or `schip`
+    fn from_str(name: &str) -> Result<QuirkProfile, Chip8Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "modern" => Ok(QuirkProfile::Modern),
+            "chip8" => Ok(QuirkProfile::Chip8),
+            "schip" => Ok(QuirkProfile::SuperChip),
+            _ => Err(Chip8Error::ParseError(format!("unknown quirk profile: {}", name))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn quirk_profile_from_str() {
+        assert_eq!("modern".parse(), Ok(QuirkProfile::Modern));
+        assert_eq!("CHIP8".parse(), Ok(QuirkProfile::Chip8));
+        assert_eq!("schip".parse(), Ok(QuirkProfile::SuperChip));
+        assert_eq!(
+            "xochip".parse::<QuirkProfile>(),
+            Err(Chip8Error::ParseError("unknown quirk profile: xochip".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn modern_quirk_profile_is_the_default() {
+        assert_eq!(QuirkProfile::default().quirks(), Quirks::default());
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use crate::chip8::{Chip8, Opcode, Address, Chip8Result, Chip8Error};
+use crate::chip8::octo;
+
+/// `RomBuilder` assembles a list of opcodes into a ROM while keeping track of named labels,
+/// so jump targets don't need to be calculated by hand.
+///
+/// Labels that have already been defined can be looked up with `resolve`. Forward references
+/// can be made with `push_ref`, which is resolved when the ROM is built:
+///
+/// ```ignore
+/// let mut builder = RomBuilder::new();
+/// builder.push_ref("main", Opcode::Jump);
+/// builder.label("subroutine");
+/// builder.push(Opcode::Return);
+/// builder.label("main");
+/// builder.push(Opcode::CallSubroutine(builder.resolve("subroutine")));
+/// let rom = builder.build().unwrap();
+/// ```
+///
+/// Sprites and other data can be placed between opcodes with `data`, which labels the data so it
+/// can be referenced in the same way:
+///
+/// ```ignore
+/// let mut builder = RomBuilder::new();
+/// builder.push_ref("sprite", Opcode::IndexAddress);
+/// builder.push(Opcode::Draw { x: 0x0, y: 0x0, n: 0x2 });
+/// builder.data("sprite", &[0xF0, 0x90]);
+/// ```
+pub struct RomBuilder {
+    start_address: Address,
+    items: Vec<RomItem>,
+    labels: HashMap<String, Address>,
+}
+
+impl Default for RomBuilder {
+    fn default() -> RomBuilder {
+        RomBuilder::new()
+    }
+}
+
+enum RomItem {
+    Opcode(Opcode),
+    Reference { label: String, opcode: Box<dyn Fn(Address) -> Opcode> },
+    Data(Vec<u8>),
+}
+
+impl RomItem {
+    fn len(&self) -> usize {
+        match self {
+            RomItem::Opcode(_) | RomItem::Reference { .. } => 2,
+            RomItem::Data(bytes) => bytes.len(),
+        }
+    }
+}
+
+impl RomBuilder {
+    /// Returns a `RomBuilder` for a ROM loaded at `Chip8::PROGRAM_START`
+    pub fn new() -> RomBuilder {
+        RomBuilder {
+            start_address: Chip8::PROGRAM_START,
+            items: Vec::new(),
+            labels: HashMap::new(),
+        }
+    }
+
+    /// Build a ROM to be loaded at `address` instead, see `Chip8::with_start_address`
+    pub fn with_start_address(mut self, address: Address) -> Self {
+        self.start_address = address;
+        self
+    }
+
+    /// The address the next pushed opcode will be placed at
+    pub fn current_address(&self) -> Address {
+        let size: usize = self.items.iter().map(RomItem::len).sum();
+        self.start_address + size as Address
+    }
+
+    /// Name the address of the next pushed opcode
+    pub fn label(&mut self, name: &str) -> &mut Self {
+        let address = self.current_address();
+        self.labels.insert(name.to_string(), address);
+        self
+    }
+
+    /// Returns the address of a label that has already been defined.
+    ///
+    /// Panics if the label has not been defined yet. Use `push_ref` for forward references.
+    pub fn resolve(&self, name: &str) -> Address {
+        match self.labels.get(name) {
+            Some(address) => *address,
+            None => panic!("Label '{}' has not been defined", name),
+        }
+    }
+
+    pub fn push(&mut self, opcode: Opcode) -> &mut Self {
+        self.items.push(RomItem::Opcode(opcode));
+        self
+    }
+
+    /// Push an opcode that refers to `label`. `opcode` is called with the address of `label`
+    /// when the ROM is built, so the label may be defined after this call.
+    pub fn push_ref<F>(&mut self, label: &str, opcode: F) -> &mut Self
+        where F: Fn(Address) -> Opcode + 'static
+    {
+        self.items.push(RomItem::Reference { label: label.to_string(), opcode: Box::new(opcode) });
+        self
+    }
+
+    /// Name the address of `bytes` and place them at the current address.
+    ///
+    /// Data isn't padded, so an odd number of bytes leaves the next opcode unaligned.
+    pub fn data(&mut self, label: &str, bytes: &[u8]) -> &mut Self {
+        self.label(label);
+        self.items.push(RomItem::Data(bytes.to_vec()));
+        self
+    }
+
+    /// Assemble `source` written in the Octo dialect and place it at the current address.
+    ///
+    /// Octo labels (`: main`) become labels of this builder and may be referenced by `push_ref`,
+    /// or by Octo statements such as `jump main`. Only statements that map to a single opcode are
+    /// supported, so macros, `loop`, `begin`/`else`/`end` and data directives are rejected.
+    pub fn push_octo(&mut self, source: &str) -> Chip8Result<&mut Self> {
+        octo::assemble(source, self)?;
+        Ok(self)
+    }
+
+    /// Resolve all references and return the assembled ROM.
+    ///
+    /// Returns an error if a reference points to a label that was never defined.
+    pub fn build(&self) -> Chip8Result<Vec<u8>> {
+        let mut rom = Vec::new();
+        for item in &self.items {
+            match item {
+                RomItem::Opcode(opcode) => rom.extend(&opcode.to_bytes()),
+                RomItem::Reference { label, opcode } => {
+                    let address = self.labels.get(label)
+                        .ok_or_else(|| Chip8Error::ParseError(format!("undefined label '{}'", label)))?;
+
+                    rom.extend(&opcode(*address).to_bytes());
+                }
+                RomItem::Data(bytes) => rom.extend(bytes),
+            }
+        }
+
+        Ok(rom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn build_loop() {
+        let mut builder = RomBuilder::new();
+        builder.push(Opcode::LoadConstant { x: 0x0, value: 0x0 });
+        builder.label("loop");
+        builder.push(Opcode::AddConstant { x: 0x0, value: 0x1 });
+        builder.push(Opcode::Jump(builder.resolve("loop")));
+
+        let rom = builder.build().unwrap();
+
+        assert_eq!(rom, vec![0x60, 0x00, 0x70, 0x01, 0x12, 0x02]);
+    }
+
+    #[test]
+    pub fn build_forward_reference() {
+        let mut builder = RomBuilder::new();
+        builder.push_ref("main", Opcode::Jump);
+        builder.label("subroutine");
+        builder.push(Opcode::Return);
+        builder.label("main");
+        builder.push(Opcode::CallSubroutine(builder.resolve("subroutine")));
+
+        let rom = builder.build().unwrap();
+
+        assert_eq!(rom, vec![0x12, 0x04, 0x00, 0xEE, 0x22, 0x02]);
+    }
+
+    #[test]
+    pub fn build_with_start_address() {
+        let mut builder = RomBuilder::new().with_start_address(0x600);
+        builder.label("loop");
+        builder.push(Opcode::Jump(builder.resolve("loop")));
+
+        let rom = builder.build().unwrap();
+
+        assert_eq!(rom, vec![0x16, 0x00]);
+    }
+
+    #[test]
+    pub fn build_with_data() {
+        let mut builder = RomBuilder::new();
+        builder.push_ref("sprite", Opcode::IndexAddress);
+        builder.push_ref("main", Opcode::Jump);
+        builder.data("sprite", &[0xF0, 0x90, 0xF0]);
+        builder.label("main");
+        builder.push(Opcode::Return);
+
+        let rom = builder.build().unwrap();
+
+        assert_eq!(builder.resolve("sprite"), 0x204);
+        assert_eq!(builder.resolve("main"), 0x207);
+        assert_eq!(rom, vec![0xA2, 0x04, 0x12, 0x07, 0xF0, 0x90, 0xF0, 0x00, 0xEE]);
+    }
+
+    #[test]
+    pub fn build_undefined_label() {
+        let mut builder = RomBuilder::new();
+        builder.push_ref("missing", Opcode::Jump);
+
+        assert_eq!(builder.build(), Err(Chip8Error::ParseError("undefined label 'missing'".to_string())));
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn resolve_undefined_label_panics() {
+        let builder = RomBuilder::new();
+        builder.resolve("missing");
+    }
+}
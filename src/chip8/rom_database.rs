@@ -0,0 +1,82 @@
+use crate::chip8::{Chip8, Quirks, ReadWriteIncrementQuirk, BitShiftQuirk, SpriteWrapQuirk, LogicQuirk, MemoryBoundsQuirk, ResolutionSwitchQuirk, DrawModeQuirk, CollisionCountQuirk};
+
+/// A ROM that needs quirks other than `Quirks::default()`, identified by its size and CRC-32.
+struct KnownRom {
+    size: usize,
+    crc32: u32,
+    quirks: Quirks,
+}
+
+/// Quirks for well known ROMs, based on the community maintained
+/// [CHIP-8 database](https://github.com/chip-8/chip-8-database).
+const KNOWN_ROMS: [KnownRom; 1] = [
+    // Blitz [David Winter] draws buildings off the bottom of the screen and expects them to be clipped
+    KnownRom {
+        size: 391,
+        crc32: 0xD106_C808,
+        quirks: Quirks {
+            read_write_increment: ReadWriteIncrementQuirk::InvariantIndex,
+            bit_shift: BitShiftQuirk::ShiftX,
+            sprite_wrap: SpriteWrapQuirk { horizontal_wrap: true, vertical_wrap: false },
+            logic: LogicQuirk::KeepVf,
+            memory_bounds: MemoryBoundsQuirk::Wrap,
+            resolution_switch: ResolutionSwitchQuirk::Clear,
+            draw_mode: DrawModeQuirk::Xor,
+            collision_count: CollisionCountQuirk::Flag,
+        },
+    },
+];
+
+impl Chip8 {
+    /// Returns the quirks `rom` is known to need, or `Quirks::default()` if it isn't a known ROM.
+    pub fn detect_quirks(rom: &[u8]) -> Quirks {
+        let crc32 = crc32fast::hash(rom);
+
+        KNOWN_ROMS.iter()
+            .find(|known| known.size == rom.len() && known.crc32 == crc32)
+            .map(|known| known.quirks)
+            .unwrap_or_default()
+    }
+
+    /// Use the quirks the loaded ROM is known to need, see `detect_quirks`
+    pub fn with_quirks_from_rom_database(self) -> Self {
+        let quirks = Chip8::detect_quirks(self.rom());
+        self.with_quirks(quirks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLITZ: &[u8] = include_bytes!("../../roms/BLITZ");
+
+    #[test]
+    pub fn detect_quirks_for_known_rom() {
+        let quirks = Chip8::detect_quirks(BLITZ);
+
+        assert_eq!(quirks.sprite_wrap, SpriteWrapQuirk { horizontal_wrap: true, vertical_wrap: false });
+    }
+
+    #[test]
+    pub fn detect_quirks_falls_back_to_default() {
+        let pong = include_bytes!("../../roms/PONG");
+
+        assert_eq!(Chip8::detect_quirks(pong), Quirks::default());
+    }
+
+    #[test]
+    pub fn detect_quirks_requires_exact_match() {
+        let mut modified = BLITZ.to_vec();
+        modified[0] ^= 0xFF;
+
+        assert_eq!(Chip8::detect_quirks(&modified), Quirks::default());
+    }
+
+    #[test]
+    pub fn with_quirks_from_rom_database() {
+        let chip8 = Chip8::new_with_rom_slice(BLITZ).with_quirks_from_rom_database();
+
+        assert_eq!(chip8.quirks(), &Chip8::detect_quirks(BLITZ));
+    }
+}
@@ -0,0 +1,192 @@
+use std::convert::TryInto;
+use std::time::Duration;
+
+use crate::chip8::{Chip8Error, Chip8Result};
+
+/// Every save state starts with `MAGIC` followed by a single `VERSION` byte.
+pub const MAGIC: [u8; 4] = *b"CHP8";
+
+/// Bump this whenever the layout written by `Chip8::save_state` changes.
+pub const VERSION: u8 = 20;
+
+/// Appends big-endian encoded values to a save state.
+pub struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+
+        StateWriter { bytes }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn u128(&mut self, value: u128) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn duration(&mut self, value: Duration) {
+        self.u64(value.as_nanos() as u64);
+    }
+
+    /// Write `bytes` without a length prefix. Use for data with a known, fixed size.
+    pub fn fixed_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Write `bytes` prefixed with its length
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.fixed_bytes(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads values written by `StateWriter`, failing with `Chip8Error::InvalidSaveState` if the
+/// data is truncated or was written by an incompatible version.
+pub struct StateReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Chip8Result<StateReader<'a>> {
+        let mut reader = StateReader { bytes, position: 0 };
+
+        if reader.fixed_bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(Chip8Error::InvalidSaveState("not a chipper save state".to_string()));
+        }
+
+        let version = reader.u8()?;
+        if version != VERSION {
+            let message = format!("unsupported version {} (expected {})", version, VERSION);
+            return Err(Chip8Error::InvalidSaveState(message));
+        }
+
+        Ok(reader)
+    }
+
+    pub fn u8(&mut self) -> Chip8Result<u8> {
+        Ok(self.fixed_bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Chip8Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Chip8Result<u16> {
+        Ok(u16::from_be_bytes(self.fixed_bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Chip8Result<u32> {
+        Ok(u32::from_be_bytes(self.fixed_bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Chip8Result<u64> {
+        Ok(u64::from_be_bytes(self.fixed_bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn u128(&mut self) -> Chip8Result<u128> {
+        Ok(u128::from_be_bytes(self.fixed_bytes(16)?.try_into().unwrap()))
+    }
+
+    pub fn duration(&mut self) -> Chip8Result<Duration> {
+        Ok(Duration::from_nanos(self.u64()?))
+    }
+
+    pub fn fixed_bytes(&mut self, len: usize) -> Chip8Result<&'a [u8]> {
+        let end = self.position + len;
+        if end > self.bytes.len() {
+            return Err(Chip8Error::InvalidSaveState("unexpected end of data".to_string()));
+        }
+
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn bytes(&mut self) -> Chip8Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.fixed_bytes(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn round_trip() {
+        let mut writer = StateWriter::new();
+        writer.u8(0xAB);
+        writer.bool(true);
+        writer.u16(0x1234);
+        writer.u64(0x1234_5678_9ABC_DEF0);
+        writer.duration(Duration::from_millis(16));
+        writer.bytes(&[1, 2, 3]);
+        let bytes = writer.into_bytes();
+
+        let mut reader = StateReader::new(&bytes).unwrap();
+        assert_eq!(reader.u8(), Ok(0xAB));
+        assert_eq!(reader.bool(), Ok(true));
+        assert_eq!(reader.u16(), Ok(0x1234));
+        assert_eq!(reader.u64(), Ok(0x1234_5678_9ABC_DEF0));
+        assert_eq!(reader.duration(), Ok(Duration::from_millis(16)));
+        assert_eq!(reader.bytes(), Ok(&[1, 2, 3][..]));
+    }
+
+    #[test]
+    pub fn rejects_missing_magic() {
+        assert_eq!(
+            StateReader::new(b"NOPE\x01").err(),
+            Some(Chip8Error::InvalidSaveState("not a chipper save state".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn rejects_other_versions() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION + 1);
+
+        assert_eq!(
+            StateReader::new(&bytes).err(),
+            Some(Chip8Error::InvalidSaveState(format!("unsupported version {} (expected {})", VERSION + 1, VERSION)))
+        );
+    }
+
+    #[test]
+    pub fn rejects_truncated_data() {
+        let mut writer = StateWriter::new();
+        writer.u8(0x1);
+        let bytes = writer.into_bytes();
+
+        let mut reader = StateReader::new(&bytes).unwrap();
+        reader.u8().unwrap();
+
+        assert_eq!(reader.u16(), Err(Chip8Error::InvalidSaveState("unexpected end of data".to_string())));
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::path::Path;
+
+use png::{ColorType, Decoder, Transformations};
+
+use crate::chip8::{Chip8Result, Chip8Error};
+
+/// Convert the PNG at `path` into sprite bytes for `DRAW`, so sprites can be drawn in an image
+/// editor rather than written out in binary.
+///
+/// A pixel is filled when its brightness is at least `threshold`, so white on black looks the
+/// same as it will on the display. Pixels that are mostly transparent are always empty.
+///
+/// The image must be a multiple of 8 pixels wide, giving one byte for every 8 pixels of each row,
+/// and at most 15 pixels tall, or 16 for a SUPER-CHIP high resolution sprite. Images wider than
+/// 8 pixels can be drawn 8 pixels at a time, or as a 16x16 sprite in high resolution.
+pub fn sprite_from_image<P: AsRef<Path>>(path: P, threshold: u8) -> Chip8Result<Vec<u8>> {
+    let invalid = |e: png::DecodingError| Chip8Error::InvalidImage(e.to_string());
+
+    let mut decoder = Decoder::new(File::open(path)?);
+    decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().map_err(invalid)?;
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    if width == 0 || width % 8 != 0 || !(1..=16).contains(&height) {
+        return Err(Chip8Error::InvalidImage(format!(
+            "sprites must be a multiple of 8 pixels wide and 1 to 16 pixels tall, not {}x{}", width, height
+        )));
+    }
+
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut pixels).map_err(invalid)?;
+
+    let (color_type, _) = reader.output_color_type();
+    let samples = color_type.samples();
+    let filled = |pixel: &[u8]| {
+        let (brightness, alpha) = match color_type {
+            ColorType::Grayscale => (pixel[0], 0xFF),
+            ColorType::GrayscaleAlpha => (pixel[0], pixel[1]),
+            _ => {
+                // Perceived brightness, weighted towards green as in ITU-R BT.601
+                let luma = (299 * pixel[0] as u32 + 587 * pixel[1] as u32 + 114 * pixel[2] as u32) / 1000;
+                (luma as u8, if color_type == ColorType::RGBA { pixel[3] } else { 0xFF })
+            },
+        };
+
+        alpha >= 0x80 && brightness >= threshold
+    };
+
+    let row_size = reader.output_line_size(info.width);
+    let sprite = pixels.chunks(row_size)
+        .take(height)
+        .flat_map(|row| {
+            row[..width * samples].chunks(8 * samples).map(|byte_pixels| {
+                byte_pixels.chunks(samples)
+                    .fold(0, |byte, pixel| (byte << 1) | filled(pixel) as u8)
+            }).collect::<Vec<u8>>()
+        })
+        .collect();
+
+    Ok(sprite)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use png::{BitDepth, Encoder};
+
+    // Write a grayscale PNG where each string is a row and `#` is white
+    fn write_png(name: &str, rows: &[&str]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("chipper-{}-{}.png", name, std::process::id()));
+        let pixels: Vec<u8> = rows.iter()
+            .flat_map(|row| row.chars().map(|c| if c == '#' { 0xFF } else { 0x00 }))
+            .collect();
+
+        let mut encoder = Encoder::new(File::create(&path).unwrap(), rows[0].len() as u32, rows.len() as u32);
+        encoder.set_color(ColorType::Grayscale);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&pixels).unwrap();
+
+        path
+    }
+
+    #[test]
+    pub fn sprite_from_8x5_image() {
+        let path = write_png("sprite-zero", &[
+            "####....",
+            "#..#....",
+            "#..#....",
+            "#..#....",
+            "####....",
+        ]);
+
+        let sprite = sprite_from_image(&path, 0x80);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(sprite, Ok(vec![0xF0, 0x90, 0x90, 0x90, 0xF0]));
+    }
+
+    #[test]
+    pub fn sprite_from_multi_byte_image() {
+        let path = write_png("sprite-wide", &[
+            "#......##......##......#",
+            "........................",
+            "########........########",
+        ]);
+
+        let sprite = sprite_from_image(&path, 0x80);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(sprite, Ok(vec![
+            0x81, 0x81, 0x81,
+            0x00, 0x00, 0x00,
+            0xFF, 0x00, 0xFF,
+        ]));
+    }
+
+    #[test]
+    pub fn sprite_from_image_rejects_wrong_size() {
+        let path = write_png("sprite-too-wide", &["#........"]);
+
+        let sprite = sprite_from_image(&path, 0x80);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(sprite, Err(Chip8Error::InvalidImage(
+            "sprites must be a multiple of 8 pixels wide and 1 to 16 pixels tall, not 9x1".to_string()
+        )));
+
+        let rows = vec!["########"; 17];
+        let path = write_png("sprite-too-tall", &rows);
+
+        let sprite = sprite_from_image(&path, 0x80);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(sprite, Err(Chip8Error::InvalidImage(
+            "sprites must be a multiple of 8 pixels wide and 1 to 16 pixels tall, not 8x17".to_string()
+        )));
+    }
+}
@@ -0,0 +1,89 @@
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+use crate::chip8::{Chip8Error, Chip8Result};
+
+/// Every binary trace starts with `MAGIC`, followed by one `RECORD_SIZE` byte record per
+/// executed instruction: the cycle number (u64), `pc` (u16) and the opcode word (u16), all
+/// big-endian.
+pub const MAGIC: [u8; 4] = *b"C8TR";
+
+const RECORD_SIZE: usize = 12;
+
+/// A single executed instruction: `(cycle, pc, opcode)`
+pub type TraceRecord = (u64, u16, u16);
+
+/// Writes the records of a binary trace, see `Chip8::start_binary_trace`
+pub struct TraceWriter {
+    writer: Box<dyn Write>,
+}
+
+impl TraceWriter {
+    pub fn new(mut writer: Box<dyn Write>) -> Chip8Result<TraceWriter> {
+        writer.write_all(&MAGIC)?;
+        Ok(TraceWriter { writer })
+    }
+
+    pub fn record(&mut self, cycle: u64, pc: u16, opcode: u16) -> Chip8Result<()> {
+        let mut record = [0; RECORD_SIZE];
+        record[0..8].copy_from_slice(&cycle.to_be_bytes());
+        record[8..10].copy_from_slice(&pc.to_be_bytes());
+        record[10..12].copy_from_slice(&opcode.to_be_bytes());
+
+        self.writer.write_all(&record)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Chip8Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Parse a trace written by `Chip8::start_binary_trace`.
+///
+/// Returns `Chip8Error::ParseError` if `reader` isn't a binary trace or ends part way through
+/// a record.
+pub fn read_binary_trace<R: Read>(mut reader: R) -> Chip8Result<Vec<TraceRecord>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if !bytes.starts_with(&MAGIC) {
+        return Err(Chip8Error::ParseError("not a binary trace".to_string()));
+    }
+
+    let records = &bytes[MAGIC.len()..];
+    if records.len() % RECORD_SIZE != 0 {
+        return Err(Chip8Error::ParseError("binary trace ends part way through a record".to_string()));
+    }
+
+    let records = records.chunks(RECORD_SIZE)
+        .map(|record| (
+            u64::from_be_bytes(record[0..8].try_into().unwrap()),
+            u16::from_be_bytes(record[8..10].try_into().unwrap()),
+            u16::from_be_bytes(record[10..12].try_into().unwrap()),
+        ))
+        .collect();
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn read_binary_trace_rejects_other_data() {
+        assert_eq!(
+            read_binary_trace(&b"not a trace"[..]),
+            Err(Chip8Error::ParseError("not a binary trace".to_string()))
+        );
+
+        let mut truncated = MAGIC.to_vec();
+        truncated.extend_from_slice(&[0; RECORD_SIZE - 1]);
+        assert_eq!(
+            read_binary_trace(&truncated[..]),
+            Err(Chip8Error::ParseError("binary trace ends part way through a record".to_string()))
+        );
+    }
+}
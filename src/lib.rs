@@ -1,5 +1,13 @@
 mod chip8;
 mod ui;
 
-pub use self::chip8::Chip8;
-pub use self::ui::ChipperUI;
+pub use self::chip8::{Chip8, Chip8Output, Chip8Event, Beep, MemorySize, CustomOpcodeHandler, Opcode, OpcodeCategory, OpcodeInfo, AssemblyStyle, NumberFormat, RomBuilder, Quirks, ReadWriteIncrementQuirk, BitShiftQuirk, SpriteWrapQuirk, LogicQuirk, MemoryBoundsQuirk, ResolutionSwitchQuirk, DrawModeQuirk, CollisionCountQuirk, Quirk, QuirkProfile};
+pub use self::chip8::{DisplayBackend, BackendEvent, run_loop};
+pub use self::chip8::{Lockstep, LockstepInput, InputScript};
+pub use self::chip8::{analyze, ControlFlowGraph, BasicBlock, Edge, RomWarning};
+pub use self::chip8::{read_binary_trace, TraceRecord};
+pub use self::chip8::MemoryBus;
+pub use self::chip8::{Gpu, Resolution, DrawRows, DrawResult};
+#[cfg(feature = "sprite-import")]
+pub use self::chip8::sprite_from_image;
+pub use self::ui::{ChipperUI, RomLoader};
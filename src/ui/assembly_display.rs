@@ -1,9 +1,10 @@
 use std::cmp::max;
+use std::collections::HashSet;
 use ggez::{Context, GameResult};
-use ggez::graphics::{self, Text, DrawParam, DrawMode, FilterMode, Rect, Mesh, Color};
+use ggez::graphics::{self, DrawParam, DrawMode, FilterMode, Rect, Mesh, MeshBuilder, Color};
 
-use crate::chip8::Chip8;
-use crate::ui::{Chip8Display, Assets, Point2, Vector2};
+use crate::chip8::{Chip8, Opcode};
+use crate::ui::{Chip8Display, Assets, Point2, Vector2, TextCache};
 
 /// Display the currently executing opcodes of a `Chip8` within a
 /// 220x320 pixel window.
@@ -20,9 +21,18 @@ pub struct AssemblyDisplay {
     /// The end address of the memory slice we are currently viewing
     window_end_address: u16,
 
-    text: Vec<(Point2, Text)>,
+    text: Vec<(Point2, String)>,
+
+    /// The `Text` for each string in `text`, so lines that didn't change keep their `Text`
+    text_cache: TextCache,
 
     pc_highlight: Option<Mesh>,
+
+    /// A preview of the font glyph `I` will point at when the next instruction is `IndexFont`
+    font_preview: Option<Mesh>,
+
+    /// Mark addresses that are the target of a `JUMP` or `CALL` with a `*`
+    show_jump_targets: bool,
 }
 
 impl AssemblyDisplay {
@@ -30,16 +40,21 @@ impl AssemblyDisplay {
     pub const WIDTH: f32 = 22.0 * AssemblyDisplay::SCALE;
     pub const HEIGHT: f32 = 32.0 * AssemblyDisplay::SCALE;
 
-    const NUM_LINES: u16 = (AssemblyDisplay::HEIGHT / AssemblyDisplay::LINE_HEIGHT) as u16 - 1;
+    const NUM_LINES: u16 = ((AssemblyDisplay::HEIGHT / AssemblyDisplay::LINE_HEIGHT) as u16).saturating_sub(1);
 
     const LINE_HEIGHT: f32 = 1.2 * AssemblyDisplay::SCALE;
     const FONT_SIZE: f32 = 1.6 * AssemblyDisplay::SCALE;
     const PADDING_LEFT: f32 = 1.0 * AssemblyDisplay::SCALE;
 
+    const JUMP_TARGET_X_OFFSET: f32 = -0.8 * AssemblyDisplay::SCALE;
     const ADDRESS_X_OFFSET: f32 = 0.0 * AssemblyDisplay::SCALE;
     const OPCODE_X_OFFSET: f32 = 3.6 * AssemblyDisplay::SCALE;
     const OPCODE_ARG_X_OFFSET: f32 = 8.0 * AssemblyDisplay::SCALE;
 
+    const FONT_PREVIEW_PIXEL_SIZE: f32 = 0.5 * AssemblyDisplay::SCALE;
+    const FONT_PREVIEW_X_OFFSET: f32 = AssemblyDisplay::WIDTH - 6.0 * AssemblyDisplay::FONT_PREVIEW_PIXEL_SIZE;
+    const FONT_PREVIEW_Y_OFFSET: f32 = AssemblyDisplay::HEIGHT - 7.0 * AssemblyDisplay::FONT_PREVIEW_PIXEL_SIZE;
+
     pub fn new(x: f32, y: f32) -> AssemblyDisplay {
         AssemblyDisplay {
             x,
@@ -47,67 +62,177 @@ impl AssemblyDisplay {
             window_start_address: Chip8::PROGRAM_START,
             window_end_address: Chip8::PROGRAM_START + (AssemblyDisplay::NUM_LINES * 2),
             text: Vec::new(),
+            text_cache: TextCache::new(),
             pc_highlight: None,
+            font_preview: None,
+            show_jump_targets: false,
         }
     }
 
+    pub fn with_jump_targets(mut self, show_jump_targets: bool) -> Self {
+        self.show_jump_targets = show_jump_targets;
+        self
+    }
+
+    /// Move this display to `x`, `y`. Takes effect on the next `refresh`.
+    pub fn set_position(&mut self, x: f32, y: f32) {
+        self.x = x;
+        self.y = y;
+    }
+
     pub fn update(&mut self, ctx: &mut Context, assets: &Assets, chip8: &Chip8) -> GameResult<()> {
         // If the window is not viewing the current instruction we should shift the window
         // and re-generate the text.
-        if self.text.is_empty() || chip8.pc < self.window_start_address || chip8.pc > self.window_end_address - 1 {
+        if self.text.is_empty() || chip8.pc < self.window_start_address || chip8.pc >= self.window_end_address {
             self.refresh(assets, chip8);
         }
 
-        let pc_window_index = (chip8.pc - self.window_start_address) / 2;
-        let pc_pos = Point2::new(self.x + AssemblyDisplay::PADDING_LEFT, self.y + pc_window_index as f32 * AssemblyDisplay::LINE_HEIGHT);
-        let rect = Rect::new(pc_pos.x, pc_pos.y, AssemblyDisplay::WIDTH, AssemblyDisplay::LINE_HEIGHT + 4.0);
-        let rect = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, Color::from_rgb(0xFF, 0x00, 0x00))?;
-        self.pc_highlight = Some(rect);
+        // `pc` can be before the window if it's before the start of the ROM, see `window`
+        self.pc_highlight = match chip8.pc.checked_sub(self.window_start_address) {
+            Some(pc_offset) => {
+                let pc_window_index = pc_offset / 2;
+                let pc_pos = Point2::new(self.x + AssemblyDisplay::PADDING_LEFT, self.y + pc_window_index as f32 * AssemblyDisplay::LINE_HEIGHT);
+                let rect = Rect::new(pc_pos.x, pc_pos.y, AssemblyDisplay::WIDTH, AssemblyDisplay::LINE_HEIGHT + 4.0);
+                Some(Mesh::new_rectangle(ctx, DrawMode::fill(), rect, Color::from_rgb(0xFF, 0x00, 0x00))?)
+            },
+            None => None,
+        };
+
+        self.font_preview = match chip8.peek_next_opcode() {
+            Ok(Opcode::IndexFont { x }) => AssemblyDisplay::font_preview(ctx, chip8, chip8.v[x as usize], self.x, self.y)?,
+            _ => None,
+        };
 
         Ok(())
     }
 
+    // Draw the glyph for `digit` in the bottom right corner, returns `None` if the glyph is empty
+    fn font_preview(ctx: &mut Context, chip8: &Chip8, digit: u8, x: f32, y: f32) -> GameResult<Option<Mesh>> {
+        let pixel_size = AssemblyDisplay::FONT_PREVIEW_PIXEL_SIZE;
+        let origin = Point2::new(x + AssemblyDisplay::FONT_PREVIEW_X_OFFSET, y + AssemblyDisplay::FONT_PREVIEW_Y_OFFSET);
+
+        let mut builder = MeshBuilder::new();
+        let mut any_filled = false;
+        for (row, pixels) in chip8.font_glyph(digit).iter().enumerate() {
+            for (col, pixel) in pixels.iter().enumerate() {
+                if *pixel != 0 {
+                    let rect = Rect::new(
+                        origin.x + col as f32 * pixel_size,
+                        origin.y + row as f32 * pixel_size,
+                        pixel_size,
+                        pixel_size
+                    );
+                    builder.rectangle(DrawMode::fill(), rect, graphics::WHITE);
+                    any_filled = true;
+                }
+            }
+        }
+
+        if any_filled {
+            Ok(Some(builder.build(ctx)?))
+        } else {
+            Ok(None)
+        }
+    }
+
     pub fn refresh(&mut self, assets: &Assets, chip8: &Chip8) {
-        self.window_start_address = max(Chip8::PROGRAM_START, chip8.pc - 2);
-        self.window_end_address = chip8.pc + (AssemblyDisplay::NUM_LINES * 2);
+        let (window_start_address, window_end_address) = AssemblyDisplay::window(chip8);
+        self.window_start_address = window_start_address;
+        self.window_end_address = window_end_address;
 
         self.text.clear();
+        self.text_cache.start_frame();
 
         let opcodes = chip8.opcodes(self.window_start_address, self.window_end_address);
+        let jump_targets = if self.show_jump_targets {
+            chip8.jump_targets(self.window_start_address, self.window_end_address)
+        } else {
+            HashSet::new()
+        };
+
         for (i, (address, opcode)) in opcodes.iter().enumerate() {
             let origin = Point2::new(
                 self.x + AssemblyDisplay::PADDING_LEFT,
                 self.y + ((i as f32) * AssemblyDisplay::LINE_HEIGHT)
             );
 
+            if jump_targets.contains(address) {
+                let marker_pos = origin + Vector2::new(AssemblyDisplay::JUMP_TARGET_X_OFFSET, 0.0);
+                self.push_text(assets, marker_pos, "*".to_string());
+            }
+
             let address_pos = origin + Vector2::new(AssemblyDisplay::ADDRESS_X_OFFSET, 0.0);
-            let address_text = format!("{:X}", address);
-            let address_text = Text::new((address_text, assets.debug_font, AssemblyDisplay::FONT_SIZE));
-            self.text.push((address_pos, address_text));
+            self.push_text(assets, address_pos, format!("{:X}", address));
 
             let opcode_pos = address_pos + Vector2::new(AssemblyDisplay::OPCODE_X_OFFSET, 0.0);
-            let opcode_text = opcode.to_assembly_name();
-            let opcode_text = Text::new((opcode_text, assets.debug_font, AssemblyDisplay::FONT_SIZE));
-            self.text.push((opcode_pos, opcode_text));
+            self.push_text(assets, opcode_pos, opcode.to_assembly_name().to_string());
 
             let opcode_arg_pos = opcode_pos + Vector2::new(AssemblyDisplay::OPCODE_ARG_X_OFFSET, 0.0);
-            let opcode_arg_text = opcode.to_assembly_args().unwrap_or(String::new());
-            let opcode_arg_text = Text::new((opcode_arg_text, assets.debug_font, AssemblyDisplay::FONT_SIZE));
-            self.text.push((opcode_arg_pos, opcode_arg_text));
+            self.push_text(assets, opcode_arg_pos, opcode.to_assembly_args().unwrap_or(String::new()));
         }
     }
 
+    // The `(start, end)` addresses to show: the instruction before `pc` and as many as fit after
+    // it. The window never starts before the ROM, even if `pc` does.
+    fn window(chip8: &Chip8) -> (u16, u16) {
+        let start = max(chip8.start_address(), chip8.pc.saturating_sub(2));
+        let end = max(start, chip8.pc).saturating_add(AssemblyDisplay::NUM_LINES * 2);
+        (start, end)
+    }
+
+    fn push_text(&mut self, assets: &Assets, position: Point2, text: String) {
+        self.text_cache.insert(&text, assets.debug_font, AssemblyDisplay::FONT_SIZE);
+        self.text.push((position, text));
+    }
+
     pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
         if let Some(pc_highlight) = &self.pc_highlight {
             graphics::draw(ctx, pc_highlight, DrawParam::default())?;
         }
 
         for (position, text) in &self.text {
-            graphics::queue_text(ctx, text, *position, Some(graphics::WHITE));
+            if let Some(text) = self.text_cache.get(text) {
+                graphics::queue_text(ctx, text, *position, Some(graphics::WHITE));
+            }
         }
 
         graphics::draw_queued_text(ctx, DrawParam::default(), None, FilterMode::Nearest)?;
 
+        if let Some(font_preview) = &self.font_preview {
+            graphics::draw(ctx, font_preview, DrawParam::default())?;
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn window_follows_pc() {
+        let mut chip8 = Chip8::new();
+        chip8.pc = 0x210;
+
+        assert_eq!(AssemblyDisplay::window(&chip8), (0x20E, 0x210 + AssemblyDisplay::NUM_LINES * 2));
+    }
+
+    #[test]
+    pub fn window_with_pc_before_rom() {
+        let chip8 = Chip8::empty();
+        assert_eq!(chip8.pc, 0);
+
+        let (start, end) = AssemblyDisplay::window(&chip8);
+        assert_eq!(start, Chip8::PROGRAM_START);
+        assert_eq!(end, Chip8::PROGRAM_START + AssemblyDisplay::NUM_LINES * 2);
+    }
+
+    #[test]
+    pub fn window_at_end_of_memory() {
+        let mut chip8 = Chip8::new();
+        chip8.pc = 0xFFFE;
+
+        assert_eq!(AssemblyDisplay::window(&chip8), (0xFFFC, 0xFFFF));
+    }
+}
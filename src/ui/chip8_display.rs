@@ -5,20 +5,22 @@ use crate::chip8::{Chip8, Gpu};
 use crate::ui::{Point2, Vector2};
 
 
-/// Displays a Chip8 device in a 640x320 area.
+/// Displays a Chip8 device, scaled to fit the area it's given.
 pub struct Chip8Display {
-    /// The horizontal position of this display relative to the main window
-    x: f32,
-
-    /// The vertical position of this display relative to the main window
-    y: f32,
+    /// The part of the main window this display may fill. The image is letterboxed within it,
+    /// see `Chip8Display::letterbox`
+    area: Rect,
 
     /// `display_image` holds the texture derived from the Chip-8 graphics memory.
     ///
-    /// We need to refresh `display_image` whenever `Chip8` executes `Opcode::Draw`.
+    /// We need to refresh `display_image` whenever `Chip8::needs_redraw` is set.
     /// Otherwise we can just keep rendering this texture until something changes.
     display_image: Image,
 
+    /// The RGBA pixels `display_image` is built from, reused so redrawing doesn't allocate them.
+    /// ggez 0.5 can't update an `Image` in place, so the `Image` itself is still recreated.
+    frame_buffer: Vec<u8>,
+
     /// `border` is the coloured border surrounding the game area
     border: Mesh,
 }
@@ -28,30 +30,32 @@ impl Chip8Display {
     pub const WIDTH: f32 = 64.0 * Chip8Display::SCALE;
     pub const HEIGHT: f32 = 32.0 * Chip8Display::SCALE;
 
-    pub fn new(ctx: &mut Context, chip8: &Chip8, x: f32, y: f32) -> Chip8Display {
-        let display_image = Chip8Display::generate_display_image(ctx, chip8);
-
-        let border_thickness = 1.0;
-        let border = Rect::new(
-            x - border_thickness,
-            y - border_thickness,
-            Chip8Display::WIDTH + border_thickness,
-            Chip8Display::HEIGHT + border_thickness
-        );
-        let border = Mesh::new_rectangle(ctx, DrawMode::stroke(border_thickness), border, graphics::WHITE)
-            .expect("Failed to construct border mesh");
+    pub fn new(ctx: &mut Context, chip8: &Chip8, area: Rect) -> Chip8Display {
+        let mut frame_buffer = Vec::new();
+        let display_image = Chip8Display::generate_display_image(ctx, chip8, &mut frame_buffer);
+        let border = Chip8Display::generate_border(ctx, area);
 
-        Chip8Display { x, y, display_image, border }
+        Chip8Display { area, display_image, frame_buffer, border }
     }
 
     pub fn update(&mut self, ctx: &mut Context, chip8: &Chip8) {
-        self.display_image = Chip8Display::generate_display_image(ctx, chip8);
+        self.display_image = Chip8Display::generate_display_image(ctx, chip8, &mut self.frame_buffer);
+    }
+
+    /// Fill `area` instead, e.g. after the window is resized
+    pub fn resize(&mut self, ctx: &mut Context, area: Rect) {
+        self.area = area;
+        self.border = Chip8Display::generate_border(ctx, area);
     }
 
     pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        // The scale is usually a non-integer, and high resolution images have more pixels to fit
+        // into the same area
+        let viewport = Chip8Display::letterbox(self.area);
+        let scale = viewport.w / self.display_image.width() as f32;
         let draw_params = DrawParam::default()
-            .scale(Vector2::new(Chip8Display::SCALE, Chip8Display::SCALE))
-            .dest(Point2::new(self.x, self.y));
+            .scale(Vector2::new(scale, scale))
+            .dest(Point2::new(viewport.x, viewport.y));
         graphics::draw(ctx, &self.display_image, draw_params)?;
 
         graphics::draw(ctx, &self.border, DrawParam::default())?;
@@ -59,10 +63,42 @@ impl Chip8Display {
         Ok(())
     }
 
-    fn generate_display_image(ctx: &mut Context, chip8: &Chip8) -> Image {
-        let frame_buffer = chip8.gpu.to_rgba(Gpu::BLACK, Gpu::WHITE);
+    /// The largest 2:1 rectangle that fits in `area`, centered so any leftover space shows as
+    /// black bars at the sides or at the top and bottom.
+    fn letterbox(area: Rect) -> Rect {
+        let width = area.w.min(area.h * 2.0).max(0.0);
+        let height = width / 2.0;
+
+        Rect::new(
+            area.x + (area.w - width) / 2.0,
+            area.y + (area.h - height) / 2.0,
+            width,
+            height,
+        )
+    }
 
-        let mut image = Image::from_rgba8(ctx, 64, 32, &frame_buffer)
+    fn generate_border(ctx: &mut Context, area: Rect) -> Mesh {
+        let viewport = Chip8Display::letterbox(area);
+        let border_thickness = 1.0;
+        let border = Rect::new(
+            viewport.x - border_thickness,
+            viewport.y - border_thickness,
+            viewport.w + border_thickness,
+            viewport.h + border_thickness
+        );
+
+        Mesh::new_rectangle(ctx, DrawMode::stroke(border_thickness), border, graphics::WHITE)
+            .expect("Failed to construct border mesh")
+    }
+
+    fn generate_display_image(ctx: &mut Context, chip8: &Chip8, frame_buffer: &mut Vec<u8>) -> Image {
+        let (width, height) = chip8.gpu.presented_size();
+
+        // Only reallocates when the resolution changes
+        frame_buffer.resize(width * height * 4, 0);
+        chip8.gpu.to_rgba_into(frame_buffer, Gpu::BLACK, Gpu::WHITE);
+
+        let mut image = Image::from_rgba8(ctx, width as u16, height as u16, frame_buffer)
             .expect("Failed to generate frame buffer");
 
         image.set_filter(FilterMode::Nearest);
@@ -70,3 +106,20 @@ impl Chip8Display {
         image
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn letterbox_centers_display_in_wide_area() {
+        let viewport = Chip8Display::letterbox(Rect::new(100.0, 0.0, 1000.0, 300.0));
+        assert_eq!(viewport, Rect::new(300.0, 0.0, 600.0, 300.0));
+    }
+
+    #[test]
+    pub fn letterbox_centers_display_in_tall_area() {
+        let viewport = Chip8Display::letterbox(Rect::new(0.0, 0.0, 640.0, 480.0));
+        assert_eq!(viewport, Rect::new(0.0, 80.0, 640.0, 320.0));
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::{self, Context};
-use std::fs;
+use std::path::Path;
 use std::thread;
+use std::time::{Duration, Instant};
 use ggez::{self, ContextBuilder, GameResult};
 use ggez::conf::{WindowSetup, WindowMode};
 use ggez::event::{self, EventHandler};
@@ -10,53 +11,109 @@ use ggez::timer;
 use tinyfiledialogs;
 
 use crate::chip8::{Chip8, Chip8Output};
-use crate::ui::{Assets, AssemblyDisplay, Chip8Display, HelpDisplay, RegisterDisplay};
+use crate::ui::{Assets, AssemblyDisplay, Chip8Display, HelpDisplay, KeypadDisplay, RegisterDisplay};
+
+/// Builds the `Chip8` for a ROM chosen with F2/F3, see `ChipperUI::run_with_rom_loader`
+pub type RomLoader = dyn Fn(&Path) -> anyhow::Result<Chip8>;
 
 pub struct ChipperUI {
     chip8: Chip8,
+
+    /// Builds the machine for ROMs loaded from the dialog
+    rom_loader: Box<RomLoader>,
+
     assets: Assets,
     help_display: HelpDisplay,
+    keypad_display: KeypadDisplay,
     register_display: RegisterDisplay,
     chip8_display: Chip8Display,
     assembly_window: AssemblyDisplay,
+
+    /// When the current frame started, used to pace rendering to `FRAME_TIME`
+    frame_start: Instant,
 }
 
 impl ChipperUI {
     const WIDTH: f32 = RegisterDisplay::WIDTH + Chip8Display::WIDTH + AssemblyDisplay::WIDTH;
     const HEIGHT: f32 = Chip8Display::HEIGHT;
 
+    /// The number of instructions Shift+F6 steps
+    const MULTI_STEP: u32 = 10;
+
+    /// Render at 60fps no matter how fast the Chip-8 runs
+    const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
     pub fn run() -> anyhow::Result<()> {
+        ChipperUI::run_with_chip8(Chip8::new_with_default_rom())
+    }
+
+    /// Run the UI starting with `chip8` instead of the default ROM
+    pub fn run_with_chip8(chip8: Chip8) -> anyhow::Result<()> {
+        ChipperUI::run_with_rom_loader(chip8, Box::new(ChipperUI::load_rom))
+    }
+
+    /// Like `run_with_chip8` but ROMs loaded from the dialog are built by `rom_loader`, e.g. to
+    /// keep the clock speed and quirks given on the command line
+    pub fn run_with_rom_loader(chip8: Chip8, rom_loader: Box<RomLoader>) -> anyhow::Result<()> {
         // Make a Context.
         let (mut ctx, mut event_loop) = ContextBuilder::new("chipper", "Jake Woods")
             .window_setup(WindowSetup::default().title("Chipper"))
-            .window_mode(WindowMode::default().dimensions(ChipperUI::WIDTH, ChipperUI::HEIGHT))
+            .window_mode(WindowMode::default().dimensions(ChipperUI::WIDTH, ChipperUI::HEIGHT).resizable(true))
             .build()
             .context("Could not create ggez context!")?;
 
-        let mut chipper_ui = ChipperUI::new(&mut ctx);
+        let mut chipper_ui = ChipperUI::new_with_chip8(&mut ctx, chip8);
+        chipper_ui.rom_loader = rom_loader;
 
         event::run(&mut ctx, &mut event_loop, &mut chipper_ui)
             .context("Event loop error")
     }
 
     pub fn new(ctx: &mut ggez::Context) -> ChipperUI {
+        ChipperUI::new_with_chip8(ctx, Chip8::new_with_default_rom())
+    }
+
+    pub fn new_with_chip8(ctx: &mut ggez::Context, chip8: Chip8) -> ChipperUI {
+        let chip8 = ChipperUI::prepare_chip8(chip8);
         let assets = Assets::load(ctx);
-        let chip8 = Chip8::new_with_default_rom();
         let help_display = HelpDisplay::new(&assets, 20.0, 0.0);
+        // The first lines of the help text are short enough to fit the keypad beside them
+        let keypad_display = KeypadDisplay::new(
+            &assets,
+            RegisterDisplay::WIDTH - KeypadDisplay::WIDTH - 20.0,
+            HelpDisplay::LINE_HEIGHT
+        );
         let register_display = RegisterDisplay::new(20.0, HelpDisplay::HEIGHT);
-        let chip8_display = Chip8Display::new(ctx, &chip8, RegisterDisplay::WIDTH, 0.0);
-        let assembly_window = AssemblyDisplay::new(RegisterDisplay::WIDTH + Chip8Display::WIDTH, 0.0);
+        let chip8_display = Chip8Display::new(ctx, &chip8, ChipperUI::display_area(ChipperUI::WIDTH, ChipperUI::HEIGHT));
+        let assembly_window = AssemblyDisplay::new(RegisterDisplay::WIDTH + Chip8Display::WIDTH, 0.0)
+            .with_jump_targets(true);
 
         ChipperUI {
             assets,
             chip8,
+            rom_loader: Box::new(ChipperUI::load_rom),
             help_display,
+            keypad_display,
             register_display,
             chip8_display,
-            assembly_window
+            assembly_window,
+            frame_start: Instant::now(),
         }
     }
 
+    // We only render once per frame, so show complete frames rather than whatever was drawn
+    // so far. Otherwise sprites that are erased and redrawn every frame flicker.
+    fn prepare_chip8(mut chip8: Chip8) -> Chip8 {
+        chip8.gpu.set_present_on_vblank(true);
+        chip8
+    }
+
+    /// The default `RomLoader`, which picks the quirks and memory size from the ROM's extension
+    fn load_rom(path: &Path) -> anyhow::Result<Chip8> {
+        let chip8 = Chip8::from_file(path, None, None)?;
+        Ok(chip8.with_input_buffering(true))
+    }
+
     fn load_rom_from_dialog(&mut self) -> anyhow::Result<()> {
         let current_dir = std::env::current_dir()
             .ok()
@@ -64,77 +121,200 @@ impl ChipperUI {
             .unwrap_or(String::new().into());
 
         if let Some(file_path) = tinyfiledialogs::open_file_dialog("Choose a Chip 8 ROM", &current_dir, None) {
-            let rom = fs::read(&file_path)
-                .with_context(|| format!("Failed to read ROM from path: {}", file_path))?;
+            let chip8 = (self.rom_loader)(Path::new(&file_path))
+                .with_context(|| format!("Failed to load ROM from path: {}", file_path))?;
+            self.chip8 = ChipperUI::prepare_chip8(chip8);
+            self.assembly_window.refresh(&self.assets, &self.chip8);
+        }
 
-            self.chip8 = Chip8::new_with_rom(rom);
+        Ok(())
+    }
+
+    fn save_state_to_dialog(&mut self) -> anyhow::Result<()> {
+        if let Some(file_path) = tinyfiledialogs::save_file_dialog("Save Chip 8 State", "chipper.state") {
+            self.chip8.save_state_to_file(&file_path)
+                .with_context(|| format!("Failed to save state to path: {}", file_path))?;
+        }
+
+        Ok(())
+    }
+
+    fn load_state_from_dialog(&mut self) -> anyhow::Result<()> {
+        if let Some(file_path) = tinyfiledialogs::open_file_dialog("Load Chip 8 State", "chipper.state", None) {
+            let chip8 = Chip8::load_state_from_file(&file_path)
+                .with_context(|| format!("Failed to load state from path: {}", file_path))?;
+            self.chip8 = ChipperUI::prepare_chip8(chip8);
             self.assembly_window.refresh(&self.assets, &self.chip8);
         }
 
         Ok(())
     }
 
+    /// Start recording to a GIF chosen from a dialog, or finish the recording in progress
+    #[cfg(feature = "gif-recording")]
+    fn toggle_gif_recording(&mut self) -> anyhow::Result<()> {
+        if self.chip8.is_recording_gif() {
+            self.chip8.stop_recording_gif()
+                .context("Failed to finish GIF recording")?;
+        } else if let Some(file_path) = tinyfiledialogs::save_file_dialog("Record GIF", "chipper.gif") {
+            self.chip8.start_recording_gif(&file_path)
+                .with_context(|| format!("Failed to record GIF to path: {}", file_path))?;
+        }
+
+        Ok(())
+    }
+
+    // Stepping through the program should show every change, not just complete frames
+    fn refresh_chip8_after_step(&mut self, ctx: &mut ggez::Context, chip8_output: Chip8Output) -> GameResult<()> {
+        self.chip8.gpu.present();
+        self.refresh_chip8(ctx, chip8_output)
+    }
+
     fn refresh_chip8(&mut self, ctx: &mut ggez::Context, chip8_output: Chip8Output) -> GameResult<()> {
+        // Self-modifying programs can change the code we're displaying without moving the PC
+        if self.chip8.code_was_modified() {
+            self.assembly_window.refresh(&self.assets, &self.chip8);
+        }
+
         if chip8_output == Chip8Output::Tick || chip8_output == Chip8Output::Redraw {
             self.register_display.update(&self.assets, &self.chip8)?;
             self.assembly_window.update(ctx, &self.assets, &self.chip8)?;
         }
 
-        if chip8_output == Chip8Output::Redraw {
-            self.chip8_display.update(ctx, &self.chip8)
-        }
-
         Ok(())
     }
 
+    /// The space left for the Chip-8 display in a `width` by `height` window, between the
+    /// registers on the left and the assembly on the right
+    fn display_area(width: f32, height: f32) -> Rect {
+        let side_panels = RegisterDisplay::WIDTH + AssemblyDisplay::WIDTH;
+        Rect::new(RegisterDisplay::WIDTH, 0.0, (width - side_panels).max(0.0), height)
+    }
+
+    /// The Chip-8 key mapped to `keycode`, laid out so the left of the keyboard matches the keypad:
+    ///
+    /// ```text
+    /// 1 2 3 4    1 2 3 C
+    /// Q W E R    4 5 6 D
+    /// A S D F    7 8 9 E
+    /// Z X C V    A 0 B F
+    /// ```
+    fn keypad_key(keycode: KeyCode) -> Option<u8> {
+        match keycode {
+            KeyCode::Key1 => Some(0x1),
+            KeyCode::Key2 => Some(0x2),
+            KeyCode::Key3 => Some(0x3),
+            KeyCode::Key4 => Some(0xC),
+
+            KeyCode::Q => Some(0x4),
+            KeyCode::W => Some(0x5),
+            KeyCode::E => Some(0x6),
+            KeyCode::R => Some(0xD),
+
+            KeyCode::A => Some(0x7),
+            KeyCode::S => Some(0x8),
+            KeyCode::D => Some(0x9),
+            KeyCode::F => Some(0xE),
+
+            KeyCode::Z => Some(0xA),
+            KeyCode::X => Some(0x0),
+            KeyCode::C => Some(0xB),
+            KeyCode::V => Some(0xF),
+
+            _ => None,
+        }
+    }
+
+    /// Press the Chip-8 key mapped to `keycode`, if any. Auto-repeat isn't a new press, so only
+    /// the first key down reaches the keypad.
+    fn keypad_key_down(chip8: &mut Chip8, keycode: KeyCode, repeat: bool) {
+        if let Some(key) = ChipperUI::keypad_key(keycode) {
+            if !repeat {
+                chip8.press_key(key);
+            }
+        }
+    }
 }
 
 impl EventHandler for ChipperUI {
-    fn resize_event(&mut self, ctx: &mut ggez::Context, _width: f32, _height: f32) {
-        graphics::set_screen_coordinates(ctx, Rect::new(0.0, 0.0, ChipperUI::WIDTH, ChipperUI::HEIGHT))
+    fn resize_event(&mut self, ctx: &mut ggez::Context, width: f32, height: f32) {
+        // Draw in window pixels rather than stretching the UI, the side panels keep their size
+        // and the Chip-8 display scales to fit the rest
+        graphics::set_screen_coordinates(ctx, Rect::new(0.0, 0.0, width, height))
             .expect("Failed to set screen coordinates");
+
+        let display_area = ChipperUI::display_area(width, height);
+        self.chip8_display.resize(ctx, display_area);
+        self.assembly_window.set_position(display_area.right(), 0.0);
+        self.assembly_window.refresh(&self.assets, &self.chip8);
     }
 
-    fn key_down_event(&mut self, ctx: &mut ggez::Context, keycode: KeyCode, keymods: KeyMods, _repeat: bool) {
+    fn key_down_event(&mut self, ctx: &mut ggez::Context, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
         match keycode {
-            KeyCode::F2 => self.load_rom_from_dialog().expect("Failed to load ROM"),
+            // Unreadable or oversized ROMs are expected so don't crash, the current ROM keeps running
+            KeyCode::F2 => {
+                if let Err(e) = self.load_rom_from_dialog() {
+                    println!("{:#}", e);
+                }
+            },
             KeyCode::F3 => {
-                self.load_rom_from_dialog().expect("Failed to load ROM");
+                if let Err(e) = self.load_rom_from_dialog() {
+                    println!("{:#}", e);
+                }
                 self.chip8.debug_mode = true;
             }
-            KeyCode::F5 => self.chip8.debug_mode = !self.chip8.debug_mode,
+            KeyCode::F5 => self.chip8.set_paused(!self.chip8.is_paused()),
             KeyCode::F6 => {
-                let chip8_output = self.chip8.step()
-                    .expect("Failed to step chip8");
+                let chip8_output = if keymods.contains(KeyMods::SHIFT) {
+                    self.chip8.step_n(ChipperUI::MULTI_STEP)
+                } else {
+                    self.chip8.step()
+                }.expect("Failed to step chip8");
 
-                self.refresh_chip8(ctx, chip8_output)
+                self.refresh_chip8_after_step(ctx, chip8_output)
                     .expect("Failed to refresh chip8");
             },
+            KeyCode::F10 => {
+                // Saving can fail for reasons outside our control (e.g. a read-only directory)
+                if let Err(e) = self.save_state_to_dialog() {
+                    println!("{:#}", e);
+                }
+            },
+            #[cfg(feature = "gif-recording")]
+            KeyCode::F12 => {
+                if let Err(e) = self.toggle_gif_recording() {
+                    println!("{:#}", e);
+                }
+            },
+            KeyCode::F11 => {
+                // Incompatible save states are expected (e.g. from older versions) so don't crash
+                if let Err(e) = self.load_state_from_dialog() {
+                    println!("{:#}", e);
+                }
 
+                self.refresh_chip8(ctx, Chip8Output::Redraw)
+                    .expect("Failed to refresh chip8");
+            },
+            KeyCode::F7 => {
+                let chip8_output = self.chip8.step_over()
+                    .expect("Failed to step over chip8");
 
-            KeyCode::Key1 => self.chip8.press_key(0x1),
-            KeyCode::Key2 => self.chip8.press_key(0x2),
-            KeyCode::Key3 => self.chip8.press_key(0x3),
-            KeyCode::Key4 => self.chip8.press_key(0xC),
-
-            KeyCode::Q => self.chip8.press_key(0x4),
-            KeyCode::W => self.chip8.press_key(0x5),
-            KeyCode::E => self.chip8.press_key(0x6),
-            KeyCode::R => self.chip8.press_key(0xD),
-
-            KeyCode::A => self.chip8.press_key(0x7),
-            KeyCode::S => self.chip8.press_key(0x8),
-            KeyCode::D => self.chip8.press_key(0x9),
-            KeyCode::F => self.chip8.press_key(0xE),
+                self.refresh_chip8_after_step(ctx, chip8_output)
+                    .expect("Failed to refresh chip8");
+            },
+            KeyCode::F8 => {
+                let chip8_output = self.chip8.step_out()
+                    .expect("Failed to step out chip8");
 
-            KeyCode::Z => self.chip8.press_key(0xA),
-            KeyCode::X => self.chip8.press_key(0x0),
-            KeyCode::C => self.chip8.press_key(0xB),
-            KeyCode::V => self.chip8.press_key(0xF),
+                self.refresh_chip8_after_step(ctx, chip8_output)
+                    .expect("Failed to refresh chip8");
+            },
 
             _ => {}
         }
 
+        ChipperUI::keypad_key_down(&mut self.chip8, keycode, repeat);
+
         match (keymods, keycode) {
             (KeyMods::SHIFT, KeyCode::F1) => println!("{:?}", self.chip8.gpu),
             _ => {}
@@ -142,28 +322,8 @@ impl EventHandler for ChipperUI {
     }
 
     fn key_up_event(&mut self, _ctx: &mut ggez::Context, keycode: KeyCode, _keymods: KeyMods) {
-        match keycode {
-            KeyCode::Key1 => self.chip8.release_key(0x1),
-            KeyCode::Key2 => self.chip8.release_key(0x2),
-            KeyCode::Key3 => self.chip8.release_key(0x3),
-            KeyCode::Key4 => self.chip8.release_key(0xC),
-
-            KeyCode::Q => self.chip8.release_key(0x4),
-            KeyCode::W => self.chip8.release_key(0x5),
-            KeyCode::E => self.chip8.release_key(0x6),
-            KeyCode::R => self.chip8.release_key(0xD),
-
-            KeyCode::A => self.chip8.release_key(0x7),
-            KeyCode::S => self.chip8.release_key(0x8),
-            KeyCode::D => self.chip8.release_key(0x9),
-            KeyCode::F => self.chip8.release_key(0xE),
-
-            KeyCode::Z => self.chip8.release_key(0xA),
-            KeyCode::X => self.chip8.release_key(0x0),
-            KeyCode::C => self.chip8.release_key(0xB),
-            KeyCode::V => self.chip8.release_key(0xF),
-
-            _ => {}
+        if let Some(key) = ChipperUI::keypad_key(keycode) {
+            self.chip8.release_key(key);
         }
     }
 
@@ -172,6 +332,7 @@ impl EventHandler for ChipperUI {
         let chip8_output = self.chip8.tick(delta_time)
             .expect("Failed to tick chip8");
         self.refresh_chip8(ctx, chip8_output)?;
+        self.keypad_display.update(ctx, &self.chip8)?;
 
         Ok(())
     }
@@ -179,20 +340,49 @@ impl EventHandler for ChipperUI {
     fn draw(&mut self, ctx: &mut ggez::Context) -> GameResult<()> {
         graphics::clear(ctx, graphics::BLACK);
 
+        // Only the latest display state is shown, so rebuild the texture at most once per frame
+        // no matter how many times the program drew since the last one
+        if self.chip8.needs_redraw() {
+            self.chip8_display.update(ctx, &self.chip8);
+            self.chip8.clear_redraw_flag();
+        }
+
         self.chip8_display.draw(ctx)?;
         self.assembly_window.draw(ctx)?;
         self.help_display.draw(ctx)?;
+        self.keypad_display.draw(ctx)?;
         self.register_display.draw(ctx)?;
 
         graphics::present(ctx)?;
 
-        // We don't need to run faster then the chip8 clock speed and
-        // we can tolerate longer sleeps by simulating multiple cycles
-        // in the same step.
-        //
-        // This means we can rely on sleep to help avoid hammering the CPU
-        thread::sleep(self.chip8.clock_speed);
+        // Nothing changes on screen faster than 60fps so sleep off the rest of the frame to avoid
+        // hammering the CPU. `tick` catches up on every cycle that was due while we slept, so the
+        // Chip-8 still runs at `clock_speed`.
+        if let Some(remaining) = ChipperUI::FRAME_TIME.checked_sub(self.frame_start.elapsed()) {
+            thread::sleep(remaining);
+        }
+        self.frame_start = Instant::now();
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Opcode;
+
+    #[test]
+    pub fn keypad_key_down_ignores_auto_repeat() {
+        let mut chip8 = Chip8::new().with_input_buffering(true);
+        chip8.load_rom(&Opcode::to_rom(vec![Opcode::Jump(Chip8::PROGRAM_START)])).unwrap();
+
+        ChipperUI::keypad_key_down(&mut chip8, KeyCode::Q, false);
+        chip8.release_key(0x4);
+        chip8.tick(chip8.clock_speed).unwrap();
+
+        // A late repeat of the released key must not be buffered as a fresh press
+        ChipperUI::keypad_key_down(&mut chip8, KeyCode::Q, true);
+        assert_eq!(chip8.key_pressed_at(0x4), Some(0));
+    }
+}
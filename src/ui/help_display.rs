@@ -13,7 +13,7 @@ impl HelpDisplay {
     pub const WIDTH: f32 = 15.0 * HelpDisplay::SCALE;
     pub const HEIGHT: f32 = 15.6 * HelpDisplay::SCALE;
 
-    const LINE_HEIGHT: f32 = 1.2 * HelpDisplay::SCALE;
+    pub const LINE_HEIGHT: f32 = 1.2 * HelpDisplay::SCALE;
     const FONT_SIZE: f32 = 1.6 * HelpDisplay::SCALE;
 
     pub fn new(assets: &Assets, x: f32, y: f32) -> HelpDisplay {
@@ -24,8 +24,8 @@ impl HelpDisplay {
             "",
             "F2 = Load ROM",
             "F5 = Pause/Resume Game",
-            "F6 = Step (When Paused)",
-            "",
+            "F6/F7/F8 = Step/Over/Out",
+            "F10/F11 = Save/Load State",
             "                 Controls",
             "       KEYBD                CHIP8",
             "       1  2 3 4    ==>    1  2 3 C",
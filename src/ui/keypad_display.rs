@@ -0,0 +1,93 @@
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, Text, DrawParam, DrawMode, FilterMode, Rect, Mesh, MeshBuilder, Color};
+
+use crate::chip8::Chip8;
+use crate::ui::{Assets, Chip8Display, Point2};
+
+/// Displays the Chip-8 hex keypad, highlighting the keys that are currently held.
+pub struct KeypadDisplay {
+    /// The horizontal position of this display relative to the main window
+    x: f32,
+
+    /// The vertical position of this display relative to the main window
+    y: f32,
+
+    text: Vec<(Point2, Text)>,
+
+    /// A filled square behind every held key, `None` if no keys are held
+    key_highlight: Option<Mesh>,
+}
+
+impl KeypadDisplay {
+    pub const SCALE: f32 = Chip8Display::SCALE;
+    pub const WIDTH: f32 = 4.0 * KeypadDisplay::KEY_SIZE;
+    pub const HEIGHT: f32 = 4.0 * KeypadDisplay::KEY_SIZE;
+
+    const KEY_SIZE: f32 = 1.2 * KeypadDisplay::SCALE;
+    const FONT_SIZE: f32 = 1.6 * KeypadDisplay::SCALE;
+    const TEXT_X_OFFSET: f32 = 0.3 * KeypadDisplay::SCALE;
+
+    /// The keys in the order they appear on the original COSMAC VIP keypad
+    const LAYOUT: [[u8; 4]; 4] = [
+        [0x1, 0x2, 0x3, 0xC],
+        [0x4, 0x5, 0x6, 0xD],
+        [0x7, 0x8, 0x9, 0xE],
+        [0xA, 0x0, 0xB, 0xF],
+    ];
+
+    pub fn new(assets: &Assets, x: f32, y: f32) -> KeypadDisplay {
+        let mut text = Vec::new();
+        for key in 0..16 {
+            let key_pos = KeypadDisplay::key_position(x, y, key);
+            let key_pos = Point2::new(key_pos.x + KeypadDisplay::TEXT_X_OFFSET, key_pos.y);
+            let key_text = Text::new((format!("{:X}", key), assets.debug_font, KeypadDisplay::FONT_SIZE));
+
+            text.push((key_pos, key_text));
+        }
+
+        KeypadDisplay { x, y, text, key_highlight: None }
+    }
+
+    pub fn update(&mut self, ctx: &mut Context, chip8: &Chip8) -> GameResult<()> {
+        let mut builder = MeshBuilder::new();
+        let mut any_pressed = false;
+        for key in chip8.keys_pressed() {
+            let key_pos = KeypadDisplay::key_position(self.x, self.y, key);
+            let rect = Rect::new(key_pos.x, key_pos.y, KeypadDisplay::KEY_SIZE, KeypadDisplay::KEY_SIZE);
+            builder.rectangle(DrawMode::fill(), rect, Color::from_rgb(0xFF, 0x00, 0x00));
+            any_pressed = true;
+        }
+
+        // Building an empty mesh is an error so we skip it when nothing is held
+        self.key_highlight = if any_pressed { Some(builder.build(ctx)?) } else { None };
+
+        Ok(())
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        if let Some(key_highlight) = &self.key_highlight {
+            graphics::draw(ctx, key_highlight, DrawParam::default())?;
+        }
+
+        for (position, text) in &self.text {
+            graphics::queue_text(ctx, text, *position, Some(graphics::WHITE));
+        }
+        graphics::draw_queued_text(ctx, DrawParam::default(), None, FilterMode::Nearest)?;
+
+        Ok(())
+    }
+
+    // The top left corner of `key` on a keypad drawn at `(x, y)`
+    fn key_position(x: f32, y: f32, key: u8) -> Point2 {
+        for (row, keys) in KeypadDisplay::LAYOUT.iter().enumerate() {
+            if let Some(col) = keys.iter().position(|k| *k == key) {
+                return Point2::new(
+                    x + col as f32 * KeypadDisplay::KEY_SIZE,
+                    y + row as f32 * KeypadDisplay::KEY_SIZE
+                );
+            }
+        }
+
+        unreachable!("{:X} is not a Chip-8 key", key)
+    }
+}
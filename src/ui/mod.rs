@@ -4,13 +4,17 @@ mod assembly_display;
 mod assets;
 mod register_display;
 mod help_display;
+mod keypad_display;
+mod text_cache;
 
-pub use self::chipper_ui::ChipperUI;
+pub use self::chipper_ui::{ChipperUI, RomLoader};
 pub use self::chip8_display::Chip8Display;
 pub use self::assembly_display::AssemblyDisplay;
 pub use self::register_display::RegisterDisplay;
 pub use self::help_display::HelpDisplay;
+pub use self::keypad_display::KeypadDisplay;
 pub use self::assets::Assets;
+pub use self::text_cache::TextCache;
 
 use nalgebra;
 
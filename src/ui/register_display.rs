@@ -1,8 +1,8 @@
 use ggez::{Context, GameResult};
-use ggez::graphics::{self, Text, DrawParam, FilterMode};
+use ggez::graphics::{self, DrawParam, FilterMode};
 
 use crate::chip8::Chip8;
-use crate::ui::{Assets, Chip8Display, Point2};
+use crate::ui::{Assets, Chip8Display, Point2, TextCache};
 
 pub struct RegisterDisplay {
     /// The horizontal position of this display relative to the main window
@@ -11,7 +11,10 @@ pub struct RegisterDisplay {
     /// The vertical position of this display relative to the main window
     y: f32,
 
-    text: Vec<(Point2, Text)>
+    text: Vec<(Point2, String)>,
+
+    /// The `Text` for each string in `text`, so registers that didn't change keep their `Text`
+    text_cache: TextCache,
 }
 
 impl RegisterDisplay {
@@ -29,15 +32,15 @@ impl RegisterDisplay {
     const VALUE_X_OFFSET: f32 = 1.5 * RegisterDisplay::SCALE;
 
     pub fn new(x: f32, y: f32) -> RegisterDisplay {
-        RegisterDisplay { x, y, text: Vec::new() }
+        RegisterDisplay { x, y, text: Vec::new(), text_cache: TextCache::new() }
     }
 
     pub fn update(&mut self, assets: &Assets, chip8: &Chip8) -> GameResult<()> {
         self.text.clear();
+        self.text_cache.start_frame();
 
         let header_pos = Point2::new(self.x + 50.0, self.y);
-        let header_text = Text::new(("Registers".to_string(), assets.debug_font, RegisterDisplay::FONT_SIZE));
-        self.text.push((header_pos, header_text));
+        self.push_text(assets, header_pos, "Registers".to_string());
 
         // Show `PC` and `I`
         self.push_line_col(assets, 0, 2, "PC".to_string(), format!("{:03X}", chip8.pc));
@@ -69,18 +72,21 @@ impl RegisterDisplay {
         let sep_pos = Point2::new(sep_x, line_y);
         let value_pos = Point2::new(value_x, line_y);
 
-        let key_text = Text::new((key, assets.debug_font, RegisterDisplay::FONT_SIZE));
-        let sep_text = Text::new(("=", assets.debug_font, RegisterDisplay::FONT_SIZE));
-        let value_text = Text::new((value, assets.debug_font, RegisterDisplay::FONT_SIZE));
+        self.push_text(assets, key_pos, key);
+        self.push_text(assets, sep_pos, "=".to_string());
+        self.push_text(assets, value_pos, value);
+    }
 
-        self.text.push((key_pos, key_text));
-        self.text.push((sep_pos, sep_text));
-        self.text.push((value_pos, value_text));
+    fn push_text(&mut self, assets: &Assets, position: Point2, text: String) {
+        self.text_cache.insert(&text, assets.debug_font, RegisterDisplay::FONT_SIZE);
+        self.text.push((position, text));
     }
 
     pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
         for (position, text) in &self.text {
-            graphics::queue_text(ctx, text, *position, Some(graphics::WHITE));
+            if let Some(text) = self.text_cache.get(text) {
+                graphics::queue_text(ctx, text, *position, Some(graphics::WHITE));
+            }
         }
         graphics::draw_queued_text(ctx, DrawParam::default(), None, FilterMode::Nearest)?;
 
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use ggez::graphics::{Font, Text};
+
+/// Keeps the `Text` built for each string a display draws, so text that didn't change since the
+/// last frame is reused rather than rebuilt.
+///
+/// Call `start_frame` before adding the text of each frame with `insert`. Text that isn't
+/// inserted again during a frame is dropped when the next frame starts, so the cache only ever
+/// holds about one frame of text.
+///
+/// Entries are keyed by their string alone, so a cache should only be used with one font and size.
+pub struct TextCache {
+    /// Text inserted during the current frame
+    current: HashMap<String, Text>,
+
+    /// Text inserted during the previous frame, which is moved into `current` when reused
+    previous: HashMap<String, Text>,
+}
+
+impl TextCache {
+    pub fn new() -> TextCache {
+        TextCache { current: HashMap::new(), previous: HashMap::new() }
+    }
+
+    pub fn start_frame(&mut self) {
+        std::mem::swap(&mut self.current, &mut self.previous);
+        self.current.clear();
+    }
+
+    /// Make the `Text` for `content` available from `get` this frame.
+    ///
+    /// Returns true if the `Text` had to be built, or false if it was reused.
+    pub fn insert(&mut self, content: &str, font: Font, size: f32) -> bool {
+        if self.current.contains_key(content) {
+            return false;
+        }
+
+        match self.previous.remove_entry(content) {
+            Some((content, text)) => {
+                self.current.insert(content, text);
+                false
+            },
+            None => {
+                self.current.insert(content.to_string(), Text::new((content, font, size)));
+                true
+            },
+        }
+    }
+
+    /// The `Text` for `content`, if it was inserted this frame
+    pub fn get(&self, content: &str) -> Option<&Text> {
+        self.current.get(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn unchanged_text_is_reused() {
+        let mut cache = TextCache::new();
+        let font = Font::default();
+
+        cache.start_frame();
+        assert!(cache.insert("V0", font, 16.0));
+        assert!(cache.insert("00", font, 16.0));
+        assert!(!cache.insert("00", font, 16.0));
+
+        // `V0` is unchanged but its value is now `01`
+        cache.start_frame();
+        assert!(!cache.insert("V0", font, 16.0));
+        assert!(cache.insert("01", font, 16.0));
+        assert!(cache.get("V0").is_some());
+
+        // `00` wasn't used last frame so it was dropped
+        cache.start_frame();
+        assert!(cache.get("01").is_none());
+        assert!(cache.insert("00", font, 16.0));
+    }
+}